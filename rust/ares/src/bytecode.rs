@@ -0,0 +1,2071 @@
+/** Bytecode compiler and VM for linearized Nock
+ *
+ * The linearizer in hoon/codegen/lib/line.hoon produces a `town`: for each arm (a formula
+ * together with what is known about its subject), a table of basic blocks over SSA values. We
+ * compile each arm to a compact bytecode, following docs/codegen-bootstrap.md, and run it on
+ * the NockStack as an alternative to the tree-walking interpreter.
+ *
+ * Instructions are bit-packed, least significant bit first, and each begins on a byte
+ * boundary. An instruction is a 5 bit opcode followed by its operands:
+ *
+ * - A location is a 0 bit and a 3 bit register `r0`-`r7`, or a 1 bit and a number naming a
+ *   stack slot. Unlike the design doc we do not load spilled values into registers: registers
+ *   and slots are both frame locals, and registers are just the short encoding.
+ * - A number is a 0 bit and 13 bits, or a 1 bit and 48 bits.
+ * - An internal label is a 0 bit for fallthrough, or a 1 bit and a number giving a byte
+ *   offset forward from the end of the instruction. Blocks are laid out in topological order,
+ *   so every internal jump is forward.
+ * - An external label is a 45 bit pointer to an [Arm], shifted right by 3, and a bit selecting
+ *   the entry with the whole subject in one location (`dole`) or with the subject split into
+ *   its used axes (`vent`). Arms are boxed and never freed while the table is live.
+ * - Calls take a number of arguments and that many locations, rather than passing them in
+ *   `r0`-`r7`.
+ *
+ * `don` (0x15) returns from an arm; the design doc does not give it an opcode. Arms using `hnt`
+ * are not compiled: the linearizer gives a hint no way to see the result it hints, which
+ * %fast and %memo need. Neither are jetted arms, since a registerized call never materializes
+ * the core the jet dashboard would match on. Such arms are kept uncompiled, and compiled code
+ * calling one runs it with [interpret], or its jet, on a subject rebuilt from the sock and the
+ * arguments.
+ *
+ * A crash in compiled code is rerun by the interpreter, with compiled code suspended, so that
+ * it carries the trace the arm's hints would have given.
+ *
+ * The town comes from the `+town` generator in hoon/codegen/gen/town.hoon, which runs the
+ * linearizer on a ship; the serf loads the jammed output named by `ARES_TOWN`.
+ */
+use crate::hamt::{Hamt, MutHamt};
+use crate::interpreter::{self, inc, interpret, Context, Mote};
+use crate::jets::warm::Warm;
+use crate::mem::{NockStack, Preserve};
+use crate::noun::{self, Cell, Noun, Slots, D, T};
+use crate::serf::{interrupted, DEADLINE_PASSED, TERMINATOR};
+use crate::trace::TraceStack;
+use crate::unifying_equality::unifying_equality;
+use ares_macros::tas;
+use bitvec::prelude::{BitSlice, Lsb0};
+use std::collections::HashMap;
+use std::ptr::{null, read_unaligned};
+use std::result;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+crate::gdb!();
+
+const OP_IMM: u64 = 0x01;
+const OP_MOV: u64 = 0x02;
+const OP_INC: u64 = 0x03;
+const OP_UNC: u64 = 0x04;
+const OP_CON: u64 = 0x05;
+const OP_HED: u64 = 0x06;
+const OP_TAL: u64 = 0x07;
+const OP_HUD: u64 = 0x08;
+const OP_TUL: u64 = 0x09;
+const OP_CLQ: u64 = 0x0A;
+const OP_EQQ: u64 = 0x0B;
+const OP_BRN: u64 = 0x0C;
+const OP_HOP: u64 = 0x0D;
+const OP_CAL: u64 = 0x0E;
+const OP_LNK: u64 = 0x0F;
+const OP_JMP: u64 = 0x10;
+const OP_LNT: u64 = 0x11;
+const OP_SPY: u64 = 0x12;
+const OP_BOM: u64 = 0x14;
+const OP_DON: u64 = 0x15;
+
+const REGISTERS: usize = 8;
+
+// Frame locals 0 and 1 are the mean and trace stacks, as for interpreter frames. The return
+// record follows, then registers and stack slots.
+const FRAME_RET_ARM: usize = 2;
+const FRAME_RET_PC: usize = 3;
+const FRAME_RET_DEST: usize = 4;
+const FRAME_LOCALS: usize = 5;
+
+/// Most arguments a call may pass; they are staged in a fixed buffer
+const MAX_ARGS: usize = 64;
+
+#[derive(Debug)]
+pub enum Error {
+    BadTown,
+    Unsupported,
+}
+
+impl From<noun::Error> for Error {
+    fn from(_: noun::Error) -> Self {
+        Error::BadTown
+    }
+}
+
+type CResult<T> = result::Result<T, Error>;
+
+/// Compiled code for one arm
+pub struct Arm {
+    sub: Noun,
+    formula: Noun,
+    code: Vec<u8>,
+    consts: Vec<Noun>,
+    vent: usize,
+    dole: usize,
+    /// Locations of the subject axes (in pool order) which are live on `vent` entry
+    vent_args: Vec<Option<usize>>,
+    dole_arg: Option<usize>,
+    /// Whether the arm has code. Calls to an arm without are run by the interpreter.
+    compiled: bool,
+    /// Subject axes passed on `vent` entry, in pool order
+    pool: Vec<Noun>,
+    /// Next arm with the same formula but a different subject
+    next: *const Arm,
+}
+
+#[derive(Copy, Clone)]
+struct ArmRef(*const Arm);
+
+impl Preserve for ArmRef {
+    unsafe fn assert_in_stack(&self, _stack: &NockStack) {}
+    unsafe fn preserve(&mut self, _stack: &mut NockStack) {}
+}
+
+/// Table of compiled arms, keyed by formula
+pub struct Code {
+    table: Hamt<ArmRef>,
+    // Boxed so that bytecode can point at arms
+    #[allow(clippy::vec_box)]
+    arms: Vec<Box<Arm>>,
+    /// Registers and stack slots in every VM frame
+    locals: usize,
+    runs: u64,
+    /// Set while a crashed arm is rerun, so that the interpreter does not run compiled code
+    suspended: bool,
+}
+
+impl Preserve for Code {
+    unsafe fn assert_in_stack(&self, stack: &NockStack) {
+        self.table.assert_in_stack(stack);
+        for arm in &self.arms {
+            arm.sub.assert_in_stack(stack);
+            arm.formula.assert_in_stack(stack);
+            for noun in arm.consts.iter().chain(&arm.pool) {
+                noun.assert_in_stack(stack);
+            }
+        }
+    }
+
+    unsafe fn preserve(&mut self, stack: &mut NockStack) {
+        self.table.preserve(stack);
+        for arm in self.arms.iter_mut() {
+            arm.sub.preserve(stack);
+            arm.formula.preserve(stack);
+            for noun in arm.consts.iter_mut().chain(arm.pool.iter_mut()) {
+                noun.preserve(stack);
+            }
+        }
+    }
+}
+
+impl Code {
+    pub fn new(stack: &mut NockStack) -> Self {
+        Code {
+            table: Hamt::new(stack),
+            arms: Vec::new(),
+            locals: REGISTERS,
+            runs: 0,
+            suspended: false,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of arms compiled
+    pub fn len(&self) -> usize {
+        self.arms.iter().filter(|arm| arm.compiled).count()
+    }
+
+    /// Number of times compiled code has been entered from the interpreter
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    /// Compile the arms of a linearizer `town` and add them to the table, returning how many
+    /// were compiled. Arms which cannot be compiled, and jetted arms, are left to the
+    /// interpreter: compiled code calling them runs them with [interpret], or their jet. The
+    /// town must live in the same frame as the table or a senior one.
+    pub fn install(&mut self, stack: &mut NockStack, warm: &Warm, town: Noun) -> CResult<usize> {
+        let entries = map_entries(town.as_cell()?.head())?;
+        let mut shells: Vec<Box<Arm>> = Vec::with_capacity(entries.len());
+        for (barn, sack) in &entries {
+            let cell = barn.as_cell()?;
+            shells.push(Box::new(Arm {
+                sub: cell.head(),
+                formula: cell.tail(),
+                code: Vec::new(),
+                consts: Vec::new(),
+                vent: 0,
+                dole: 0,
+                vent_args: Vec::new(),
+                dole_arg: None,
+                compiled: false,
+                pool: decode_pool(*sack)?,
+                next: null(),
+            }));
+        }
+        let ptrs: Vec<*const Arm> = shells.iter().map(|arm| &**arm as *const Arm).collect();
+
+        stack.frame_push(0);
+        let barns = MutHamt::<usize>::new(stack);
+        for (i, (barn, _sack)) in entries.iter().enumerate() {
+            let mut barn = *barn;
+            barns.insert(stack, &mut barn, i);
+        }
+        let mut compiled = Vec::with_capacity(entries.len());
+        for (shell, (barn, sack)) in shells.iter().zip(&entries) {
+            let mut formula = shell.formula;
+            compiled.push(if warm.is_jetted(stack, &mut formula) {
+                Err(Error::Unsupported)
+            } else {
+                decode_arm(stack, barns, *barn, *sack).and_then(|linear| compile(&linear, &ptrs))
+            });
+        }
+        unsafe { stack.frame_pop() };
+
+        let mut installed = 0;
+        for (mut shell, c) in shells.into_iter().zip(compiled) {
+            if let Ok(compiled) = c {
+                self.locals = self.locals.max(REGISTERS + compiled.slots);
+                shell.code = compiled.code;
+                shell.consts = compiled.consts;
+                shell.vent = compiled.vent;
+                shell.dole = compiled.dole;
+                shell.vent_args = compiled.vent_args;
+                shell.dole_arg = compiled.dole_arg;
+                shell.compiled = true;
+
+                let mut formula = shell.formula;
+                shell.next = self
+                    .table
+                    .lookup(stack, &mut formula)
+                    .map_or(null(), |arm| arm.0);
+                self.table = self
+                    .table
+                    .insert(stack, &mut formula, ArmRef(&*shell as *const Arm));
+                installed += 1;
+            }
+            self.arms.push(shell);
+        }
+        Ok(installed)
+    }
+
+    /// Find compiled code for this formula whose subject knowledge matches the subject
+    pub fn find(&self, stack: &mut NockStack, subject: Noun, formula: Noun) -> Option<*const Arm> {
+        if self.arms.is_empty() || self.suspended {
+            return None;
+        }
+        let mut formula = formula;
+        let mut arm = self.table.lookup(stack, &mut formula)?.0;
+        unsafe {
+            while !arm.is_null() {
+                if sock_matches(stack, (*arm).sub, subject) {
+                    return Some(arm);
+                }
+                arm = (*arm).next;
+            }
+        }
+        None
+    }
+}
+
+fn sock_matches(stack: &mut NockStack, sock: Noun, subject: Noun) -> bool {
+    let Ok(sock) = sock.as_cell() else {
+        return false;
+    };
+    let Ok(tag) = sock.head().as_direct() else {
+        return false;
+    };
+    match tag.data() {
+        tas!(b"know") => {
+            let mut know = sock.tail();
+            let mut subject = subject;
+            unsafe { unifying_equality(stack, &mut know, &mut subject) }
+        }
+        tas!(b"bets") => {
+            let (Ok(knows), Ok(subject)) = (sock.tail().as_cell(), subject.as_cell()) else {
+                return false;
+            };
+            sock_matches(stack, knows.head(), subject.head())
+                && sock_matches(stack, knows.tail(), subject.tail())
+        }
+        tas!(b"dice") => subject.is_atom(),
+        tas!(b"flip") => unsafe { subject.raw_equals(D(0)) || subject.raw_equals(D(1)) },
+        tas!(b"toss") => true,
+        _ => false,
+    }
+}
+
+//
+// Decoding the linearizer's output
+//
+
+type Ssa = usize;
+
+enum Bran {
+    Imm(usize, Ssa),
+    Mov(Ssa, Ssa),
+    Una(u64, Ssa, Ssa),
+    Con(Ssa, Ssa, Ssa),
+}
+
+/// Block terminators. Labels are block indices; calls name arms by index in the town.
+enum Germ {
+    Clq(Ssa, usize, usize),
+    Eqq(Ssa, Ssa, usize, usize),
+    Brn(Ssa, usize, usize),
+    Hop(usize),
+    Lnk(Ssa, Ssa, Ssa, usize),
+    Cal(usize, bool, Vec<Ssa>, Ssa, usize),
+    Jmp(usize, bool, Vec<Ssa>),
+    Lnt(Ssa, Ssa),
+    Spy(Ssa, Ssa, Ssa, usize),
+    Don(Ssa),
+    Bom,
+}
+
+impl Bran {
+    fn uses(&self) -> Vec<Ssa> {
+        match *self {
+            Bran::Imm(_, _) => vec![],
+            Bran::Mov(s, _) | Bran::Una(_, s, _) => vec![s],
+            Bran::Con(h, t, _) => vec![h, t],
+        }
+    }
+
+    fn def(&self) -> Ssa {
+        match *self {
+            Bran::Imm(_, d) | Bran::Mov(_, d) | Bran::Una(_, _, d) | Bran::Con(_, _, d) => d,
+        }
+    }
+}
+
+impl Germ {
+    fn uses(&self) -> Vec<Ssa> {
+        match self {
+            Germ::Clq(s, _, _) | Germ::Brn(s, _, _) | Germ::Don(s) => vec![*s],
+            Germ::Eqq(a, b, _, _) | Germ::Lnk(a, b, _, _) | Germ::Spy(a, b, _, _) => {
+                vec![*a, *b]
+            }
+            Germ::Lnt(f, s) => vec![*f, *s],
+            Germ::Cal(_, _, args, _, _) | Germ::Jmp(_, _, args) => args.clone(),
+            Germ::Hop(_) | Germ::Bom => vec![],
+        }
+    }
+
+    fn def(&self) -> Option<Ssa> {
+        match self {
+            Germ::Lnk(_, _, d, _) | Germ::Cal(_, _, _, d, _) | Germ::Spy(_, _, d, _) => Some(*d),
+            _ => None,
+        }
+    }
+
+    fn succs(&self) -> Vec<usize> {
+        match self {
+            Germ::Clq(_, y, n) | Germ::Eqq(_, _, y, n) | Germ::Brn(_, y, n) => vec![*y, *n],
+            Germ::Hop(l)
+            | Germ::Lnk(_, _, _, l)
+            | Germ::Cal(_, _, _, _, l)
+            | Germ::Spy(_, _, _, l) => vec![*l],
+            Germ::Jmp(..) | Germ::Lnt(..) | Germ::Don(_) | Germ::Bom => vec![],
+        }
+    }
+}
+
+struct Block {
+    body: Vec<Bran>,
+    end: Germ,
+}
+
+/// An arm's basic blocks, with SSA values renumbered densely
+struct Linear {
+    blocks: Vec<Block>,
+    vent: usize,
+    dole: usize,
+    pool: Vec<Ssa>,
+    lump: Ssa,
+    consts: Vec<Noun>,
+    ssas: usize,
+}
+
+/// Entries of a Hoon map, in no particular order
+fn map_entries(map: Noun) -> CResult<Vec<(Noun, Noun)>> {
+    let mut entries = Vec::new();
+    let mut todo = vec![map];
+    while let Some(node) = todo.pop() {
+        if node.is_atom() {
+            if unsafe { !node.raw_equals(D(0)) } {
+                return Err(Error::BadTown);
+            }
+            continue;
+        }
+        let entry = node.slot(2)?.as_cell()?;
+        entries.push((entry.head(), entry.tail()));
+        todo.push(node.slot(6)?);
+        todo.push(node.slot(7)?);
+    }
+    Ok(entries)
+}
+
+fn list_items(mut list: Noun) -> CResult<Vec<Noun>> {
+    let mut items = Vec::new();
+    while let Ok(cell) = list.as_cell() {
+        items.push(cell.head());
+        list = cell.tail();
+    }
+    if unsafe { !list.raw_equals(D(0)) } {
+        return Err(Error::BadTown);
+    }
+    Ok(items)
+}
+
+struct Decoder {
+    labels: MutHamt<usize>,
+    barns: MutHamt<usize>,
+    ssas: HashMap<u64, Ssa>,
+    consts: Vec<Noun>,
+}
+
+impl Decoder {
+    fn ssa(&mut self, noun: Noun) -> CResult<Ssa> {
+        let n = noun.as_atom()?.as_u64()?;
+        let next = self.ssas.len();
+        Ok(*self.ssas.entry(n).or_insert(next))
+    }
+
+    fn ssas(&mut self, list: Noun) -> CResult<Vec<Ssa>> {
+        list_items(list)?
+            .into_iter()
+            .map(|ssa| self.ssa(ssa))
+            .collect()
+    }
+
+    fn label(&mut self, stack: &mut NockStack, mut berm: Noun) -> CResult<usize> {
+        self.labels.lookup(stack, &mut berm).ok_or(Error::BadTown)
+    }
+
+    fn callee(&mut self, stack: &mut NockStack, mut barn: Noun) -> CResult<usize> {
+        self.barns
+            .lookup(stack, &mut barn)
+            .ok_or(Error::Unsupported)
+    }
+
+    fn bran(&mut self, bran: Noun) -> CResult<Bran> {
+        let bran = bran.as_cell()?;
+        let args = bran.tail();
+        let una = |op| -> CResult<(u64, Noun, Noun)> { Ok((op, args.slot(2)?, args.slot(3)?)) };
+        let (op, s, d) = match bran.head().as_direct()?.data() {
+            tas!(b"imm") => {
+                self.consts.push(args.slot(2)?);
+                let d = self.ssa(args.slot(3)?)?;
+                return Ok(Bran::Imm(self.consts.len() - 1, d));
+            }
+            tas!(b"con") => {
+                return Ok(Bran::Con(
+                    self.ssa(args.slot(2)?)?,
+                    self.ssa(args.slot(6)?)?,
+                    self.ssa(args.slot(7)?)?,
+                ));
+            }
+            tas!(b"mov") => {
+                return Ok(Bran::Mov(
+                    self.ssa(args.slot(2)?)?,
+                    self.ssa(args.slot(3)?)?,
+                ));
+            }
+            tas!(b"inc") => una(OP_INC)?,
+            tas!(b"unc") => una(OP_UNC)?,
+            tas!(b"hed") => una(OP_HED)?,
+            tas!(b"tal") => una(OP_TAL)?,
+            tas!(b"hud") => una(OP_HUD)?,
+            tas!(b"tul") => una(OP_TUL)?,
+            _ => return Err(Error::BadTown),
+        };
+        Ok(Bran::Una(op, self.ssa(s)?, self.ssa(d)?))
+    }
+
+    fn germ(&mut self, stack: &mut NockStack, germ: Noun) -> CResult<Germ> {
+        let germ = germ.as_cell()?;
+        let a = germ.tail();
+        Ok(match germ.head().as_direct()?.data() {
+            tas!(b"clq") => Germ::Clq(
+                self.ssa(a.slot(2)?)?,
+                self.label(stack, a.slot(6)?)?,
+                self.label(stack, a.slot(7)?)?,
+            ),
+            tas!(b"eqq") => Germ::Eqq(
+                self.ssa(a.slot(2)?)?,
+                self.ssa(a.slot(6)?)?,
+                self.label(stack, a.slot(14)?)?,
+                self.label(stack, a.slot(15)?)?,
+            ),
+            tas!(b"brn") => Germ::Brn(
+                self.ssa(a.slot(2)?)?,
+                self.label(stack, a.slot(6)?)?,
+                self.label(stack, a.slot(7)?)?,
+            ),
+            tas!(b"hop") => Germ::Hop(self.label(stack, a)?),
+            tas!(b"lnk") => Germ::Lnk(
+                self.ssa(a.slot(2)?)?,
+                self.ssa(a.slot(6)?)?,
+                self.ssa(a.slot(14)?)?,
+                self.label(stack, a.slot(15)?)?,
+            ),
+            tas!(b"cal") => Germ::Cal(
+                self.callee(stack, a.slot(2)?)?,
+                false,
+                self.ssas(a.slot(6)?)?,
+                self.ssa(a.slot(14)?)?,
+                self.label(stack, a.slot(15)?)?,
+            ),
+            tas!(b"bec") => Germ::Cal(
+                self.callee(stack, a.slot(2)?)?,
+                true,
+                vec![self.ssa(a.slot(6)?)?],
+                self.ssa(a.slot(14)?)?,
+                self.label(stack, a.slot(15)?)?,
+            ),
+            tas!(b"lnt") => Germ::Lnt(self.ssa(a.slot(2)?)?, self.ssa(a.slot(3)?)?),
+            tas!(b"jmp") => {
+                // The entry blocks jump to a local label rather than an arm
+                let mut berm = a;
+                if let Some(label) = self.labels.lookup(stack, &mut berm) {
+                    Germ::Hop(label)
+                } else {
+                    Germ::Jmp(
+                        self.callee(stack, a.slot(2)?)?,
+                        false,
+                        self.ssas(a.slot(3)?)?,
+                    )
+                }
+            }
+            tas!(b"eye") => Germ::Jmp(
+                self.callee(stack, a.slot(2)?)?,
+                true,
+                vec![self.ssa(a.slot(3)?)?],
+            ),
+            tas!(b"spy") => Germ::Spy(
+                self.ssa(a.slot(2)?)?,
+                self.ssa(a.slot(6)?)?,
+                self.ssa(a.slot(14)?)?,
+                self.label(stack, a.slot(15)?)?,
+            ),
+            tas!(b"hnt") => return Err(Error::Unsupported),
+            tas!(b"don") => Germ::Don(self.ssa(a)?),
+            tas!(b"bom") => Germ::Bom,
+            _ => return Err(Error::BadTown),
+        })
+    }
+}
+
+/// Subject axes an arm is passed on `vent` entry, from its pool
+fn decode_pool(sack: Noun) -> CResult<Vec<Noun>> {
+    let rice = sack.as_cell()?.head();
+    list_items(rice.slot(6)?)?
+        .into_iter()
+        .map(|use_| {
+            let axis = use_.slot(2)?;
+            axis.as_atom()?;
+            Ok(axis)
+        })
+        .collect()
+}
+
+fn decode_arm(
+    stack: &mut NockStack,
+    barns: MutHamt<usize>,
+    barn: Noun,
+    sack: Noun,
+) -> CResult<Linear> {
+    let rice = sack.as_cell()?.head();
+    let lake = map_entries(rice.slot(2)?)?;
+    let mut decoder = Decoder {
+        labels: MutHamt::new(stack),
+        barns,
+        ssas: HashMap::new(),
+        consts: Vec::new(),
+    };
+    for (i, (berm, _lock)) in lake.iter().enumerate() {
+        let mut berm = *berm;
+        decoder.labels.insert(stack, &mut berm, i);
+    }
+
+    let mut blocks = Vec::with_capacity(lake.len());
+    for (_berm, lock) in &lake {
+        let lock = lock.as_cell()?;
+        let body = list_items(lock.head())?
+            .into_iter()
+            .map(|bran| decoder.bran(bran))
+            .collect::<CResult<Vec<_>>>()?;
+        let end = decoder.germ(stack, lock.tail())?;
+        blocks.push(Block { body, end });
+    }
+
+    let barn = barn.as_cell()?;
+    let vent = T(stack, &[barn.head(), barn.tail(), D(1), D(tas!(b"vent"))]);
+    let dole = T(stack, &[barn.head(), barn.tail(), D(1), D(tas!(b"dole"))]);
+    let vent = decoder.label(stack, vent)?;
+    let dole = decoder.label(stack, dole)?;
+    let pool = list_items(rice.slot(6)?)?
+        .into_iter()
+        .map(|use_| decoder.ssa(use_.slot(6)?))
+        .collect::<CResult<Vec<_>>>()?;
+    let lump = decoder.ssa(rice.slot(7)?)?;
+
+    Ok(Linear {
+        blocks,
+        vent,
+        dole,
+        pool,
+        lump,
+        ssas: decoder.ssas.len(),
+        consts: decoder.consts,
+    })
+}
+
+//
+// Compilation
+//
+
+struct Compiled {
+    code: Vec<u8>,
+    consts: Vec<Noun>,
+    vent: usize,
+    dole: usize,
+    vent_args: Vec<Option<usize>>,
+    dole_arg: Option<usize>,
+    slots: usize,
+}
+
+/// Blocks in reverse postorder from the entry points, which is a topological order
+fn layout(linear: &Linear) -> CResult<Vec<usize>> {
+    let mut color = vec![0u8; linear.blocks.len()];
+    let mut post = Vec::with_capacity(linear.blocks.len());
+    for root in [linear.vent, linear.dole] {
+        if color[root] != 0 {
+            continue;
+        }
+        color[root] = 1;
+        let mut todo = vec![(root, linear.blocks[root].end.succs(), 0)];
+        while let Some((block, succs, next)) = todo.last_mut() {
+            if let Some(&succ) = succs.get(*next) {
+                *next += 1;
+                match color[succ] {
+                    0 => {
+                        color[succ] = 1;
+                        todo.push((succ, linear.blocks[succ].end.succs(), 0));
+                    }
+                    // A back edge: the blocks are not a DAG
+                    1 => return Err(Error::BadTown),
+                    _ => {}
+                }
+            } else {
+                color[*block] = 2;
+                post.push(*block);
+                todo.pop();
+            }
+        }
+    }
+    post.reverse();
+    Ok(post)
+}
+
+/// Assign each SSA value a location by linear scan over conservative live intervals.
+/// Returns locations and whether each value is live into each laid-out block.
+fn allocate(linear: &Linear, order: &[usize]) -> CResult<(Vec<usize>, Vec<Vec<bool>>, usize)> {
+    let n = linear.ssas;
+
+    // Instruction positions: uses at 2p, definitions at 2p + 1
+    let mut first = Vec::with_capacity(order.len());
+    let mut p = 0;
+    for &b in order {
+        first.push(p);
+        p += linear.blocks[b].body.len() + 1;
+    }
+    let mut place = vec![usize::MAX; linear.blocks.len()];
+    for (k, &b) in order.iter().enumerate() {
+        place[b] = k;
+    }
+
+    let mut live_in = vec![Vec::new(); order.len()];
+    let mut live_out = vec![Vec::new(); order.len()];
+    for k in (0..order.len()).rev() {
+        let block = &linear.blocks[order[k]];
+        let mut live = vec![false; n];
+        for succ in block.end.succs() {
+            for (v, l) in live_in[place[succ]].iter().enumerate() {
+                live[v] |= *l;
+            }
+        }
+        live_out[k] = live.clone();
+        if let Some(d) = block.end.def() {
+            live[d] = false;
+        }
+        for u in block.end.uses() {
+            live[u] = true;
+        }
+        for bran in block.body.iter().rev() {
+            live[bran.def()] = false;
+            for u in bran.uses() {
+                live[u] = true;
+            }
+        }
+        live_in[k] = live;
+    }
+
+    // Nothing but the entry arguments may be read before it is written
+    let vent = place[linear.vent];
+    let dole = place[linear.dole];
+    let vent_ok = live_in[vent]
+        .iter()
+        .enumerate()
+        .all(|(v, live)| !live || linear.pool.contains(&v));
+    let dole_ok = live_in[dole]
+        .iter()
+        .enumerate()
+        .all(|(v, live)| !live || v == linear.lump);
+    if !vent_ok || !dole_ok {
+        return Err(Error::BadTown);
+    }
+
+    let mut start = vec![usize::MAX; n];
+    let mut end = vec![0; n];
+    let mut extend = |v: Ssa, at: usize| {
+        start[v] = start[v].min(at);
+        end[v] = end[v].max(at);
+    };
+    for (k, &b) in order.iter().enumerate() {
+        let block = &linear.blocks[b];
+        let last = first[k] + block.body.len();
+        for v in 0..n {
+            if live_in[k][v] {
+                extend(v, 2 * first[k]);
+            }
+            if live_out[k][v] {
+                extend(v, 2 * last + 1);
+            }
+        }
+        for (i, bran) in block.body.iter().enumerate() {
+            for u in bran.uses() {
+                extend(u, 2 * (first[k] + i));
+            }
+            extend(bran.def(), 2 * (first[k] + i) + 1);
+        }
+        for u in block.end.uses() {
+            extend(u, 2 * last);
+        }
+        if let Some(d) = block.end.def() {
+            extend(d, 2 * last + 1);
+        }
+    }
+
+    let mut intervals: Vec<(usize, usize, Ssa)> = (0..n)
+        .filter(|v| start[*v] != usize::MAX)
+        .map(|v| (start[v], end[v], v))
+        .collect();
+    intervals.sort_unstable();
+
+    let mut loc = vec![usize::MAX; n];
+    let mut free_regs: Vec<usize> = (0..REGISTERS).rev().collect();
+    let mut free_slots: Vec<usize> = Vec::new();
+    let mut slots = 0;
+    let mut active: Vec<(usize, Ssa)> = Vec::new();
+    let mut spilled: Vec<(usize, Ssa)> = Vec::new();
+    let mut new_slot = |free_slots: &mut Vec<usize>| {
+        free_slots.pop().unwrap_or_else(|| {
+            slots += 1;
+            REGISTERS + slots - 1
+        })
+    };
+    for (s, e, v) in intervals {
+        active.retain(|&(ae, av)| {
+            if ae < s {
+                free_regs.push(loc[av]);
+            }
+            ae >= s
+        });
+        spilled.retain(|&(se, sv)| {
+            if se < s {
+                free_slots.push(loc[sv]);
+            }
+            se >= s
+        });
+        if let Some(reg) = free_regs.pop() {
+            loc[v] = reg;
+            active.push((e, v));
+            continue;
+        }
+        // Spill whichever of the live values is used furthest away
+        let (i, &(fe, fv)) = active
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, (ae, _))| *ae)
+            .expect("bytecode: no live registers to spill");
+        if fe > e {
+            loc[v] = loc[fv];
+            loc[fv] = new_slot(&mut free_slots);
+            spilled.push((fe, fv));
+            active[i] = (e, v);
+        } else {
+            loc[v] = new_slot(&mut free_slots);
+            spilled.push((e, v));
+        }
+    }
+
+    Ok((loc, live_in, slots))
+}
+
+/// An instruction with locations and laid-out block indices
+enum Ins {
+    Imm(usize, usize),
+    Mov(usize, usize),
+    Una(u64, usize, usize),
+    Con(usize, usize, usize),
+    Test(u64, usize, usize, usize),
+    Eqq(usize, usize, usize, usize),
+    Hop(usize),
+    Cal(*const Arm, bool, Vec<usize>, usize, usize),
+    Lnk(usize, usize, usize, usize),
+    Jmp(*const Arm, bool, Vec<usize>),
+    Lnt(usize, usize),
+    Spy(usize, usize, usize, usize),
+    Don(usize),
+    Bom,
+}
+
+impl Ins {
+    fn labels(&self) -> Vec<usize> {
+        match self {
+            Ins::Test(_, _, y, n) | Ins::Eqq(_, _, y, n) => vec![*y, *n],
+            Ins::Hop(l) | Ins::Cal(_, _, _, _, l) | Ins::Lnk(_, _, _, l) | Ins::Spy(_, _, _, l) => {
+                vec![*l]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+enum Lab {
+    Fall,
+    Short(usize),
+    Long(usize),
+}
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+    bits: usize,
+}
+
+impl Writer {
+    fn bits(&mut self, value: u64, width: usize) {
+        for i in 0..width {
+            if self.bits & 7 == 0 {
+                self.bytes.push(0);
+            }
+            if value >> i & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 1 << (self.bits & 7);
+            }
+            self.bits += 1;
+        }
+    }
+
+    fn align(&mut self) {
+        self.bits = self.bytes.len() * 8;
+    }
+
+    fn number(&mut self, n: usize) {
+        if n < 1 << 13 {
+            self.bits(0, 1);
+            self.bits(n as u64, 13);
+        } else {
+            self.bits(1, 1);
+            self.bits(n as u64, 48);
+        }
+    }
+
+    fn loc(&mut self, loc: usize) {
+        if loc < REGISTERS {
+            self.bits(0, 1);
+            self.bits(loc as u64, 3);
+        } else {
+            self.bits(1, 1);
+            self.number(loc - REGISTERS);
+        }
+    }
+
+    fn label(&mut self, lab: &Lab) {
+        match *lab {
+            Lab::Fall => self.bits(0, 1),
+            Lab::Short(off) => {
+                self.bits(0b01, 2);
+                self.bits(off as u64, 13);
+            }
+            Lab::Long(off) => {
+                self.bits(0b11, 2);
+                self.bits(off as u64, 48);
+            }
+        }
+    }
+
+    fn arm(&mut self, arm: *const Arm, dole: bool, args: &[usize]) {
+        self.bits(arm as u64 >> 3, 45);
+        self.bits(dole as u64, 1);
+        self.number(args.len());
+        for arg in args {
+            self.loc(*arg);
+        }
+    }
+
+    fn ins(&mut self, ins: &Ins, labs: &[Lab]) {
+        match ins {
+            Ins::Imm(d, k) => {
+                self.bits(OP_IMM, 5);
+                self.loc(*d);
+                self.number(*k);
+            }
+            Ins::Mov(d, s) => {
+                self.bits(OP_MOV, 5);
+                self.loc(*d);
+                self.loc(*s);
+            }
+            Ins::Una(op, d, s) => {
+                self.bits(*op, 5);
+                self.loc(*d);
+                self.loc(*s);
+            }
+            Ins::Con(d, h, t) => {
+                self.bits(OP_CON, 5);
+                self.loc(*d);
+                self.loc(*h);
+                self.loc(*t);
+            }
+            Ins::Test(op, s, _, _) => {
+                self.bits(*op, 5);
+                self.loc(*s);
+            }
+            Ins::Eqq(a, b, _, _) => {
+                self.bits(OP_EQQ, 5);
+                self.loc(*a);
+                self.loc(*b);
+            }
+            Ins::Hop(_) => self.bits(OP_HOP, 5),
+            Ins::Cal(arm, dole, args, d, _) => {
+                self.bits(OP_CAL, 5);
+                self.arm(*arm, *dole, args);
+                self.loc(*d);
+            }
+            Ins::Lnk(f, s, d, _) => {
+                self.bits(OP_LNK, 5);
+                self.loc(*f);
+                self.loc(*s);
+                self.loc(*d);
+            }
+            Ins::Jmp(arm, dole, args) => {
+                self.bits(OP_JMP, 5);
+                self.arm(*arm, *dole, args);
+            }
+            Ins::Lnt(f, s) => {
+                self.bits(OP_LNT, 5);
+                self.loc(*f);
+                self.loc(*s);
+            }
+            Ins::Spy(r, p, d, _) => {
+                self.bits(OP_SPY, 5);
+                self.loc(*r);
+                self.loc(*p);
+                self.loc(*d);
+            }
+            Ins::Don(s) => {
+                self.bits(OP_DON, 5);
+                self.loc(*s);
+            }
+            Ins::Bom => self.bits(OP_BOM, 5),
+        }
+        for lab in labs {
+            self.label(lab);
+        }
+        self.align();
+    }
+}
+
+fn compile(linear: &Linear, arms: &[*const Arm]) -> CResult<Compiled> {
+    let order = layout(linear)?;
+    let (loc, live_in, slots) = allocate(linear, &order)?;
+    let mut place = vec![usize::MAX; linear.blocks.len()];
+    for (k, &b) in order.iter().enumerate() {
+        place[b] = k;
+    }
+
+    let callee = |arm: usize| -> CResult<*const Arm> {
+        let ptr = arms[arm];
+        if ptr as u64 >> 48 != 0 {
+            return Err(Error::Unsupported);
+        }
+        Ok(ptr)
+    };
+    let args = |ssas: &[Ssa]| -> CResult<Vec<usize>> {
+        if ssas.len() > MAX_ARGS {
+            return Err(Error::Unsupported);
+        }
+        Ok(ssas.iter().map(|v| loc[*v]).collect())
+    };
+
+    // Instructions, with the laid-out block each belongs to
+    let mut code: Vec<(usize, Ins)> = Vec::new();
+    for (k, &b) in order.iter().enumerate() {
+        let block = &linear.blocks[b];
+        for bran in &block.body {
+            let ins = match *bran {
+                Bran::Imm(c, d) => Ins::Imm(loc[d], c),
+                Bran::Mov(s, d) => {
+                    if loc[s] == loc[d] {
+                        continue;
+                    }
+                    Ins::Mov(loc[d], loc[s])
+                }
+                Bran::Una(op, s, d) => Ins::Una(op, loc[d], loc[s]),
+                Bran::Con(h, t, d) => Ins::Con(loc[d], loc[h], loc[t]),
+            };
+            code.push((k, ins));
+        }
+        let ins = match &block.end {
+            Germ::Clq(s, y, n) => Ins::Test(OP_CLQ, loc[*s], place[*y], place[*n]),
+            Germ::Brn(s, y, n) => Ins::Test(OP_BRN, loc[*s], place[*y], place[*n]),
+            Germ::Eqq(a, b, y, n) => Ins::Eqq(loc[*a], loc[*b], place[*y], place[*n]),
+            Germ::Hop(l) => Ins::Hop(place[*l]),
+            Germ::Lnk(f, s, d, l) => Ins::Lnk(loc[*f], loc[*s], loc[*d], place[*l]),
+            Germ::Cal(arm, dole, ssas, d, l) => {
+                Ins::Cal(callee(*arm)?, *dole, args(ssas)?, loc[*d], place[*l])
+            }
+            Germ::Jmp(arm, dole, ssas) => Ins::Jmp(callee(*arm)?, *dole, args(ssas)?),
+            Germ::Lnt(f, s) => Ins::Lnt(loc[*f], loc[*s]),
+            Germ::Spy(r, p, d, l) => Ins::Spy(loc[*r], loc[*p], loc[*d], place[*l]),
+            Germ::Don(s) => Ins::Don(loc[*s]),
+            Germ::Bom => Ins::Bom,
+        };
+        code.push((k, ins));
+    }
+
+    // Widen labels until every offset fits
+    let mut wide: Vec<Vec<bool>> = code.iter().map(|_| vec![false; 2]).collect();
+    let mut starts = vec![0; order.len()];
+    let mut ends = vec![0; code.len()];
+    let labs =
+        |i: usize, k: usize, ins: &Ins, starts: &[usize], ends: &[usize], wide: &[Vec<bool>]| {
+            ins.labels()
+                .iter()
+                .enumerate()
+                .map(|(j, &t)| {
+                    if t == k + 1 {
+                        Lab::Fall
+                    } else if wide[i][j] {
+                        Lab::Long(starts[t].saturating_sub(ends[i]))
+                    } else {
+                        Lab::Short(starts[t].saturating_sub(ends[i]))
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+    let writer = loop {
+        let mut w = Writer::default();
+        let mut next_starts = vec![0; order.len()];
+        for (i, (k, ins)) in code.iter().enumerate() {
+            if i == 0 || code[i - 1].0 != *k {
+                next_starts[*k] = w.bytes.len();
+            }
+            w.ins(ins, &labs(i, *k, ins, &starts, &ends, &wide));
+            ends[i] = w.bytes.len();
+        }
+        let mut changed = next_starts != starts;
+        starts = next_starts;
+        for (i, (k, ins)) in code.iter().enumerate() {
+            for (j, &t) in ins.labels().iter().enumerate() {
+                if t != k + 1 {
+                    if t <= *k {
+                        return Err(Error::BadTown);
+                    }
+                    if !wide[i][j] && starts[t] - ends[i] >= 1 << 13 {
+                        wide[i][j] = true;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        if !changed {
+            break w;
+        }
+    };
+
+    let mut bytes = writer.bytes;
+    // The VM reads a word at a time
+    bytes.extend_from_slice(&[0; 8]);
+
+    let vent = place[linear.vent];
+    let dole = place[linear.dole];
+    Ok(Compiled {
+        code: bytes,
+        consts: linear.consts.clone(),
+        vent: starts[vent],
+        dole: starts[dole],
+        vent_args: linear
+            .pool
+            .iter()
+            .map(|v| live_in[vent][*v].then_some(loc[*v]))
+            .collect(),
+        dole_arg: live_in[dole][linear.lump].then_some(loc[linear.lump]),
+        slots,
+    })
+}
+
+//
+// The VM
+//
+
+struct Reader {
+    code: *const u8,
+    bit: usize,
+}
+
+impl Reader {
+    #[inline]
+    unsafe fn bits(&mut self, width: usize) -> u64 {
+        let word = read_unaligned(self.code.add(self.bit >> 3) as *const u64);
+        let value = (u64::from_le(word) >> (self.bit & 7)) & ((1 << width) - 1);
+        self.bit += width;
+        value
+    }
+
+    #[inline]
+    unsafe fn number(&mut self) -> usize {
+        if self.bits(1) == 0 {
+            self.bits(13) as usize
+        } else {
+            self.bits(48) as usize
+        }
+    }
+
+    #[inline]
+    unsafe fn loc(&mut self) -> usize {
+        if self.bits(1) == 0 {
+            self.bits(3) as usize
+        } else {
+            REGISTERS + self.number()
+        }
+    }
+
+    /// Offset of a label from the end of the instruction
+    #[inline]
+    unsafe fn label(&mut self) -> usize {
+        if self.bits(1) == 0 {
+            0
+        } else if self.bits(1) == 0 {
+            self.bits(13) as usize
+        } else {
+            self.bits(48) as usize
+        }
+    }
+
+    #[inline]
+    unsafe fn arm(&mut self) -> (*const Arm, bool) {
+        let arm = (self.bits(45) << 3) as *const Arm;
+        (arm, self.bits(1) == 1)
+    }
+
+    /// Byte offset of the next instruction
+    #[inline]
+    fn end(&self) -> usize {
+        (self.bit + 7) >> 3
+    }
+}
+
+#[inline]
+unsafe fn local(stack: &mut NockStack, loc: usize) -> *mut Noun {
+    stack.local_noun_pointer(FRAME_LOCALS + loc)
+}
+
+/// Push a VM frame which carries over the mean stack, like interpreter frames
+unsafe fn frame_push(
+    stack: &mut NockStack,
+    locals: usize,
+    ret_arm: *const Arm,
+    ret_pc: usize,
+    ret_dest: usize,
+) {
+    let mean = *(stack.local_noun_pointer(0));
+    let trace_stack = *(stack.local_noun_pointer(1) as *const *const TraceStack);
+    stack.frame_push(FRAME_LOCALS + locals);
+    *(stack.local_noun_pointer(0)) = mean;
+    *(stack.local_noun_pointer(1) as *mut *const TraceStack) = trace_stack;
+    *(stack.local_noun_pointer(FRAME_RET_ARM) as *mut *const Arm) = ret_arm;
+    *(stack.local_noun_pointer(FRAME_RET_PC) as *mut usize) = ret_pc;
+    *(stack.local_noun_pointer(FRAME_RET_DEST) as *mut usize) = ret_dest;
+}
+
+/// Place arguments for an arm in the current frame, returning the entry point
+unsafe fn enter(stack: &mut NockStack, arm: *const Arm, dole: bool, args: &[Noun]) -> usize {
+    if dole {
+        if let Some(loc) = (*arm).dole_arg {
+            *local(stack, loc) = args[0];
+        }
+        (*arm).dole
+    } else {
+        for (loc, arg) in (*arm).vent_args.iter().zip(args) {
+            if let Some(loc) = loc {
+                *local(stack, *loc) = *arg;
+            }
+        }
+        (*arm).vent
+    }
+}
+
+/// Run an arm which was not compiled, by its jet if it has one that matches
+unsafe fn interpret_arm(
+    context: &mut Context,
+    arm: *const Arm,
+    dole: bool,
+    args: &[Noun],
+) -> interpreter::Result {
+    let mut subject = if dole {
+        args[0]
+    } else {
+        rebuild_subject(&mut context.stack, &*arm, args)
+    };
+    let mut formula = (*arm).formula;
+    context.suspend_mean();
+    if !cfg!(feature = "sham_hints") {
+        let jet = context
+            .warm
+            .find_jet(&mut context.stack, &mut subject, &mut formula);
+        if let Some((jet, path)) = jet {
+            if let Some(res) = interpreter::apply_jet(context, jet, path, subject, formula) {
+                return res;
+            }
+        }
+    }
+    interpret(context, subject, formula)
+}
+
+/// Rebuild enough of an arm's subject to run it from the arguments of a `vent` entry: they are
+/// grafted at their axes onto what the arm's sock knows of its subject, with 0 for the rest.
+/// The arm only reads the axes in its pool, so the rest of the subject cannot change its
+/// result.
+fn rebuild_subject(stack: &mut NockStack, arm: &Arm, args: &[Noun]) -> Noun {
+    let mut subject = sock_noun(stack, arm.sub);
+    for (axis, arg) in arm.pool.iter().zip(args) {
+        // Decoding checked that axes are atoms
+        let axis = unsafe { axis.as_atom().unwrap_unchecked() };
+        subject = graft(stack, axis.as_bitslice(), *arg, subject);
+    }
+    subject
+}
+
+/// What a sock knows of a noun, with 0 for what it does not
+fn sock_noun(stack: &mut NockStack, sock: Noun) -> Noun {
+    enum Todo {
+        Sock(Noun),
+        Cons,
+    }
+    let mut todo = vec![Todo::Sock(sock)];
+    let mut done = Vec::new();
+    while let Some(next) = todo.pop() {
+        match next {
+            Todo::Sock(sock) => {
+                let Ok(sock) = sock.as_cell() else {
+                    done.push(D(0));
+                    continue;
+                };
+                match (
+                    sock.head().as_direct().map(|tag| tag.data()),
+                    sock.tail().as_cell(),
+                ) {
+                    (Ok(tas!(b"know")), _) => done.push(sock.tail()),
+                    (Ok(tas!(b"bets")), Ok(bets)) => {
+                        todo.push(Todo::Cons);
+                        todo.push(Todo::Sock(bets.tail()));
+                        todo.push(Todo::Sock(bets.head()));
+                    }
+                    _ => done.push(D(0)),
+                }
+            }
+            Todo::Cons => {
+                let tail = done.pop().unwrap();
+                let head = done.pop().unwrap();
+                done.push(T(stack, &[head, tail]));
+            }
+        }
+    }
+    done.pop().unwrap()
+}
+
+/// Replace the subtree of `tree` at `axis` with `patch`, like Nock 10, except that atoms on
+/// the way are taken to be [0 0]
+fn graft(stack: &mut NockStack, axis: &BitSlice<u64, Lsb0>, patch: Noun, tree: Noun) -> Noun {
+    let Some(top) = axis.last_one() else {
+        return tree;
+    };
+    let mut res = patch;
+    let mut dest: *mut Noun = &mut res;
+    let mut tree = tree;
+    for cursor in (0..top).rev() {
+        let (head, tail) = tree
+            .as_cell()
+            .map_or((D(0), D(0)), |cell| (cell.head(), cell.tail()));
+        unsafe {
+            let (cell, cellmem) = Cell::new_raw_mut(stack);
+            *dest = cell.as_noun();
+            if axis[cursor] {
+                (*cellmem).head = head;
+                dest = &mut (*cellmem).tail;
+                tree = tail;
+            } else {
+                (*cellmem).tail = tail;
+                dest = &mut (*cellmem).head;
+                tree = head;
+            }
+        }
+    }
+    unsafe { *dest = patch };
+    res
+}
+
+/** Rerun a crashed arm with the interpreter alone, which gives the crash the trace of the
+ * arm's hints. Compiled code is not run again until the rerun returns, so that nested arms are
+ * not rerun once at every level.
+ */
+pub fn rerun(context: &mut Context, subject: Noun, formula: Noun) -> interpreter::Result {
+    let suspended = std::mem::replace(&mut context.code.suspended, true);
+    let res = interpret(context, subject, formula);
+    context.code.suspended = suspended;
+    res
+}
+
+const BAIL_EXIT: interpreter::Result = Err(interpreter::Error::Deterministic(Mote::Exit, D(0)));
+const BAIL_INTR: interpreter::Result = Err(interpreter::Error::NonDeterministic(Mote::Intr, D(0)));
+
+/** Run compiled code for an arm against the whole subject.
+ *
+ * Nock 2 and 9 with formulas not in the table are evaluated with [interpret]. On a crash the
+ * caller's cold and warm state, jet cache and mean stacks are restored.
+ *
+ * ## Safety
+ *
+ * The arm must come from [Code::find] on the context's table, and the current frame must
+ * have a mean stack, as within [interpret] or a jet.
+ */
+pub unsafe fn run(context: &mut Context, arm: *const Arm, subject: Noun) -> interpreter::Result {
+    let terminator = Arc::clone(&TERMINATOR);
+    let deadline = Arc::clone(&DEADLINE_PASSED);
+    let snapshot = context.save();
+    let cache = context.cache;
+    let virtual_frame = context.stack.get_frame_pointer();
+    context.code.runs += 1;
+
+    frame_push(&mut context.stack, context.code.locals, null(), 0, 0);
+    let pc = enter(&mut context.stack, arm, true, &[subject]);
+    match execute(context, &terminator, &deadline, arm, pc) {
+        Ok(res) => Ok(res),
+        Err(mut err) => {
+            while context.stack.get_frame_pointer() != virtual_frame {
+                context.stack.preserve(&mut err);
+                context.stack.frame_pop();
+            }
+            context.restore(&snapshot);
+            context.cache = cache;
+            Err(err)
+        }
+    }
+}
+
+unsafe fn execute(
+    context: &mut Context,
+    terminator: &AtomicBool,
+    deadline: &AtomicBool,
+    mut arm: *const Arm,
+    mut pc: usize,
+) -> interpreter::Result {
+    let mut args = [D(0); MAX_ARGS];
+    loop {
+        let mut ins = Reader {
+            code: (*arm).code.as_ptr(),
+            bit: pc << 3,
+        };
+        let stack = &mut context.stack;
+        let res = match ins.bits(5) {
+            OP_IMM => {
+                let d = ins.loc();
+                let k = ins.number();
+                *local(stack, d) = (&(*arm).consts)[k];
+                pc = ins.end();
+                continue;
+            }
+            OP_MOV => {
+                let d = ins.loc();
+                let s = ins.loc();
+                *local(stack, d) = *local(stack, s);
+                pc = ins.end();
+                continue;
+            }
+            op @ (OP_INC | OP_UNC) => {
+                let d = ins.loc();
+                let Ok(atom) = (*local(stack, ins.loc())).as_atom() else {
+                    debug_assert!(op == OP_INC);
+                    break BAIL_EXIT;
+                };
+                *local(stack, d) = inc(stack, atom).as_noun();
+                pc = ins.end();
+                continue;
+            }
+            OP_CON => {
+                let d = ins.loc();
+                let h = *local(stack, ins.loc());
+                let t = *local(stack, ins.loc());
+                *local(stack, d) = T(stack, &[h, t]);
+                pc = ins.end();
+                continue;
+            }
+            op @ (OP_HED | OP_TAL | OP_HUD | OP_TUL) => {
+                let d = ins.loc();
+                let Ok(cell) = (*local(stack, ins.loc())).as_cell() else {
+                    break BAIL_EXIT;
+                };
+                *local(stack, d) = if op == OP_HED || op == OP_HUD {
+                    cell.head()
+                } else {
+                    cell.tail()
+                };
+                pc = ins.end();
+                continue;
+            }
+            op @ (OP_CLQ | OP_BRN) => {
+                let s = *local(stack, ins.loc());
+                let yes = ins.label();
+                let no = ins.label();
+                let taken = if op == OP_CLQ {
+                    s.is_cell()
+                } else if s.raw_equals(D(0)) {
+                    true
+                } else if s.raw_equals(D(1)) {
+                    false
+                } else {
+                    break BAIL_EXIT;
+                };
+                pc = ins.end() + if taken { yes } else { no };
+                continue;
+            }
+            OP_EQQ => {
+                let a = local(stack, ins.loc());
+                let b = local(stack, ins.loc());
+                let yes = ins.label();
+                let no = ins.label();
+                let taken = unifying_equality(stack, a, b);
+                pc = ins.end() + if taken { yes } else { no };
+                continue;
+            }
+            OP_HOP => {
+                let to = ins.label();
+                pc = ins.end() + to;
+                continue;
+            }
+            OP_CAL => {
+                if interrupted(terminator, deadline) {
+                    break BAIL_INTR;
+                }
+                let (callee, dole) = ins.arm();
+                let argc = ins.number();
+                for arg in args.iter_mut().take(argc) {
+                    *arg = *local(stack, ins.loc());
+                }
+                let d = ins.loc();
+                let then = ins.end() + ins.label();
+                if !(*callee).compiled {
+                    let res = interpret_arm(context, callee, dole, &args[..argc])?;
+                    *local(&mut context.stack, d) = res;
+                    pc = then;
+                    continue;
+                }
+                frame_push(stack, context.code.locals, arm, then, d);
+                pc = enter(stack, callee, dole, &args[..argc]);
+                arm = callee;
+                continue;
+            }
+            OP_JMP => {
+                if interrupted(terminator, deadline) {
+                    break BAIL_INTR;
+                }
+                let (callee, dole) = ins.arm();
+                let argc = ins.number();
+                for arg in args.iter_mut().take(argc) {
+                    *arg = *local(stack, ins.loc());
+                }
+                if !(*callee).compiled {
+                    interpret_arm(context, callee, dole, &args[..argc])?
+                } else {
+                    pc = enter(stack, callee, dole, &args[..argc]);
+                    arm = callee;
+                    continue;
+                }
+            }
+            OP_LNK => {
+                if interrupted(terminator, deadline) {
+                    break BAIL_INTR;
+                }
+                let f = *local(stack, ins.loc());
+                let s = *local(stack, ins.loc());
+                let d = ins.loc();
+                let then = ins.end() + ins.label();
+                if let Some(callee) = context.code.find(stack, s, f) {
+                    frame_push(stack, context.code.locals, arm, then, d);
+                    pc = enter(stack, callee, true, &[s]);
+                    arm = callee;
+                } else {
+                    context.suspend_mean();
+                    let res = interpret(context, s, f)?;
+                    *local(&mut context.stack, d) = res;
+                    pc = then;
+                }
+                continue;
+            }
+            OP_LNT => {
+                if interrupted(terminator, deadline) {
+                    break BAIL_INTR;
+                }
+                let f = *local(stack, ins.loc());
+                let s = *local(stack, ins.loc());
+                if let Some(callee) = context.code.find(stack, s, f) {
+                    pc = enter(stack, callee, true, &[s]);
+                    arm = callee;
+                    continue;
+                }
+                context.suspend_mean();
+                interpret(context, s, f)?
+            }
+            OP_SPY => {
+                let r = *local(stack, ins.loc());
+                let p = *local(stack, ins.loc());
+                let d = ins.loc();
+                let then = ins.end() + ins.label();
+                let subject = T(stack, &[r, p]);
+                let ref_ = T(stack, &[D(0), D(2)]);
+                let path = T(stack, &[D(0), D(3)]);
+                let formula = T(stack, &[D(12), ref_, path]);
+                context.suspend_mean();
+                let res = interpret(context, subject, formula)?;
+                *local(&mut context.stack, d) = res;
+                pc = then;
+                continue;
+            }
+            OP_DON => *local(stack, ins.loc()),
+            _ => break BAIL_EXIT,
+        };
+
+        // Return from the arm
+        let mut res = res;
+        let stack = &mut context.stack;
+        let ret_arm = *(stack.local_noun_pointer(FRAME_RET_ARM) as *const *const Arm);
+        let ret_pc = *(stack.local_noun_pointer(FRAME_RET_PC) as *const usize);
+        let ret_dest = *(stack.local_noun_pointer(FRAME_RET_DEST) as *const usize);
+        stack.preserve(&mut context.cache);
+        stack.preserve(&mut context.cold);
+        stack.preserve(&mut context.warm);
+        stack.preserve(&mut res);
+        stack.frame_pop();
+        if ret_arm.is_null() {
+            break Ok(res);
+        }
+        *local(stack, ret_dest) = res;
+        arm = ret_arm;
+        pc = ret_pc;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::jets::list::util::zing;
+    use crate::jets::util::test::{assert_noun_eq, init_context};
+
+    fn list(stack: &mut NockStack, items: &[Noun]) -> Noun {
+        let mut list = D(0);
+        for item in items.iter().rev() {
+            list = T(stack, &[*item, list]);
+        }
+        list
+    }
+
+    /// A degenerate (unbalanced) Hoon map, which decoding accepts
+    fn map(stack: &mut NockStack, entries: &[(Noun, Noun)]) -> Noun {
+        let mut map = D(0);
+        for (k, v) in entries.iter().rev() {
+            let n = T(stack, &[*k, *v]);
+            map = T(stack, &[n, D(0), map]);
+        }
+        map
+    }
+
+    struct TestArm<'a> {
+        formula: Noun,
+        pool: &'a [(u64, u64)],
+        lump: u64,
+        blocks: Vec<(&'static [u8], Noun, Noun)>,
+    }
+
+    fn toss(stack: &mut NockStack) -> Noun {
+        T(stack, &[D(tas!(b"toss")), D(0)])
+    }
+
+    fn berm(stack: &mut NockStack, formula: Noun, gen: &[u8]) -> Noun {
+        let sub = toss(stack);
+        let mut bytes = [0; 8];
+        bytes[..gen.len()].copy_from_slice(gen);
+        T(stack, &[sub, formula, D(1), D(u64::from_le_bytes(bytes))])
+    }
+
+    fn barn(stack: &mut NockStack, formula: Noun) -> Noun {
+        let sub = toss(stack);
+        T(stack, &[sub, formula])
+    }
+
+    /// Build a town from arms whose blocks are named by `gen`. Each arm gets %vent and %dole
+    /// entry blocks which jump to the block named `tern`, or `peel` for %dole if present.
+    fn town(stack: &mut NockStack, arms: &[TestArm]) -> Noun {
+        let mut land = Vec::new();
+        for arm in arms {
+            let mut lake = Vec::new();
+            let mut has_peel = false;
+            for (gen, body, germ) in &arm.blocks {
+                has_peel |= *gen == b"peel";
+                let label = berm(stack, arm.formula, gen);
+                let lock = T(stack, &[*body, *germ]);
+                lake.push((label, lock));
+            }
+            for (entry, to) in [
+                (&b"vent"[..], &b"tern"[..]),
+                (b"dole", if has_peel { b"peel" } else { b"tern" }),
+            ] {
+                let label = berm(stack, arm.formula, entry);
+                let to = berm(stack, arm.formula, to);
+                let germ = T(stack, &[D(tas!(b"jmp")), to]);
+                let lock = T(stack, &[D(0), germ]);
+                lake.push((label, lock));
+            }
+            let goes = map(stack, &lake);
+            let uses: Vec<Noun> = arm
+                .pool
+                .iter()
+                .map(|(axe, ssa)| T(stack, &[D(*axe), D(*ssa), D(1)]))
+                .collect();
+            let uses = list(stack, &uses);
+            let rice = T(stack, &[goes, uses, D(arm.lump)]);
+            let says = T(stack, &[D(tas!(b"risk")), D(tas!(b"toss")), D(0)]);
+            let sack = T(stack, &[rice, says]);
+            let key = barn(stack, arm.formula);
+            land.push((key, sack));
+        }
+        let land = map(stack, &land);
+        T(stack, &[land, D(1000)])
+    }
+
+    fn ops(stack: &mut NockStack, ops: &[&[u64]]) -> Noun {
+        let items: Vec<Noun> = ops
+            .iter()
+            .map(|op| {
+                let items: Vec<Noun> = op.iter().map(|n| D(*n)).collect();
+                T(stack, &items)
+            })
+            .collect();
+        list(stack, &items)
+    }
+
+    fn nock2(context: &mut Context, subject: Noun, formula: Noun) -> Noun {
+        let stack = &mut context.stack;
+        let zero = T(stack, &[D(0), D(1)]);
+        let f = T(stack, &[D(2), zero, D(1), formula]);
+        interpret(context, subject, f).unwrap_or_else(|_| panic!("nock crashed"))
+    }
+
+    /// Run compiled code without the interpreter to fall back on
+    fn direct(context: &mut Context, subject: Noun, formula: Noun) -> Noun {
+        let arm = context
+            .code
+            .find(&mut context.stack, subject, formula)
+            .expect("no compiled arm");
+        unsafe {
+            context.with_stack_frame(2, |context| {
+                *(context.stack.local_noun_pointer(0)) = D(0);
+                *(context.stack.local_noun_pointer(1) as *mut *const TraceStack) = null();
+                run(context, arm, subject).unwrap_or_else(|_| panic!("code crashed"))
+            })
+        }
+    }
+
+    fn increment(stack: &mut NockStack) -> (Noun, Noun) {
+        let formula = T(stack, &[D(4), D(0), D(1)]);
+        let body = ops(stack, &[&[tas!(b"inc"), 0, 1]]);
+        let don = T(stack, &[D(tas!(b"don")), D(1)]);
+        (formula, T(stack, &[body, don]))
+    }
+
+    #[test]
+    fn test_increment() {
+        let c = &mut init_context();
+        let (formula, lock) = increment(&mut c.stack);
+        let lock = lock.as_cell().unwrap();
+        let town = town(
+            &mut c.stack,
+            &[TestArm {
+                formula,
+                pool: &[(1, 0)],
+                lump: 0,
+                blocks: vec![(b"tern", lock.head(), lock.tail())],
+            }],
+        );
+        assert_eq!(c.code.install(&mut c.stack, &c.warm, town).unwrap(), 1);
+
+        let res = nock2(c, D(41), formula);
+        assert_noun_eq(&mut c.stack, res, D(42));
+        assert_eq!(c.code.runs(), 1);
+        let res = direct(c, D(41), formula);
+        assert_noun_eq(&mut c.stack, res, D(42));
+    }
+
+    #[test]
+    fn test_gas() {
+        // A bounded computation is charged the same whether or not its arms are compiled
+        let c = &mut init_context();
+        let (formula, lock) = increment(&mut c.stack);
+        let lock = lock.as_cell().unwrap();
+        let town = town(
+            &mut c.stack,
+            &[TestArm {
+                formula,
+                pool: &[(1, 0)],
+                lump: 0,
+                blocks: vec![(b"tern", lock.head(), lock.tail())],
+            }],
+        );
+        assert_eq!(c.code.install(&mut c.stack, &c.warm, town).unwrap(), 1);
+
+        c.gas = Some(100);
+        let res = nock2(c, D(41), formula);
+        assert_noun_eq(&mut c.stack, res, D(42));
+        assert_eq!(c.code.runs(), 0);
+
+        let plain = &mut init_context();
+        plain.gas = Some(100);
+        let res = nock2(plain, D(41), formula);
+        assert_noun_eq(&mut plain.stack, res, D(42));
+        assert_eq!(c.gas, plain.gas);
+    }
+
+    #[test]
+    fn test_loop() {
+        // Count i up to n in the core [battery n i]
+        let c = &mut init_context();
+        let s = &mut c.stack;
+        let formula = {
+            let n = T(s, &[D(0), D(6)]);
+            let i = T(s, &[D(0), D(7)]);
+            let test = T(s, &[D(5), n, i]);
+            let battery = T(s, &[D(0), D(2)]);
+            let more = T(s, &[D(4), D(0), D(7)]);
+            let core = T(s, &[battery, n, more]);
+            let recur = T(s, &[D(9), D(2), core]);
+            T(s, &[D(6), test, i, recur])
+        };
+        let me = barn(s, formula);
+        let done = berm(s, formula, b"done");
+        let more = berm(s, formula, b"more");
+        let tern = berm(s, formula, b"tern");
+        let eqq = T(s, &[D(tas!(b"eqq")), D(1), D(2), done, more]);
+        let don = T(s, &[D(tas!(b"don")), D(2)]);
+        let incr = ops(s, &[&[tas!(b"inc"), 2, 3]]);
+        let args = list(s, &[D(0), D(1), D(3)]);
+        let jmp = T(s, &[D(tas!(b"jmp")), me, args]);
+        let peel = ops(
+            s,
+            &[
+                &[tas!(b"hed"), 4, 0],
+                &[tas!(b"tal"), 4, 5],
+                &[tas!(b"hed"), 5, 1],
+                &[tas!(b"tal"), 5, 2],
+            ],
+        );
+        let hop = T(s, &[D(tas!(b"hop")), tern]);
+        let town = town(
+            s,
+            &[TestArm {
+                formula,
+                pool: &[(2, 0), (6, 1), (7, 2)],
+                lump: 4,
+                blocks: vec![
+                    (b"tern", D(0), eqq),
+                    (b"done", D(0), don),
+                    (b"more", incr, jmp),
+                    (b"peel", peel, hop),
+                ],
+            }],
+        );
+        assert_eq!(c.code.install(&mut c.stack, &c.warm, town).unwrap(), 1);
+
+        let s = &mut c.stack;
+        let state = T(s, &[D(1000), D(0)]);
+        let core = T(s, &[formula, state]);
+        let nine = T(s, &[D(9), D(2), D(0), D(1)]);
+        let res = interpret(c, core, nine).unwrap_or_else(|_| panic!("nock crashed"));
+        assert_noun_eq(&mut c.stack, res, D(1000));
+        assert_eq!(c.code.runs(), 1);
+        let res = direct(c, core, formula);
+        assert_noun_eq(&mut c.stack, res, D(1000));
+
+        let plain = &mut init_context();
+        let s = &mut plain.stack;
+        let state = T(s, &[D(1000), D(0)]);
+        let core = T(s, &[formula, state]);
+        let nine = T(s, &[D(9), D(2), D(0), D(1)]);
+        let res = interpret(plain, core, nine).unwrap_or_else(|_| panic!("nock crashed"));
+        assert_noun_eq(&mut plain.stack, res, D(1000));
+    }
+
+    #[test]
+    fn test_call_and_spill() {
+        // [+(1) 2 3 ... 10], with ten values live at once and a call to the increment arm
+        let c = &mut init_context();
+        let s = &mut c.stack;
+        let (inc_formula, inc_lock) = increment(s);
+        let inc_lock = inc_lock.as_cell().unwrap();
+        let formula = {
+            let one = T(s, &[D(1), D(1)]);
+            let inc = T(s, &[D(2), one, D(1), inc_formula]);
+            let mut f = T(s, &[D(1), D(10)]);
+            for i in (2..10).rev() {
+                let k = T(s, &[D(1), D(i)]);
+                f = T(s, &[k, f]);
+            }
+            T(s, &[inc, f])
+        };
+        let imms: Vec<Noun> = (0..10)
+            .map(|i| T(s, &[D(tas!(b"imm")), D(i + 1), D(i)]))
+            .collect();
+        let imms = list(s, &imms);
+        let inc_barn = barn(s, inc_formula);
+        let ret = berm(s, formula, b"ret");
+        let args = list(s, &[D(0)]);
+        let cal = T(s, &[D(tas!(b"cal")), inc_barn, args, D(10), ret]);
+        let mut cons: Vec<Noun> = Vec::new();
+        let mut tail = 9;
+        for (i, head) in (1..9).rev().chain([10]).enumerate() {
+            cons.push(T(s, &[D(tas!(b"con")), D(head), D(tail), D(20 + i as u64)]));
+            tail = 20 + i as u64;
+        }
+        let cons = list(s, &cons);
+        let don = T(s, &[D(tas!(b"don")), D(tail)]);
+        let town = town(
+            s,
+            &[
+                TestArm {
+                    formula: inc_formula,
+                    pool: &[(1, 0)],
+                    lump: 0,
+                    blocks: vec![(b"tern", inc_lock.head(), inc_lock.tail())],
+                },
+                TestArm {
+                    formula,
+                    pool: &[],
+                    lump: 99,
+                    blocks: vec![(b"tern", imms, cal), (b"ret", cons, don)],
+                },
+            ],
+        );
+        assert_eq!(c.code.install(&mut c.stack, &c.warm, town).unwrap(), 2);
+        assert!(c.code.locals > REGISTERS);
+
+        let res = nock2(c, D(0), formula);
+        let s = &mut c.stack;
+        let expected: Vec<Noun> = [2, 2, 3, 4, 5, 6, 7, 8, 9, 10]
+            .iter()
+            .map(|n| D(*n))
+            .collect();
+        let expected = T(s, &expected);
+        assert_noun_eq(s, res, expected);
+        assert_eq!(c.code.runs(), 1);
+        let res = direct(c, D(0), formula);
+        assert_noun_eq(&mut c.stack, res, expected);
+
+        let plain = &mut init_context();
+        let res = nock2(plain, D(0), formula);
+        let s = &mut plain.stack;
+        let expected: Vec<Noun> = [2, 2, 3, 4, 5, 6, 7, 8, 9, 10]
+            .iter()
+            .map(|n| D(*n))
+            .collect();
+        let expected = T(s, &expected);
+        assert_noun_eq(s, res, expected);
+    }
+
+    #[test]
+    fn test_long_labels() {
+        // ?^(. . 2.999), with enough code on the atom side to need a 48 bit label
+        let c = &mut init_context();
+        let s = &mut c.stack;
+        let formula = {
+            let test = T(s, &[D(3), D(0), D(1)]);
+            let yes = T(s, &[D(0), D(1)]);
+            let no = T(s, &[D(1), D(2999)]);
+            T(s, &[D(6), test, yes, no])
+        };
+        let yes = berm(s, formula, b"yes");
+        let no = berm(s, formula, b"no");
+        let clq = T(s, &[D(tas!(b"clq")), D(0), yes, no]);
+        let don0 = T(s, &[D(tas!(b"don")), D(0)]);
+        let imms: Vec<Noun> = (0..3000)
+            .map(|i| T(s, &[D(tas!(b"imm")), D(i), D(1)]))
+            .collect();
+        let imms = list(s, &imms);
+        let don1 = T(s, &[D(tas!(b"don")), D(1)]);
+        let town = town(
+            s,
+            &[TestArm {
+                formula,
+                pool: &[(1, 0)],
+                lump: 0,
+                blocks: vec![
+                    (b"tern", D(0), clq),
+                    (b"yes", D(0), don0),
+                    (b"no", imms, don1),
+                ],
+            }],
+        );
+        assert_eq!(c.code.install(&mut c.stack, &c.warm, town).unwrap(), 1);
+        assert!(c.code.arms[0].code.len() > 1 << 13);
+
+        let cell = T(&mut c.stack, &[D(1), D(2)]);
+        let res = nock2(c, cell, formula);
+        assert_noun_eq(&mut c.stack, res, cell);
+        let res = nock2(c, D(5), formula);
+        assert_noun_eq(&mut c.stack, res, D(2999));
+        assert_eq!(c.code.runs(), 2);
+        let res = direct(c, D(5), formula);
+        assert_noun_eq(&mut c.stack, res, D(2999));
+        let res = direct(c, cell, formula);
+        assert_noun_eq(&mut c.stack, res, cell);
+    }
+
+    #[test]
+    fn test_crash() {
+        // Compiled code which crashes is rerun by the interpreter. [1 7] never crashes there.
+        let c = &mut init_context();
+        let s = &mut c.stack;
+        let formula = T(s, &[D(1), D(7)]);
+        let bom = T(s, &[D(tas!(b"bom")), D(0)]);
+        let head = T(s, &[D(0), D(2)]);
+        let hed = ops(s, &[&[tas!(b"hed"), 0, 1]]);
+        let don = T(s, &[D(tas!(b"don")), D(1)]);
+        let town = town(
+            s,
+            &[
+                TestArm {
+                    formula,
+                    pool: &[],
+                    lump: 0,
+                    blocks: vec![(b"tern", D(0), bom)],
+                },
+                TestArm {
+                    formula: head,
+                    pool: &[(1, 0)],
+                    lump: 0,
+                    blocks: vec![(b"tern", hed, don)],
+                },
+            ],
+        );
+        assert_eq!(c.code.install(&mut c.stack, &c.warm, town).unwrap(), 2);
+
+        let res = nock2(c, D(0), formula);
+        assert_noun_eq(&mut c.stack, res, D(7));
+        assert_eq!(c.code.runs(), 1);
+
+        // A real crash has the trace the interpreter gives it
+        let s = &mut c.stack;
+        let zero = T(s, &[D(0), D(1)]);
+        let nock2 = T(s, &[D(2), zero, D(1), head]);
+        let foo = T(s, &[D(1), D(tas!(b"foo"))]);
+        let mean = T(s, &[D(tas!(b"mean")), foo]);
+        let hinted = T(s, &[D(11), mean, nock2]);
+        let Err(interpreter::Error::Deterministic(Mote::Exit, traces)) = interpret(c, D(0), hinted)
+        else {
+            panic!("code did not crash");
+        };
+        assert_eq!(c.code.runs(), 2);
+        assert!(!c.code.suspended);
+        let s = &mut c.stack;
+        let trace = zing(s, traces).unwrap();
+        let entry = T(s, &[D(tas!(b"mean")), D(tas!(b"foo"))]);
+        let expected = T(s, &[entry, D(0)]);
+        assert_noun_eq(s, trace, expected);
+    }
+
+    #[test]
+    fn test_uncompiled() {
+        // An arm with a hint is left to the interpreter, and compiled arms calling it run it
+        // there on a subject rebuilt from their arguments
+        let c = &mut init_context();
+        let s = &mut c.stack;
+        let tail = T(s, &[D(0), D(3)]);
+        let hinted = T(s, &[D(11), D(1), tail]);
+        let next = berm(s, hinted, b"next");
+        let hnt = T(s, &[D(tas!(b"hnt")), D(0), next]);
+        let don = T(s, &[D(tas!(b"don")), D(0)]);
+        let zero = T(s, &[D(0), D(1)]);
+        let quote = T(s, &[D(1), hinted]);
+        let caller = T(s, &[D(2), zero, quote]);
+        let jumper = T(s, &[D(7), zero, caller]);
+        let hinted_barn = barn(s, hinted);
+        let tal = ops(s, &[&[tas!(b"tal"), 0, 1]]);
+        let args = list(s, &[D(1)]);
+        let ret = berm(s, caller, b"ret");
+        let cal = T(s, &[D(tas!(b"cal")), hinted_barn, args, D(2), ret]);
+        let don2 = T(s, &[D(tas!(b"don")), D(2)]);
+        let jmp = T(s, &[D(tas!(b"jmp")), hinted_barn, args]);
+        let town = town(
+            s,
+            &[
+                TestArm {
+                    formula: hinted,
+                    pool: &[(3, 0)],
+                    lump: 9,
+                    blocks: vec![(b"tern", D(0), hnt), (b"next", D(0), don)],
+                },
+                TestArm {
+                    formula: caller,
+                    pool: &[(1, 0)],
+                    lump: 0,
+                    blocks: vec![(b"tern", tal, cal), (b"ret", D(0), don2)],
+                },
+                TestArm {
+                    formula: jumper,
+                    pool: &[(1, 0)],
+                    lump: 0,
+                    blocks: vec![(b"tern", tal, jmp)],
+                },
+            ],
+        );
+        assert_eq!(c.code.install(&mut c.stack, &c.warm, town).unwrap(), 2);
+        assert_eq!(c.code.len(), 2);
+
+        let subject = T(&mut c.stack, &[D(5), D(6)]);
+        for formula in [caller, jumper] {
+            let res = direct(c, subject, formula);
+            assert_noun_eq(&mut c.stack, res, D(6));
+        }
+        let res = nock2(c, subject, hinted);
+        assert_noun_eq(&mut c.stack, res, D(6));
+        assert_eq!(c.code.runs(), 2);
+    }
+}
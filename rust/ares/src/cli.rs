@@ -0,0 +1,473 @@
+/** Standalone subcommands: running Nock on jammed files, and converting jammed nouns to and from
+ * text. The commands which work on piers are in [pier].
+ *
+ * The text format is that of Hoon nouns, restricted to what can be read back unambiguously:
+ *
+ * - cells are bracketed, and nest to the right: `[a b c]` is `[a [b c]]`
+ * - atoms are decimal (`1.024` or `1024`), hexadecimal (`0x400`), or a `%term` of lowercase
+ *   letters, digits and hyphens
+ * - `~` is `0`
+ */
+pub mod pier;
+
+use crate::bytecode::Code;
+use crate::debugger::Debugger;
+use crate::hamt::Hamt;
+use crate::interpreter::{interpret, Context, Error, Formulas};
+use crate::jets::cold::Cold;
+use crate::jets::hot::{Hot, HotEntry};
+use crate::jets::list::util::zing;
+use crate::jets::nock::util::mook;
+use crate::jets::warm::Warm;
+use crate::jets::JetTest;
+use crate::mem::NockStack;
+use crate::mug::met3_usize;
+use crate::newt::Newt;
+use crate::noun::{Atom, Cell, IndirectAtom, Noun, Slots, D, T};
+use crate::serialization::{cue, jam};
+use crate::trace::LiveCounters;
+use ares_macros::tas;
+use either::Either::{Left, Right};
+use ibig::UBig;
+use std::io::{self, Read, Write};
+
+crate::gdb!();
+
+/// Size of the NockStack for subcommands, in words
+pub const STACK_SIZE: usize = 2048 << 10 << 10;
+
+/**
+ * `ares nock <subject.jam> <formula.jam>`
+ *
+ * Run a formula against a subject and print the product, or the stack trace if it crashes.
+ */
+pub fn nock(constant_hot_state: &[HotEntry]) -> io::Result<()> {
+    let subject_path = std::env::args()
+        .nth(2)
+        .ok_or(io::Error::other("no subject"))?;
+    let formula_path = std::env::args()
+        .nth(3)
+        .ok_or(io::Error::other("no formula"))?;
+
+    let mut context = cli_context(constant_hot_state);
+    let subject_atom = read_atom(&mut context.stack, &std::fs::read(subject_path)?);
+    let subject = cue(&mut context.stack, subject_atom);
+    let formula_atom = read_atom(&mut context.stack, &std::fs::read(formula_path)?);
+    let formula = cue(&mut context.stack, formula_atom);
+
+    let mut out = io::stdout();
+    match interpret(&mut context, subject, formula) {
+        Ok(product) => writeln!(out, "{}", noun_to_text(product)),
+        Err(Error::Deterministic(mote, traces)) | Err(Error::NonDeterministic(mote, traces)) => {
+            writeln!(out, "bail: {}", noun_to_text(D(mote as u64)))?;
+            let tang = trace_to_tang(&mut context, traces);
+            write_tang(&mut out, tang)
+        }
+        Err(Error::ScryBlocked(path)) => {
+            writeln!(out, "bail: scry blocked: {}", noun_to_text(path))
+        }
+        Err(Error::ScryCrashed(trace)) => {
+            writeln!(out, "bail: scry crashed")?;
+            let tang = trace_to_tang(&mut context, trace);
+            write_tang(&mut out, tang)
+        }
+    }
+}
+
+/**
+ * `ares cue [file.jam]`
+ *
+ * Print a jammed noun, read from the file or from stdin, as text.
+ */
+pub fn cue_text() -> io::Result<()> {
+    let bytes = read_input(std::env::args().nth(2))?;
+    let mut stack = NockStack::new(STACK_SIZE, 0);
+    let atom = read_atom(&mut stack, &bytes);
+    let noun = cue(&mut stack, atom);
+    writeln!(io::stdout(), "{}", noun_to_text(noun))
+}
+
+/**
+ * `ares jam [file.txt]`
+ *
+ * Read a noun as text, from the file or from stdin, and write it jammed to stdout.
+ */
+pub fn jam_text() -> io::Result<()> {
+    let bytes = read_input(std::env::args().nth(2))?;
+    let text = std::str::from_utf8(&bytes)
+        .map_err(|_e| io::Error::new(io::ErrorKind::InvalidData, "noun text is not UTF-8"))?;
+    let mut stack = NockStack::new(STACK_SIZE, 0);
+    let noun = text_to_noun(&mut stack, text)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let jammed = jam(&mut stack, noun);
+    io::stdout().write_all(&jammed.as_bytes()[0..met3_usize(jammed)])
+}
+
+fn cli_context(constant_hot_state: &[HotEntry]) -> Context {
+    let mut stack = NockStack::new(STACK_SIZE, 0);
+    let newt = Newt::new_mock();
+    let mut cold = Cold::new(&mut stack);
+    let hot = Hot::init(&mut stack, constant_hot_state);
+    let warm = Warm::init(&mut stack, &mut cold, &hot);
+    let code = Code::new(&mut stack);
+    let cache = Hamt::<Noun>::new(&mut stack);
+
+    Context {
+        stack,
+        newt,
+        cold,
+        warm,
+        hot,
+        code,
+        formulas: Formulas::new(),
+        cache,
+        scry_stack: D(0),
+        trace_info: None,
+        jet_test: JetTest::from_env(),
+        mean_slot: std::ptr::null(),
+        outer_means: D(0),
+        outer_trace: std::ptr::null(),
+        live: LiveCounters::new(),
+        profile: None,
+        jet_stats: None,
+        gas: None,
+        debugger: Debugger::from_env(),
+    }
+}
+
+fn read_input(path: Option<String>) -> io::Result<Vec<u8>> {
+    match path {
+        Some(path) => std::fs::read(path),
+        None => {
+            let mut bytes = Vec::new();
+            io::stdin().read_to_end(&mut bytes)?;
+            Ok(bytes)
+        }
+    }
+}
+
+/// Make an atom of little-endian bytes
+pub fn read_atom(stack: &mut NockStack, bytes: &[u8]) -> Atom {
+    if bytes.is_empty() {
+        return D(0).as_atom().unwrap();
+    }
+    unsafe { IndirectAtom::new_raw_bytes_ref(stack, bytes).normalize_as_atom() }
+}
+
+/// Render a stack trace, as from the mean stack, to a tang with +mook
+pub fn trace_to_tang(context: &mut Context, traces: Noun) -> Noun {
+    let tang = zing(&mut context.stack, traces).ok().and_then(|trace| {
+        let tone = Cell::new(&mut context.stack, D(2), trace);
+        mook(context, tone, false).ok().map(|toon| toon.tail())
+    });
+    tang.unwrap_or(D(0))
+}
+
+/// Write each tank of a tang on its own line, outermost last
+pub fn write_tang<W: Write>(out: &mut W, tang: Noun) -> io::Result<()> {
+    let mut tanks = Vec::new();
+    let mut list = tang;
+    while let Ok(cell) = list.as_cell() {
+        tanks.push(cell.head());
+        list = cell.tail();
+    }
+    for tank in tanks.into_iter().rev() {
+        writeln!(out, "{}", tank_to_string(tank))?;
+    }
+    Ok(())
+}
+
+/** Render a tank on one line, as +ram:re does
+ *
+ * +$  tank
+ *   $~  [%leaf ~]
+ *   $%  [%leaf p=tape]
+ *       [%palm p=(qual tape tape tape tape) q=(list tank)]
+ *       [%rose p=(trel tape tape tape) q=(list tank)]
+ *   ==
+ */
+pub fn tank_to_string(tank: Noun) -> String {
+    let mut out = String::new();
+    write_tank(&mut out, tank);
+    out
+}
+
+fn write_tank(out: &mut String, tank: Noun) {
+    match tank_parts(tank) {
+        None => out.push_str(&noun_to_text(tank)),
+        Some(TankParts::Leaf(tape)) => write_tape(out, tape),
+        Some(TankParts::Wide(sep, open, close, mut tanks)) => {
+            for tape in open {
+                write_tape(out, tape);
+            }
+            let mut first = true;
+            while let Ok(cell) = tanks.as_cell() {
+                if !first {
+                    write_tape(out, sep);
+                }
+                first = false;
+                write_tank(out, cell.head());
+                tanks = cell.tail();
+            }
+            write_tape(out, close);
+        }
+    }
+}
+
+enum TankParts {
+    Leaf(Noun),
+    /// separator, opening tapes, closing tape, and list of tanks
+    Wide(Noun, Vec<Noun>, Noun, Noun),
+}
+
+fn tank_parts(tank: Noun) -> Option<TankParts> {
+    let cell = tank.as_cell().ok()?;
+    match cell.head().as_direct().ok()?.data() {
+        tas!(b"leaf") => Some(TankParts::Leaf(cell.tail())),
+        tas!(b"palm") => {
+            // +ram renders a %palm as a %rose, welding the two opening tapes
+            let tapes = cell.tail().as_cell().ok()?;
+            let tanks = tapes.tail();
+            let sep = tapes.head().slot(2).ok()?;
+            let open = vec![tapes.head().slot(6).ok()?, tapes.head().slot(14).ok()?];
+            let close = tapes.head().slot(15).ok()?;
+            Some(TankParts::Wide(sep, open, close, tanks))
+        }
+        tas!(b"rose") => {
+            let tapes = cell.tail().as_cell().ok()?;
+            let tanks = tapes.tail();
+            let sep = tapes.head().slot(2).ok()?;
+            let open = vec![tapes.head().slot(6).ok()?];
+            let close = tapes.head().slot(7).ok()?;
+            Some(TankParts::Wide(sep, open, close, tanks))
+        }
+        _ => None,
+    }
+}
+
+fn write_tape(out: &mut String, tape: Noun) {
+    let mut bytes = Vec::new();
+    let mut list = tape;
+    while let Ok(cell) = list.as_cell() {
+        if let Ok(c) = cell.head().as_direct() {
+            bytes.push(c.data() as u8);
+        }
+        list = cell.tail();
+    }
+    out.push_str(&String::from_utf8_lossy(&bytes));
+}
+
+/// Render a noun in the text format
+pub fn noun_to_text(noun: Noun) -> String {
+    let mut out = String::new();
+    write_noun(&mut out, noun);
+    out
+}
+
+fn write_noun(out: &mut String, noun: Noun) {
+    enum Write {
+        Noun(Noun),
+        Text(&'static str),
+    }
+
+    //  An explicit work stack rather than recursion, since nouns may be deeper than the native stack
+    let mut work = vec![Write::Noun(noun)];
+    while let Some(next) = work.pop() {
+        let noun = match next {
+            Write::Text(text) => {
+                out.push_str(text);
+                continue;
+            }
+            Write::Noun(noun) => noun,
+        };
+        match noun.as_either_atom_cell() {
+            Left(atom) => write_atom(out, atom),
+            Right(cell) => {
+                let mut elements = vec![cell.head()];
+                let mut tail = cell.tail();
+                while let Ok(cell) = tail.as_cell() {
+                    elements.push(cell.head());
+                    tail = cell.tail();
+                }
+                elements.push(tail);
+
+                out.push('[');
+                work.push(Write::Text("]"));
+                for (i, element) in elements.into_iter().enumerate().rev() {
+                    work.push(Write::Noun(element));
+                    if i > 0 {
+                        work.push(Write::Text(" "));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn write_atom(out: &mut String, atom: Atom) {
+    let bytes = &atom.as_bytes()[0..met3_usize(atom)];
+    if bytes.len() > 1 && is_term(bytes) {
+        out.push('%');
+        out.push_str(std::str::from_utf8(bytes).unwrap());
+    } else if let Ok(direct) = atom.as_direct() {
+        out.push_str(&direct.data().to_string());
+    } else {
+        out.push_str("0x");
+        for byte in bytes.iter().rev() {
+            out.push_str(&format!("{:02x}", byte));
+        }
+    }
+}
+
+fn is_term(bytes: &[u8]) -> bool {
+    bytes[0].is_ascii_lowercase()
+        && bytes
+            .iter()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || *b == b'-')
+}
+
+/// Parse a noun in the text format
+pub fn text_to_noun(stack: &mut NockStack, text: &str) -> Result<Noun, String> {
+    let mut tokens = Tokens {
+        text: text.as_bytes(),
+        cursor: 0,
+    };
+    let noun = parse_noun(stack, &mut tokens)?;
+    match tokens.next() {
+        None => Ok(noun),
+        Some(token) => Err(format!("unexpected {} after noun", token)),
+    }
+}
+
+struct Tokens<'a> {
+    text: &'a [u8],
+    cursor: usize,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        while self.cursor < self.text.len() && self.text[self.cursor].is_ascii_whitespace() {
+            self.cursor += 1;
+        }
+        if self.cursor == self.text.len() {
+            return None;
+        }
+        let start = self.cursor;
+        if self.text[start] == b'[' || self.text[start] == b']' {
+            self.cursor += 1;
+        } else {
+            while self.cursor < self.text.len()
+                && !self.text[self.cursor].is_ascii_whitespace()
+                && self.text[self.cursor] != b'['
+                && self.text[self.cursor] != b']'
+            {
+                self.cursor += 1;
+            }
+        }
+        std::str::from_utf8(&self.text[start..self.cursor]).ok()
+    }
+}
+
+fn parse_noun(stack: &mut NockStack, tokens: &mut Tokens) -> Result<Noun, String> {
+    //  The elements of each cell still open, innermost last
+    let mut open: Vec<Vec<Noun>> = Vec::new();
+    loop {
+        let noun = match tokens.next() {
+            None if open.is_empty() => return Err("expected noun".to_string()),
+            None => return Err("unclosed [".to_string()),
+            Some("[") => {
+                open.push(Vec::new());
+                continue;
+            }
+            Some("]") => match open.pop() {
+                None => return Err("unexpected ]".to_string()),
+                Some(elements) if elements.len() < 2 => {
+                    return Err("cell with fewer than two elements".to_string())
+                }
+                Some(elements) => T(stack, &elements),
+            },
+            Some(token) => parse_atom(stack, token)?,
+        };
+        match open.last_mut() {
+            None => return Ok(noun),
+            Some(elements) => elements.push(noun),
+        }
+    }
+}
+
+fn parse_atom(stack: &mut NockStack, token: &str) -> Result<Noun, String> {
+    if token == "~" {
+        return Ok(D(0));
+    }
+    if let Some(term) = token.strip_prefix('%') {
+        if term.is_empty() || !is_term(term.as_bytes()) {
+            return Err(format!("bad term {}", token));
+        }
+        return Ok(read_atom(stack, term.as_bytes()).as_noun());
+    }
+    let (digits, radix) = match token.strip_prefix("0x") {
+        Some(hex) => (hex, 16),
+        None => (token, 10),
+    };
+    let digits: String = digits.chars().filter(|c| *c != '.' && *c != '_').collect();
+    let big = UBig::from_str_radix(&digits, radix).map_err(|_e| format!("bad atom {}", token))?;
+    Ok(Atom::from_ubig(stack, &big).as_noun())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jets::util::test::assert_noun_eq;
+
+    #[test]
+    fn text_round_trip() {
+        let mut stack = NockStack::new(8 << 10 << 10, 0);
+        let text = "[%pill [0 1.024 0x1.0000.0000.0000.0000] ~ [%a-b [1 2] 3] 4]";
+        let noun = text_to_noun(&mut stack, text).unwrap();
+        let big = Atom::from_ubig(&mut stack, &(UBig::from(1u64) << 64)).as_noun();
+        let term = read_atom(&mut stack, b"a-b").as_noun();
+        let inner = T(&mut stack, &[D(1), D(2)]);
+        let pill = read_atom(&mut stack, b"pill").as_noun();
+        let expected = T(&mut stack, &[D(0), D(1024), big]);
+        let rest = T(&mut stack, &[term, inner, D(3)]);
+        let expected = T(&mut stack, &[pill, expected, D(0), rest, D(4)]);
+        assert_noun_eq(&mut stack, noun, expected);
+
+        let printed = noun_to_text(noun);
+        assert_eq!(
+            printed,
+            "[%pill [0 1024 0x010000000000000000] 0 [%a-b [1 2] 3] 4]"
+        );
+        let reparsed = text_to_noun(&mut stack, &printed).unwrap();
+        assert_noun_eq(&mut stack, reparsed, noun);
+    }
+
+    #[test]
+    fn text_deep() {
+        // Deeper than the native stack would allow if printing or parsing recursed
+        let mut stack = NockStack::new(64 << 10 << 10, 0);
+        let depth = 1 << 20;
+        let mut noun = D(0);
+        for _ in 0..depth {
+            noun = T(&mut stack, &[noun, D(1)]);
+        }
+        let text = noun_to_text(noun);
+        assert_eq!(text.len(), 4 * depth + 1);
+        let reparsed = text_to_noun(&mut stack, &text).unwrap();
+        assert_noun_eq(&mut stack, reparsed, noun);
+    }
+
+    #[test]
+    fn text_errors() {
+        let mut stack = NockStack::new(8 << 10 << 10, 0);
+        assert!(text_to_noun(&mut stack, "[1]").is_err());
+        assert!(text_to_noun(&mut stack, "[1 2").is_err());
+        assert!(text_to_noun(&mut stack, "1 2").is_err());
+        assert!(text_to_noun(&mut stack, "]").is_err());
+        assert!(text_to_noun(&mut stack, "").is_err());
+        assert!(text_to_noun(&mut stack, "%Foo").is_err());
+        assert!(text_to_noun(&mut stack, "0xzz").is_err());
+    }
+}
@@ -0,0 +1,903 @@
+/** The commands which work on a pier without a king: booting a pill and replaying an event log,
+ * checking the serf against a recorded session, and inspecting, repairing and moving snapshots.
+ */
+use crate::cli::{noun_to_text, read_atom, write_tang};
+use crate::jets::cold::Cold;
+use crate::jets::hot::HotEntry;
+use crate::mem::NockStack;
+use crate::mug::*;
+use crate::newt::{read_frame, write_frame, Newt, TRANSCRIPT_PLEA, TRANSCRIPT_WRIT};
+use crate::noun::{CellMemory, Noun, Slots, D, T};
+use crate::persist::{
+    pma_allocations, pma_check, pma_check_file, pma_contains, pma_hash_cons_stats, pma_meta_get,
+    pma_meta_set, pma_open, pma_open_read_only, pma_sync, Persist,
+};
+use crate::serf::{
+    life, play, retained_snapshots, serve, set_retained_snapshots, slot, stack_size, BTMetaField,
+    Context, Snapshot, SnapshotMem, SnapshotMemV1, PMA_CURRENT_SNAPSHOT_VERSION,
+    SNAPSHOTS_RETAINED,
+};
+use crate::serialization::{cue, jam};
+use crate::trace::*;
+use crate::unifying_equality::unifying_equality;
+use ares_macros::tas;
+use either::Either::{Left, Right};
+use std::collections::HashSet;
+use std::fs::{create_dir_all, File};
+use std::io::{self, Write};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::result::Result;
+
+crate::gdb!();
+
+/**
+ * Boot a pill without a king: run the lifecycle formula over the pill's boot events, and print the
+ * resulting event number and mug, or the stack trace if it crashes.
+ *
+ * `ares boot <pill> [pier]`
+ *
+ * Without a pier, the snapshot is kept in a scratch directory and discarded.
+ *
+ * Here and in the other commands which load a pier without a king, `ARES_LOOM` in the environment
+ * sets the size of the NockStack as the serf's loom argument does, as the log2 of its bytes.
+ */
+pub fn boot(constant_hot_state: &[HotEntry]) -> io::Result<()> {
+    let pill_path = std::env::args()
+        .nth(2)
+        .ok_or(io::Error::other("no pill path"))?;
+    let (pier_path, _scratch) = match std::env::args().nth(3) {
+        Some(pier) => (PathBuf::from(pier), None),
+        None => {
+            let path = std::env::temp_dir().join(format!("ares-boot-{}", std::process::id()));
+            (path.clone(), Some(ScratchDir(path)))
+        }
+    };
+
+    let mut context = fresh_context(pier_path, None, constant_hot_state)?;
+    let bot = read_pill(&mut context, pill_path)?;
+    let res = life(&mut context, bot);
+    report(&mut context, "boot", res)
+}
+
+/// A directory which is removed, with everything in it, once it goes out of scope
+struct ScratchDir(PathBuf);
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/**
+ * Replay an event log without a king: boot a pill into a fresh pier, apply a sequence of events
+ * on top of it, and print the final event number and mug, or the stack trace of the event which
+ * crashed. The time taken by each event is recorded in a trace file in the pier.
+ *
+ * `ares replay <pill> <events> <pier>`
+ *
+ * The events are the jammed `(pair @da ovum)`s which follow the pill's boot sequence in the event
+ * log. They are read either from a single file holding a jammed list of events, or from a
+ * directory holding one jammed event per file, applied in file name order.
+ */
+pub fn replay(constant_hot_state: &[HotEntry]) -> io::Result<()> {
+    let pill_path = std::env::args()
+        .nth(2)
+        .ok_or(io::Error::other("no pill path"))?;
+    let events_path = std::env::args()
+        .nth(3)
+        .ok_or(io::Error::other("no events path"))?;
+    let pier_path = PathBuf::from(
+        std::env::args()
+            .nth(4)
+            .ok_or(io::Error::other("no pier path"))?,
+    );
+
+    let trace_config = TraceConfig::from_env()?.unwrap_or_default();
+    let mut trace_info = create_trace_file(pier_path.clone(), trace_config)?;
+    write_metadata(&mut trace_info)?;
+
+    let mut context = fresh_context(pier_path, Some(trace_info), constant_hot_state)?;
+    let bot = read_pill(&mut context, pill_path)?;
+    //  Read the events only once the lifecycle has run, since it pops the frame they'd be in
+    let res = match life(&mut context, bot) {
+        Ok(()) => {
+            let lit = read_events(&mut context.nock_context.stack, events_path)?;
+            play(&mut context, lit)
+        }
+        Err(goof) => Err(goof),
+    };
+    report(&mut context, "replay", res)
+}
+
+/**
+ * Check the serf against a recorded session between a king and a serf: feed the recorded writs
+ * to a fresh pier, and compare the pleas the serf sends back to the recorded ones.
+ *
+ * `ares conform <transcript> <pier>`
+ * `ares conform <writs> <pleas> <pier>`
+ *
+ * The session is either a transcript, as the serf records with `ARES_TRANSCRIPT`, or the two
+ * streams of newt frames between a king and a serf, as captured off the pipes of a vere serf: what
+ * the king wrote to it, such as the event log it replayed on boot, and the serf's replies.
+ *
+ * The session must start from an unbooted pier. %slog and %flog pleas are skipped, and only
+ * the motes of failed events are compared, since stack traces and debug output may differ between
+ * runtimes. The serf's own transcript is written to `.urb/put/conform.transcript` in the pier.
+ */
+pub fn conform(constant_hot_state: &[HotEntry]) -> io::Result<()> {
+    let mut args = std::env::args().skip(2).collect::<Vec<String>>();
+    let pier_path = PathBuf::from(args.pop().ok_or(io::Error::other("no transcript path"))?);
+    let (transcript_path, pleas_path) = match &args[..] {
+        [transcript] => (transcript, None),
+        [writs, pleas] => (writs, Some(pleas)),
+        [] => return Err(io::Error::other("no pier path")),
+        _ => return Err(io::Error::other("too many arguments")),
+    };
+    let mut put_path = pier_path.clone();
+    put_path.push(".urb");
+    put_path.push("put");
+    create_dir_all(&put_path)?;
+    let ours_path = put_path.join("conform.transcript");
+
+    let mut context = fresh_context(pier_path, None, constant_hot_state)?;
+
+    //  Split the recorded session into the writs to send and the pleas to expect
+    let mut writs = Vec::new();
+    let mut expected = Vec::new();
+    let stack = &mut context.nock_context.stack;
+    let mut theirs = File::open(transcript_path)?;
+    match pleas_path {
+        None => {
+            while let Some((tag, atom)) = read_frame(stack, &mut theirs)? {
+                match tag {
+                    TRANSCRIPT_WRIT => write_frame(&mut writs, 0, atom.as_bytes())?,
+                    TRANSCRIPT_PLEA => expected.push(atom.as_bytes().to_vec()),
+                    _ => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "bad transcript frame",
+                        ))
+                    }
+                }
+            }
+        }
+        //  Frames on the wire are all tagged with newt's version, 0
+        Some(pleas_path) => {
+            while let Some((_, atom)) = read_frame(stack, &mut theirs)? {
+                write_frame(&mut writs, 0, atom.as_bytes())?;
+            }
+            let mut replies = File::open(pleas_path)?;
+            while let Some((_, atom)) = read_frame(stack, &mut replies)? {
+                expected.push(atom.as_bytes().to_vec());
+            }
+        }
+    }
+
+    let mut newt = Newt::from_io(io::Cursor::new(writs), io::sink());
+    newt.record(File::create(&ours_path)?);
+    context.nock_context.newt = newt;
+    serve(&mut context)?;
+
+    let stack = &mut context.nock_context.stack;
+    let mut ours = File::open(&ours_path)?;
+    let mut actual = Vec::new();
+    while let Some((tag, atom)) = read_frame(stack, &mut ours)? {
+        if tag == TRANSCRIPT_PLEA {
+            let plea = cue(stack, atom);
+            if let Some(digest) = plea_digest(stack, plea)? {
+                actual.push(digest);
+            }
+        }
+    }
+    let mut expected_digests = Vec::new();
+    for bytes in expected {
+        let atom = read_atom(stack, &bytes);
+        let plea = cue(stack, atom);
+        if let Some(digest) = plea_digest(stack, plea)? {
+            expected_digests.push(digest);
+        }
+    }
+
+    let mut out = io::stdout();
+    let mut differ = 0;
+    for idx in 0..std::cmp::max(expected_digests.len(), actual.len()) {
+        let same = match (expected_digests.get_mut(idx), actual.get_mut(idx)) {
+            (Some(want), Some(got)) => unsafe { unifying_equality(stack, want, got) },
+            _ => false,
+        };
+        if !same {
+            differ += 1;
+            let show = |digest: Option<&Noun>| match digest {
+                Some(noun) => noun_to_text(*noun),
+                None => String::from("nothing"),
+            };
+            writeln!(
+                out,
+                "conform: plea {}: expected {}, got {}",
+                idx,
+                show(expected_digests.get(idx)),
+                show(actual.get(idx))
+            )?;
+        }
+    }
+
+    if differ == 0 {
+        writeln!(out, "conform: {} pleas match", actual.len())?;
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{} pleas differ from the transcript",
+            differ
+        )))
+    }
+}
+
+/** The part of a plea which should agree between runtimes, or None if it is free to differ.
+ *
+ * Protocol versions and stack traces are dropped, and effects are compared by mug.
+ */
+fn plea_digest(stack: &mut NockStack, plea: Noun) -> io::Result<Option<Noun>> {
+    let tag = slot(plea, 2)?;
+    //  %flog has no kind
+    let kind = plea.slot(6).unwrap_or(D(0));
+    let digest = match (direct_data(tag), direct_data(kind)) {
+        (tas!(b"slog"), _) | (tas!(b"flog"), _) => return Ok(None),
+        //  [%ripe [pro hon nok] eve mug]
+        (tas!(b"ripe"), _) => T(stack, &[tag, slot(plea, 7)?]),
+        //  [%peek %bail dud]
+        (tas!(b"peek"), tas!(b"bail")) => T(stack, &[tag, kind, slot(plea, 14)?]),
+        //  [%play %bail eve mug dud]
+        (tas!(b"play"), tas!(b"bail")) => T(
+            stack,
+            &[tag, kind, slot(plea, 14)?, slot(plea, 30)?, slot(plea, 62)?],
+        ),
+        //  [%work %done eve mug fec]
+        (tas!(b"work"), tas!(b"done")) => {
+            let fec = mug_u32(stack, slot(plea, 31)?) as u64;
+            T(
+                stack,
+                &[tag, kind, slot(plea, 14)?, slot(plea, 30)?, D(fec)],
+            )
+        }
+        //  [%work %swap eve mug job fec]
+        (tas!(b"work"), tas!(b"swap")) => T(stack, &[tag, kind, slot(plea, 14)?, slot(plea, 30)?]),
+        //  [%work %bail lud]
+        (tas!(b"work"), tas!(b"bail")) => {
+            let mut motes = Vec::new();
+            let mut lud = slot(plea, 7)?;
+            while let Ok(cell) = lud.as_cell() {
+                motes.push(slot(cell.head(), 2)?);
+                lud = cell.tail();
+            }
+            let mut list = D(0);
+            for mote in motes.into_iter().rev() {
+                list = T(stack, &[mote, list]);
+            }
+            T(stack, &[tag, kind, list])
+        }
+        _ => plea,
+    };
+    Ok(Some(digest))
+}
+
+fn direct_data(noun: Noun) -> u64 {
+    noun.as_direct().map_or(u64::MAX, |direct| direct.data())
+}
+
+/**
+ * List the snapshots kept in a pier: the current one, then the rollback points retained at each
+ * %save, newest first.
+ *
+ * `ares snapshots <pier>`
+ */
+pub fn snapshots() -> io::Result<()> {
+    let pier_path = std::env::args()
+        .nth(2)
+        .ok_or(io::Error::other("no pier path"))?;
+
+    let mut out = io::stdout();
+    let Some(current) = open_snapshots(pier_path, false)? else {
+        return writeln!(out, "snapshots: pier is not booted");
+    };
+    writeln!(out, "current: {}", describe_snapshot(&current))?;
+    for snapshot in retained_snapshots() {
+        writeln!(out, "retained: {}", describe_snapshot(&snapshot))?;
+    }
+    Ok(())
+}
+
+fn describe_snapshot(snapshot: &Snapshot) -> String {
+    let mut description = format!("epoch {} event {}", snapshot.epoch(), snapshot.event_num());
+    if snapshot.time() != 0 {
+        description.push_str(&format!(", saved at {}", snapshot.time()));
+    }
+    description
+}
+
+/**
+ * Roll a pier back to a retained snapshot, dropping the current snapshot and any retained ones
+ * which are newer. When next started, the serf reports the earlier event to the king, which
+ * replays the event log from there.
+ *
+ * `ares rollback <pier> <event>`
+ *
+ * Space held by the dropped snapshots is reclaimed at the next %pack.
+ */
+pub fn rollback() -> io::Result<()> {
+    let pier_path = std::env::args()
+        .nth(2)
+        .ok_or(io::Error::other("no pier path"))?;
+    let event_num: u64 = std::env::args()
+        .nth(3)
+        .ok_or(io::Error::other("no event number"))?
+        .parse()
+        .or(Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "event number is not integer",
+        )))?;
+
+    if open_snapshots(pier_path, true)?.is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "pier is not booted",
+        ));
+    }
+    let retained = retained_snapshots();
+    let idx = retained
+        .iter()
+        .position(|snapshot| snapshot.event_num() == event_num)
+        .ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no snapshot retained at that event",
+        ))?;
+
+    unsafe {
+        pma_meta_set(
+            BTMetaField::Snapshot as usize,
+            retained[idx].handle_to_u64(),
+        );
+    }
+    set_retained_snapshots(&retained[idx + 1..]);
+    pma_sync();
+
+    writeln!(
+        io::stdout(),
+        "rollback: epoch {} event {}",
+        retained[idx].epoch(),
+        retained[idx].event_num()
+    )
+}
+
+/**
+ * Check the integrity of a pier's PMA: its meta pages, B-tree and free lists, and then every
+ * pointer reachable from the current and retained snapshots, each of which must fall within an
+ * allocation. Each problem is reported on stderr.
+ *
+ * `ares check <pier>`
+ *
+ * The pier is only read: snapshots at an older version are checked as they are, unmigrated.
+ * Data pages carry no checksums, so corrupt noun contents are only found where they break a
+ * pointer.
+ */
+pub fn check() -> io::Result<()> {
+    let pier_path = PathBuf::from(
+        std::env::args()
+            .nth(2)
+            .ok_or(io::Error::other("no pier path"))?,
+    );
+    let snap_path = pier_path.join(".urb").join("chk");
+    if !snap_path.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no snapshot"));
+    }
+
+    let mut out = io::stdout();
+    // The PMA is only opened once its meta pages and B-tree are known to be sound, since opening
+    // it otherwise may abort.
+    let mut problems = pma_check_file(snap_path)?;
+    let mut version = 0;
+    if problems == 0 {
+        version = open_pier_pma(&pier_path, false)?;
+        problems = pma_check();
+    }
+    if problems > 0 {
+        writeln!(out, "check: {} problems in the PMA", problems)?;
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "PMA is corrupt"));
+    }
+    writeln!(out, "check: meta pages, B-tree and free lists ok")?;
+    if version == 0 {
+        return writeln!(out, "check: pier is not booted");
+    }
+    if version > PMA_CURRENT_SNAPSHOT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "snapshot version {} is newer than supported version {}",
+                version, PMA_CURRENT_SNAPSHOT_VERSION
+            ),
+        ));
+    }
+    let snapshot_size = if version == 1 {
+        size_of::<SnapshotMemV1>()
+    } else {
+        size_of::<SnapshotMem>()
+    };
+
+    let allocations = pma_allocations();
+    let valid = |ptr: *const u8, len: usize| {
+        if len == 0 {
+            return true;
+        }
+        let addr = ptr as usize;
+        let idx = allocations.partition_point(|(lo, _)| *lo as usize <= addr);
+        addr & 7 == 0
+            && unsafe { pma_contains(ptr, len) }
+            && idx > 0
+            && addr + len <= allocations[idx - 1].1 as usize
+    };
+
+    let mut nouns = NounCheck {
+        valid: &valid,
+        visited: HashSet::new(),
+        cells: 0,
+        atoms: 0,
+        problems: 0,
+    };
+    let mut handles = vec![(
+        "current".to_string(),
+        pma_meta_get(BTMetaField::Snapshot as usize),
+    )];
+    for idx in 0..SNAPSHOTS_RETAINED {
+        let handle = pma_meta_get(BTMetaField::Retained as usize + idx);
+        if handle == 0 {
+            break;
+        }
+        handles.push((format!("retained {}", idx), handle));
+    }
+
+    let mut problems = 0;
+    for (label, handle) in handles {
+        let before = nouns.problems;
+        if !valid(handle as *const u8, snapshot_size) {
+            eprintln!(
+                "check: {} snapshot at {:#x} outside any PMA allocation",
+                label, handle
+            );
+            problems += 1;
+            continue;
+        }
+        unsafe {
+            let (event_num, arvo, mut cold) = if version == 1 {
+                let snapshot = handle as *const SnapshotMemV1;
+                ((*snapshot).event_num, (*snapshot).arvo, (*snapshot).cold)
+            } else {
+                let snapshot = handle as *const SnapshotMem;
+                ((*snapshot).event_num, (*snapshot).arvo, (*snapshot).cold)
+            };
+            nouns.check(&label, "arvo", arvo);
+            let mut cold_nouns = Vec::new();
+            cold.for_each_noun_checked(
+                &|ptr, len| {
+                    let ok = valid(ptr, len);
+                    if !ok {
+                        eprintln!(
+                            "check: {} cold state: {} bytes at {:p} outside any PMA allocation",
+                            label, len, ptr
+                        );
+                    }
+                    ok
+                },
+                |noun| cold_nouns.push(*noun),
+            );
+            for noun in cold_nouns {
+                nouns.check(&label, "cold state", noun);
+            }
+            let found = nouns.problems - before;
+            if found == 0 {
+                writeln!(out, "check: {} snapshot, event {}: ok", label, event_num)?;
+            } else {
+                writeln!(
+                    out,
+                    "check: {} snapshot, event {}: {} problems",
+                    label, event_num, found
+                )?;
+            }
+        }
+    }
+    problems += nouns.problems;
+    writeln!(
+        out,
+        "check: {} cells and {} indirect atoms reached",
+        nouns.cells, nouns.atoms
+    )?;
+    if problems > 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "snapshot is corrupt",
+        ));
+    }
+    Ok(())
+}
+
+/// Walks snapshot nouns for [check], visiting each allocation once
+struct NounCheck<'a, V: Fn(*const u8, usize) -> bool> {
+    valid: &'a V,
+    visited: HashSet<u64>,
+    cells: usize,
+    atoms: usize,
+    problems: usize,
+}
+
+impl<'a, V: Fn(*const u8, usize) -> bool> NounCheck<'a, V> {
+    /// Check every allocated noun reachable from `root`, reporting each problem with the cell
+    /// which refers to it
+    unsafe fn check(&mut self, label: &str, what: &str, root: Noun) {
+        let mut work: Vec<(Noun, Option<(*const u64, &str)>)> = vec![(root, None)];
+        while let Some((noun, parent)) = work.pop() {
+            let Ok(allocated) = noun.as_allocated() else {
+                continue;
+            };
+            let ptr = allocated.to_raw_pointer();
+            if !self.visited.insert(ptr as u64) {
+                continue;
+            }
+            let from = match parent {
+                Some((cell, side)) => format!("{} of cell {:p}", side, cell),
+                None => "root".to_string(),
+            };
+            let kind = if allocated.is_indirect() {
+                "indirect atom"
+            } else {
+                "cell"
+            };
+
+            // A cell is three words, and an indirect atom is at least three: its metadata, its size
+            // in words, and at least one word of data. Check those before reading the size.
+            if !(self.valid)(ptr as *const u8, size_of::<CellMemory>()) {
+                eprintln!(
+                    "check: {} {}: {} {:p} ({}) outside any PMA allocation",
+                    label, what, kind, ptr, from
+                );
+                self.problems += 1;
+                continue;
+            }
+            if allocated.forwarding_pointer().is_some() {
+                eprintln!(
+                    "check: {} {}: {} {:p} ({}) is a forwarding pointer",
+                    label, what, kind, ptr, from
+                );
+                self.problems += 1;
+                continue;
+            }
+
+            match allocated.as_either() {
+                Left(indirect) => {
+                    let size = indirect.size();
+                    if size == 0 || !(self.valid)(ptr as *const u8, (size + 2) << 3) {
+                        eprintln!(
+                            "check: {} {}: indirect atom {:p} ({}) of {} words overruns its allocation",
+                            label, what, ptr, from, size
+                        );
+                        self.problems += 1;
+                        continue;
+                    }
+                    self.atoms += 1;
+                }
+                Right(cell) => {
+                    self.cells += 1;
+                    work.push((cell.tail(), Some((ptr, "tail"))));
+                    work.push((cell.head(), Some((ptr, "head"))));
+                }
+            }
+        }
+    }
+}
+
+/**
+ * Write a pier's state as a single jammed noun, independent of the PMA's layout, to a file or to
+ * stdout: `[%snap epoch=@ eve=@ arvo=* cold=*]`, with the cold state as from [Cold::to_noun].
+ *
+ * `ares export <pier> [file.jam]`
+ */
+pub fn export(constant_hot_state: &[HotEntry]) -> io::Result<()> {
+    let pier_path = PathBuf::from(
+        std::env::args()
+            .nth(2)
+            .ok_or(io::Error::other("no pier path"))?,
+    );
+    let snap_path = pier_path.join(".urb").join("chk");
+    if !snap_path.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no snapshot"));
+    }
+
+    let mut context = Context::load(
+        pier_path,
+        snap_path,
+        command_stack_size()?,
+        Newt::new_mock(),
+        None,
+        constant_hot_state,
+    );
+    if context.epoch == 0 && context.event_num == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "pier is not booted",
+        ));
+    }
+
+    let stack = &mut context.nock_context.stack;
+    let cold = context.nock_context.cold.to_noun(stack);
+    let snap = T(
+        stack,
+        &[
+            D(tas!(b"snap")),
+            D(context.epoch),
+            D(context.event_num),
+            context.arvo,
+            cold,
+        ],
+    );
+    let jammed = jam(stack, snap);
+    let bytes = &jammed.as_bytes()[0..met3_usize(jammed)];
+    match std::env::args().nth(3) {
+        Some(path) => std::fs::write(path, bytes),
+        None => io::stdout().write_all(bytes),
+    }
+}
+
+/**
+ * Rebuild a pier's snapshot from the output of [export], in a fresh PMA.
+ *
+ * `ares import <file.jam> <pier>`
+ */
+pub fn import(constant_hot_state: &[HotEntry]) -> io::Result<()> {
+    let snap_file = std::env::args()
+        .nth(2)
+        .ok_or(io::Error::other("no snapshot file"))?;
+    let pier_path = PathBuf::from(
+        std::env::args()
+            .nth(3)
+            .ok_or(io::Error::other("no pier path"))?,
+    );
+
+    let bytes = std::fs::read(snap_file)?;
+    let mut context = fresh_context(pier_path, None, constant_hot_state)?;
+    let stack = &mut context.nock_context.stack;
+    let atom = read_atom(stack, &bytes);
+    let snap = cue(stack, atom);
+    let bad = |_e| io::Error::new(io::ErrorKind::InvalidData, "not an exported snapshot");
+    let tag = slot(snap, 2)?;
+    if !tag.is_direct() || tag.as_direct().unwrap().data() != tas!(b"snap") {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not an exported snapshot",
+        ));
+    }
+    let epoch = slot(snap, 6)?.as_direct().map_err(bad)?.data();
+    let event_num = slot(snap, 14)?.as_direct().map_err(bad)?.data();
+    let arvo = slot(snap, 30)?;
+    let cold = Cold::from_noun(stack, slot(snap, 31)?)
+        .map_err(|_e| io::Error::new(io::ErrorKind::InvalidData, "bad cold state in snapshot"))?;
+
+    context.epoch = epoch;
+    context.nock_context.cold = cold;
+    unsafe {
+        context.event_update(event_num, arvo);
+    }
+    pma_sync();
+
+    writeln!(
+        io::stdout(),
+        "import: event {} mug {:x}",
+        context.event_num,
+        context.mug
+    )
+}
+
+/// Open the PMA of a pier, only to read it unless `writable`, and return its current snapshot, if
+/// it has one
+fn open_snapshots<P: AsRef<Path>>(pier_path: P, writable: bool) -> io::Result<Option<Snapshot>> {
+    match open_pier_pma(pier_path.as_ref(), writable)? {
+        0 => Ok(None),
+        PMA_CURRENT_SNAPSHOT_VERSION => Ok(Some(unsafe {
+            Snapshot::handle_from_u64(pma_meta_get(BTMetaField::Snapshot as usize))
+        })),
+        // Only the serf migrates snapshots, so that inspecting a pier never changes it
+        version if version < PMA_CURRENT_SNAPSHOT_VERSION => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "snapshot version {} predates version {}: run the serf on the pier to migrate it",
+                version, PMA_CURRENT_SNAPSHOT_VERSION
+            ),
+        )),
+        version => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "snapshot version {} is newer than supported version {}",
+                version, PMA_CURRENT_SNAPSHOT_VERSION
+            ),
+        )),
+    }
+}
+
+/// Open the PMA of a pier, only to read it unless `writable`, and return its snapshot version
+fn open_pier_pma(pier_path: &Path, writable: bool) -> io::Result<u64> {
+    let snap_path = pier_path.join(".urb").join("chk");
+    if !snap_path.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "no snapshot"));
+    }
+    if writable {
+        pma_open(snap_path)?;
+    } else {
+        pma_open_read_only(snap_path)?;
+    }
+    Ok(pma_meta_get(BTMetaField::SnapshotVersion as usize))
+}
+
+/// Load the snapshot of a pier for `ares boot`, `ares replay` or `ares conform`, which must not yet
+/// be booted
+fn fresh_context(
+    pier_path: PathBuf,
+    trace_info: Option<TraceInfo>,
+    constant_hot_state: &[HotEntry],
+) -> io::Result<Context> {
+    let mut snap_path = pier_path.clone();
+    snap_path.push(".urb");
+    snap_path.push("chk");
+    create_dir_all(&snap_path)?;
+
+    let context = Context::load(
+        pier_path,
+        snap_path,
+        command_stack_size()?,
+        Newt::new_mock(),
+        trace_info,
+        constant_hot_state,
+    );
+    if context.epoch != 0 || context.event_num != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "pier is already booted",
+        ));
+    }
+    Ok(context)
+}
+
+/// Read a jammed pill and return its boot events
+fn read_pill<P: AsRef<Path>>(context: &mut Context, pill_path: P) -> io::Result<Noun> {
+    let pill_bytes = std::fs::read(pill_path)?;
+    let stack = &mut context.nock_context.stack;
+    let pill_atom = read_atom(stack, &pill_bytes);
+    let pill = cue(stack, pill_atom);
+    //  [%pill nam=term boot-ova=(list) kernel-ova=(list) userspace-ova=(list)]
+    let tag = slot(pill, 2)?;
+    if !tag.is_direct() || tag.as_direct().unwrap().data() != tas!(b"pill") {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a pill"));
+    }
+    slot(pill, 14)
+}
+
+/// Read a list of events from a jammed list, or from a directory of jammed events
+fn read_events<P: AsRef<Path>>(stack: &mut NockStack, events_path: P) -> io::Result<Noun> {
+    let events_path = events_path.as_ref();
+    if !events_path.is_dir() {
+        let bytes = std::fs::read(events_path)?;
+        let atom = read_atom(stack, &bytes);
+        return Ok(cue(stack, atom));
+    }
+
+    let mut paths = std::fs::read_dir(events_path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+    paths.sort();
+
+    let mut lit = D(0);
+    for path in paths.iter().rev() {
+        let bytes = std::fs::read(path)?;
+        let atom = read_atom(stack, &bytes);
+        let job = cue(stack, atom);
+        lit = T(stack, &[job, lit]);
+    }
+    Ok(lit)
+}
+
+/// Print the event number and mug reached by `ares boot` or `ares replay`, or the goof it hit
+fn report(context: &mut Context, name: &str, res: Result<(), Noun>) -> io::Result<()> {
+    let mut out = io::stdout();
+    match res {
+        Ok(()) => {
+            pma_sync();
+            writeln!(
+                out,
+                "{}: event {} mug {:x}",
+                name, context.event_num, context.mug
+            )?;
+            if context.hash_cons {
+                let stats = pma_hash_cons_stats();
+                writeln!(
+                    out,
+                    "{}: hash-consing: {} bytes persisted of {}, {:.2}x dedup",
+                    name,
+                    stats.consed,
+                    stats.plain,
+                    stats.ratio()
+                )?;
+            }
+            Ok(())
+        }
+        Err(goof) => {
+            let mote = slot(goof, 2)?;
+            writeln!(
+                out,
+                "{}: bail {} after event {}",
+                name,
+                noun_to_text(mote),
+                context.event_num
+            )?;
+            write_tang(&mut out, slot(goof, 3)?)
+        }
+    }
+}
+
+/// The NockStack size for the commands which load a pier without a king, from `ARES_LOOM` in the
+/// environment, which is read as the serf's loom argument
+fn command_stack_size() -> io::Result<usize> {
+    stack_size(std::env::var("ARES_LOOM").ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether [conform] takes two pleas to agree
+    fn pleas_agree(stack: &mut NockStack, a: Noun, b: Noun) -> bool {
+        let mut a = plea_digest(stack, a).unwrap().unwrap();
+        let mut b = plea_digest(stack, b).unwrap().unwrap();
+        unsafe { unifying_equality(stack, &mut a, &mut b) }
+    }
+
+    #[test]
+    fn plea_digests() {
+        let mut stack = NockStack::new(8 << 10 << 10, 0);
+        let stack = &mut stack;
+
+        // Debug output is skipped
+        let slog = T(stack, &[D(tas!(b"slog")), D(0), D(tas!(b"hi"))]);
+        assert!(plea_digest(stack, slog).unwrap().is_none());
+
+        // Protocol versions may differ, but not the event number or mug
+        let ripe = |stack: &mut NockStack, hon, mug| {
+            let version = T(stack, &[D(1), D(hon), D(4)]);
+            T(stack, &[D(tas!(b"ripe")), version, D(2), D(mug)])
+        };
+        let ours = ripe(stack, 139, 0x57d6b64f);
+        let vere = ripe(stack, 138, 0x57d6b64f);
+        assert!(pleas_agree(stack, ours, vere));
+        let other = ripe(stack, 139, 0x57d6b64e);
+        assert!(!pleas_agree(stack, ours, other));
+
+        // Stack traces may differ, but not the motes
+        let tang = T(stack, &[D(tas!(b"leaf")), D(0)]);
+        let goof = T(stack, &[D(tas!(b"exit")), tang, D(0)]);
+        let ours = T(stack, &[D(tas!(b"work")), D(tas!(b"bail")), goof, D(0)]);
+        let goof = T(stack, &[D(tas!(b"exit")), D(0)]);
+        let vere = T(stack, &[D(tas!(b"work")), D(tas!(b"bail")), goof, D(0)]);
+        assert!(pleas_agree(stack, ours, vere));
+        let goof = T(stack, &[D(tas!(b"meme")), D(0)]);
+        let other = T(stack, &[D(tas!(b"work")), D(tas!(b"bail")), goof, D(0)]);
+        assert!(!pleas_agree(stack, ours, other));
+
+        // Effects are compared by mug
+        let fec = T(stack, &[D(1), D(2), D(0)]);
+        let ours = T(
+            stack,
+            &[D(tas!(b"work")), D(tas!(b"done")), D(3), D(4), fec],
+        );
+        let fec = T(stack, &[D(1), D(3), D(0)]);
+        let other = T(
+            stack,
+            &[D(tas!(b"work")), D(tas!(b"done")), D(3), D(4), fec],
+        );
+        assert!(!pleas_agree(stack, ours, other));
+    }
+}
@@ -0,0 +1,547 @@
+/** Debugger: stop the interpreter at breakpoints, and look around
+ *
+ * Breakpoints are set on calls into cold-registered cores, by cold path written outermost first
+ * like `/k/139/one/two/turn`, or on `%spot` hints, by source file and line like
+ * `/sys/vane/ames/hoon:123`. Calls are found as they are pushed onto the trace stack, so a
+ * debugged interpreter traces calls, and runs compiled arms as raw Nock.
+ *
+ * Once stopped, the debugger speaks a line protocol, on stderr and the terminal or on a Unix
+ * socket. It announces where it stopped with a `break <where>` line, then answers commands, each
+ * answer ending with an `ok` or `error: <message>` line:
+ *
+ * - `subject [axis]`: the subject, or a slot of it
+ * - `formula`: the formula about to be run
+ * - `mean`: the stack trace, rendered as it would be on a crash
+ * - `trace`: the cold paths of the calls the interpreter is in, innermost first
+ * - `break <breakpoint>`, `delete <breakpoint>`, `breaks`: manage breakpoints
+ * - `step`: go on to the next call or `%spot`, breakpoint or not
+ * - `continue`: go on to the next breakpoint
+ *
+ * The debugger goes on by itself once the other end hangs up.
+ */
+use crate::cli::write_tang;
+use crate::interpreter::Context;
+use crate::jets::nock::util::mook;
+use crate::mug::met3_usize;
+use crate::noun::{Atom, Cell, Noun, Slots, D};
+use crate::trace::TraceStack;
+use assert_no_alloc::permit_alloc;
+use std::fmt::{self, Write as _};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+
+crate::gdb!();
+
+/// How much of a noun is printed
+const RENDER_LIMIT: usize = 4096;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// A call into the core at a cold path, like `/k/139/one/two/turn`
+    Path(String),
+    /// A `%spot` hint in a source file whose range takes in a line
+    Spot(String, u64),
+}
+
+impl Breakpoint {
+    /// Parse `<file>:<line>` as a `%spot` breakpoint, and anything else as a cold path
+    pub fn parse(spec: &str) -> Self {
+        match spec.rsplit_once(':') {
+            Some((file, line)) if line.parse::<u64>().is_ok() => {
+                Breakpoint::Spot(file.to_string(), line.parse().unwrap())
+            }
+            _ => Breakpoint::Path(spec.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Path(path) => write!(f, "{}", path),
+            Breakpoint::Spot(file, line) => write!(f, "{}:{}", file, line),
+        }
+    }
+}
+
+pub struct Debugger {
+    pub breakpoints: Vec<Breakpoint>,
+    /// Whether to stop at the next call or `%spot`, breakpoint or not
+    pub stepping: bool,
+    /// The socket to listen on for the other end, rather than the terminal
+    socket: Option<PathBuf>,
+    /// The other end, once connected
+    io: Option<(Box<dyn BufRead>, Box<dyn Write>)>,
+}
+
+impl Debugger {
+    /// A debugger which talks on the socket at `socket`, or else on stderr and the terminal
+    pub fn new(breakpoints: Vec<Breakpoint>, socket: Option<PathBuf>) -> Self {
+        Debugger {
+            breakpoints,
+            stepping: false,
+            socket,
+            io: None,
+        }
+    }
+
+    /// A debugger which talks over `input` and `output`
+    pub fn with_io(
+        breakpoints: Vec<Breakpoint>,
+        input: Box<dyn BufRead>,
+        output: Box<dyn Write>,
+    ) -> Self {
+        Debugger {
+            breakpoints,
+            stepping: false,
+            socket: None,
+            io: Some((input, output)),
+        }
+    }
+
+    /// Read a comma-separated list of breakpoints from `ARES_DEBUG`, if it is set, and the socket
+    /// to talk on from `ARES_DEBUG_SOCKET`
+    pub fn from_env() -> Option<Self> {
+        let spec = std::env::var("ARES_DEBUG").ok()?;
+        let breakpoints = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|spec| !spec.is_empty())
+            .map(Breakpoint::parse)
+            .collect();
+        let socket = std::env::var_os("ARES_DEBUG_SOCKET").map(PathBuf::from);
+        Some(Debugger::new(breakpoints, socket))
+    }
+
+    fn connect(&mut self) -> io::Result<()> {
+        if self.io.is_some() {
+            return Ok(());
+        }
+        self.io = Some(match &self.socket {
+            Some(path) => {
+                let _ = std::fs::remove_file(path);
+                let listener = UnixListener::bind(path)?;
+                eprintln!("\rdebugger: waiting on {}", path.display());
+                let (stream, _) = listener.accept()?;
+                (
+                    Box::new(BufReader::new(stream.try_clone()?)),
+                    Box::new(stream),
+                )
+            }
+            // Stdin may be the king's
+            None => (
+                Box::new(BufReader::new(File::open("/dev/tty")?)),
+                Box::new(io::stderr()),
+            ),
+        });
+        Ok(())
+    }
+}
+
+/// Stop at a call into the core at cold path `path`, if there is a breakpoint on it or the
+/// debugger is stepping
+pub fn call(context: &mut Context, subject: Noun, formula: Noun, path: Noun) {
+    let Some(debugger) = context.debugger.as_ref() else {
+        return;
+    };
+    let stop = debugger.stepping
+        || debugger
+            .breakpoints
+            .iter()
+            .any(|breakpoint| matches!(breakpoint, Breakpoint::Path(_)));
+    if !stop {
+        return;
+    }
+    permit_alloc(|| {
+        let name = cold_path(path);
+        let debugger = context.debugger.as_ref().unwrap();
+        let hit = debugger
+            .breakpoints
+            .iter()
+            .any(|breakpoint| match breakpoint {
+                Breakpoint::Path(at) => *at == name,
+                Breakpoint::Spot(_, _) => false,
+            });
+        if debugger.stepping || hit {
+            stop_at(context, subject, formula, &name);
+        }
+    })
+}
+
+/// Stop at a `%spot` hint with clue `spot`, if there is a breakpoint in its range or the debugger
+/// is stepping
+pub fn spot(context: &mut Context, subject: Noun, formula: Noun, spot: Noun) {
+    if context.debugger.is_none() {
+        return;
+    }
+    permit_alloc(|| {
+        // [p=path q=pint], where a pint is [p=[p=line q=col] q=[p=line q=col]]
+        let Some((file, from, to)) = spot_parts(spot) else {
+            return;
+        };
+        let debugger = context.debugger.as_ref().unwrap();
+        let hit = debugger
+            .breakpoints
+            .iter()
+            .any(|breakpoint| match breakpoint {
+                Breakpoint::Spot(at, line) => *at == file && from.0 <= *line && *line <= to.0,
+                Breakpoint::Path(_) => false,
+            });
+        if debugger.stepping || hit {
+            let name = format!("{}:{}.{}-{}.{}", file, from.0, from.1, to.0, to.1);
+            stop_at(context, subject, formula, &name);
+        }
+    })
+}
+
+/// Answer commands at a stop. Runs with allocation permitted.
+fn stop_at(context: &mut Context, subject: Noun, formula: Noun, name: &str) {
+    // Out of the context while stopped, so Nock run to render the stack trace can't stop
+    let mut debugger = context.debugger.take().unwrap();
+    if let Err(e) = session(context, &mut debugger, subject, formula, name) {
+        eprintln!("\rdebugger: {}", e);
+        debugger.io = None;
+    }
+    context.debugger = Some(debugger);
+}
+
+fn session(
+    context: &mut Context,
+    debugger: &mut Debugger,
+    subject: Noun,
+    formula: Noun,
+    name: &str,
+) -> io::Result<()> {
+    debugger.connect()?;
+    let (mut input, mut output) = debugger.io.take().unwrap();
+    writeln!(output, "break {}", name)?;
+    output.flush()?;
+    if commands(context, debugger, &mut input, &mut output, subject, formula)? {
+        debugger.io = Some((input, output));
+    } else {
+        // Hung up
+        debugger.stepping = false;
+    }
+    Ok(())
+}
+
+/// Answer commands until told to go on, or until the other end hangs up, which is `false`
+fn commands(
+    context: &mut Context,
+    debugger: &mut Debugger,
+    input: &mut Box<dyn BufRead>,
+    output: &mut Box<dyn Write>,
+    subject: Noun,
+    formula: Noun,
+) -> io::Result<bool> {
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(false);
+        }
+        let (command, arg) = match line.trim().split_once(' ') {
+            Some((command, arg)) => (command, arg.trim()),
+            None => (line.trim(), ""),
+        };
+        let reply: Result<(), String> = match command {
+            "" => continue,
+            "s" | "step" => {
+                debugger.stepping = true;
+                answer(output, Ok(()))?;
+                return Ok(true);
+            }
+            "c" | "continue" => {
+                debugger.stepping = false;
+                answer(output, Ok(()))?;
+                return Ok(true);
+            }
+            "subject" => {
+                let axis = if arg.is_empty() { Ok(1) } else { arg.parse() };
+                match axis {
+                    Ok(axis) if axis > 0 => match subject.slot(axis) {
+                        Ok(noun) => writeln!(output, "{}", render(noun)).map_err(|e| e.to_string()),
+                        Err(_) => Err(format!("no axis {} in the subject", axis)),
+                    },
+                    _ => Err(format!("bad axis {}", arg)),
+                }
+            }
+            "formula" => writeln!(output, "{}", render(formula)).map_err(|e| e.to_string()),
+            "mean" => {
+                let mean = context.full_mean();
+                let tone = Cell::new(&mut context.stack, D(2), mean);
+                match mook(context, tone, false) {
+                    Ok(toon) => write_tang(output, toon.tail()).map_err(|e| e.to_string()),
+                    Err(e) => Err(format!("could not render the stack trace: {:?}", e)),
+                }
+            }
+            "trace" => {
+                let mut trace_stack =
+                    unsafe { *(context.stack.local_noun_pointer(1) as *const *const TraceStack) };
+                let mut paths = Vec::new();
+                while !trace_stack.is_null() {
+                    unsafe {
+                        paths.push(cold_path((*trace_stack).path));
+                        trace_stack = (*trace_stack).next;
+                    }
+                }
+                paths
+                    .into_iter()
+                    .try_for_each(|path| writeln!(output, "{}", path))
+                    .map_err(|e| e.to_string())
+            }
+            "break" if !arg.is_empty() => {
+                let breakpoint = Breakpoint::parse(arg);
+                if !debugger.breakpoints.contains(&breakpoint) {
+                    debugger.breakpoints.push(breakpoint);
+                }
+                Ok(())
+            }
+            "delete" if !arg.is_empty() => {
+                let breakpoint = Breakpoint::parse(arg);
+                let before = debugger.breakpoints.len();
+                debugger.breakpoints.retain(|b| *b != breakpoint);
+                if debugger.breakpoints.len() < before {
+                    Ok(())
+                } else {
+                    Err(format!("no breakpoint {}", breakpoint))
+                }
+            }
+            "breaks" => debugger
+                .breakpoints
+                .iter()
+                .try_for_each(|breakpoint| writeln!(output, "{}", breakpoint))
+                .map_err(|e| e.to_string()),
+            "help" => writeln!(
+                output,
+                "subject [axis], formula, mean, trace, break <breakpoint>, delete <breakpoint>, \
+                 breaks, step, continue"
+            )
+            .map_err(|e| e.to_string()),
+            _ => Err(format!("bad command {}", line.trim())),
+        };
+        answer(output, reply)?;
+    }
+}
+
+fn answer(output: &mut dyn Write, reply: Result<(), String>) -> io::Result<()> {
+    match reply {
+        Ok(()) => writeln!(output, "ok")?,
+        Err(e) => writeln!(output, "error: {}", e)?,
+    }
+    output.flush()
+}
+
+/// A cold path written outermost first, as breakpoints are: `[%turn %two %one [%k 139] ~]` is
+/// `/k/139/one/two/turn`
+pub fn cold_path(path: Noun) -> String {
+    let mut components = Vec::new();
+    let mut cursor = path;
+    while let Ok(cell) = cursor.as_cell() {
+        match cell.head().as_cell() {
+            Ok(pair) => components.push(format!("{}/{}", text(pair.head()), text(pair.tail()))),
+            Err(_) => components.push(text(cell.head())),
+        }
+        cursor = cell.tail();
+    }
+    components.iter().rev().map(|c| format!("/{}", c)).collect()
+}
+
+/// A line and column in a source file
+type Point = (u64, u64);
+
+/// The source file and range of a `%spot` clue
+fn spot_parts(spot: Noun) -> Option<(String, Point, Point)> {
+    let spot = spot.as_cell().ok()?;
+    let mut file = String::new();
+    let mut cursor = spot.head();
+    while let Ok(cell) = cursor.as_cell() {
+        file.push('/');
+        file.push_str(&text(cell.head()));
+        cursor = cell.tail();
+    }
+    let pint = spot.tail().as_cell().ok()?;
+    let point = |noun: Noun| -> Option<Point> {
+        let cell = noun.as_cell().ok()?;
+        Some((
+            cell.head().as_direct().ok()?.data(),
+            cell.tail().as_direct().ok()?.data(),
+        ))
+    };
+    Some((file, point(pint.head())?, point(pint.tail())?))
+}
+
+/// An atom as text, or as a number if it is not text
+fn text(noun: Noun) -> String {
+    match noun.as_atom() {
+        Ok(atom) if is_text(atom) => {
+            String::from_utf8_lossy(&atom.as_bytes()[..met3_usize(atom)]).into_owned()
+        }
+        Ok(atom) => match atom.as_direct() {
+            Ok(direct) => direct.data().to_string(),
+            Err(_) => render(noun),
+        },
+        Err(_) => render(noun),
+    }
+}
+
+fn is_text(atom: Atom) -> bool {
+    let bytes = &atom.as_bytes()[..met3_usize(atom)];
+    !bytes.is_empty() && bytes.iter().all(|b| b.is_ascii_graphic())
+}
+
+/// A noun as text, cut off at [RENDER_LIMIT] characters
+fn render(noun: Noun) -> String {
+    struct Limited(String);
+
+    impl fmt::Write for Limited {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            if self.0.len() + s.len() > RENDER_LIMIT {
+                return Err(fmt::Error);
+            }
+            self.0.push_str(s);
+            Ok(())
+        }
+    }
+
+    let mut out = Limited(String::new());
+    match write!(out, "{}", noun) {
+        Ok(()) => out.0,
+        Err(_) => out.0 + "...",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::interpret;
+    use crate::jets::util::test::{assert_noun_eq, init_context};
+    use crate::noun::T;
+    use ares_macros::tas;
+    use std::io::Cursor;
+
+    /// Debug `c` with `commands` as the input, to be read back with [read_back]
+    fn debug(c: &mut Context, name: &str, breakpoints: &[&str], commands: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ares-{}-{}", name, std::process::id()));
+        let breakpoints = breakpoints.iter().map(|b| Breakpoint::parse(b)).collect();
+        c.debugger = Some(Debugger::with_io(
+            breakpoints,
+            Box::new(Cursor::new(commands.as_bytes().to_vec())),
+            Box::new(File::create(&path).unwrap()),
+        ));
+        path
+    }
+
+    fn read_back(path: PathBuf) -> String {
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        output
+    }
+
+    #[test]
+    fn parse_breakpoints() {
+        assert_eq!(
+            Breakpoint::parse("/k/139/one/two/turn"),
+            Breakpoint::Path("/k/139/one/two/turn".to_string())
+        );
+        assert_eq!(
+            Breakpoint::parse("/sys/vane/ames/hoon:123"),
+            Breakpoint::Spot("/sys/vane/ames/hoon".to_string(), 123)
+        );
+        assert_eq!(
+            Breakpoint::parse("/weird:path"),
+            Breakpoint::Path("/weird:path".to_string())
+        );
+    }
+
+    #[test]
+    fn write_cold_path() {
+        let c = &mut init_context();
+        let k = T(&mut c.stack, &[D(tas!(b"k")), D(139)]);
+        let path = T(
+            &mut c.stack,
+            &[D(tas!(b"turn")), D(tas!(b"two")), D(tas!(b"one")), k, D(0)],
+        );
+        assert_eq!(cold_path(path), "/k/139/one/two/turn");
+    }
+
+    #[test]
+    fn break_on_call() {
+        let c = &mut init_context();
+        // A core with battery [0 6] and sample 0, whose parent is the root [1 2]
+        let root = T(&mut c.stack, &[D(1), D(2)]);
+        let battery = T(&mut c.stack, &[D(0), D(6)]);
+        let core = T(&mut c.stack, &[battery, D(0), root]);
+        let root_axis = D(0).as_atom().unwrap();
+        let parent_axis = D(7).as_atom().unwrap();
+        assert!(matches!(
+            c.cold
+                .register(&mut c.stack, root, root_axis, D(tas!(b"root"))),
+            Ok(true)
+        ));
+        assert!(matches!(
+            c.cold
+                .register(&mut c.stack, core, parent_axis, D(tas!(b"core"))),
+            Ok(true)
+        ));
+
+        let output = debug(
+            c,
+            "break-on-call",
+            &["/root/core"],
+            "subject 6\nformula\ntrace\nsubject 99\nbreaks\nbogus\ncontinue\n",
+        );
+        //  [9 2 0 1]
+        let f = T(&mut c.stack, &[D(9), D(2), D(0), D(1)]);
+        let r = interpret(c, core, f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(0));
+        c.debugger = None;
+
+        assert_eq!(
+            read_back(output),
+            "break /root/core\n\
+             0\nok\n\
+             [0 6]\nok\n\
+             /root/core\nok\n\
+             error: no axis 99 in the subject\n\
+             /root/core\nok\n\
+             error: bad command bogus\n\
+             ok\n"
+        );
+    }
+
+    #[test]
+    fn break_on_spot() {
+        let c = &mut init_context();
+        // [11 [%spot 1 [/sys/hoon [[10 1] [12 5]]]] 0 1]
+        let file = T(&mut c.stack, &[D(tas!(b"sys")), D(tas!(b"hoon")), D(0)]);
+        let from = T(&mut c.stack, &[D(10), D(1)]);
+        let to = T(&mut c.stack, &[D(12), D(5)]);
+        let spot = T(&mut c.stack, &[file, from, to]);
+        let clue = T(&mut c.stack, &[D(1), spot]);
+        let hint = T(&mut c.stack, &[D(tas!(b"spot")), clue]);
+        let f = T(&mut c.stack, &[D(11), hint, D(0), D(1)]);
+
+        // Not in the range
+        let output = debug(c, "break-off-spot", &["/sys/hoon:9"], "");
+        let r = interpret(c, D(42), f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+        assert_eq!(read_back(output), "");
+
+        let output = debug(
+            c,
+            "break-on-spot",
+            &["/sys/hoon:11"],
+            "subject\nmean\nstep\n",
+        );
+        let r = interpret(c, D(42), f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+        assert!(c.debugger.as_ref().unwrap().stepping);
+        c.debugger = None;
+        assert_eq!(
+            read_back(output),
+            "break /sys/hoon:10.1-12.5\n42\nok\n/sys/hoon:<[10 1].[12 5]>\nok\nok\n"
+        );
+    }
+}
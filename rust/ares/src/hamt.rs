@@ -1,7 +1,7 @@
 use crate::mem::{NockStack, Preserve};
 use crate::mug::mug_u32;
 use crate::noun::Noun;
-use crate::persist::{pma_contains, Persist};
+use crate::persist::{Keep, Persist};
 use crate::unifying_equality::unifying_equality;
 use either::Either::{self, *};
 use std::mem::size_of;
@@ -23,7 +23,7 @@ fn chunk_to_mask(chunk: u32) -> u32 {
     chunk_to_bit(chunk) - 1
 }
 
-#[repr(packed)]
+#[repr(C, packed)]
 struct MutStem<T: Copy> {
     bitmap: u32,
     typemap: u32,
@@ -161,6 +161,58 @@ impl<T: Copy> MutHamt<T> {
             }
         }
     }
+
+    /// Make an immutable HAMT of the same keys, with each value mapped through `f`
+    pub fn freeze<U: Copy, F: Fn(T) -> U>(self, stack: &mut NockStack, f: &F) -> Hamt<U> {
+        unsafe {
+            let stem_ptr = stack.struct_alloc::<Stem<U>>(1);
+            *stem_ptr = freeze_stem(stack, self.0, f);
+            Hamt(stem_ptr)
+        }
+    }
+}
+
+/// Copy a mutable stem, and the stems and leaves below it, into the layout of an immutable one
+unsafe fn freeze_stem<T: Copy, U: Copy, F: Fn(T) -> U>(
+    stack: &mut NockStack,
+    mut_stem: *mut MutStem<T>,
+    f: &F,
+) -> Stem<U> {
+    let bitmap = (*mut_stem).bitmap;
+    let buffer = if bitmap == 0 {
+        null_mut()
+    } else {
+        stack.struct_alloc::<Entry<U>>(bitmap.count_ones() as usize)
+    };
+    let mut idx = 0;
+    for chunk in 0..32 {
+        match (*mut_stem).entry(chunk) {
+            None => continue,
+            Some(Left(next_stem)) => {
+                *buffer.add(idx) = Entry {
+                    stem: freeze_stem(stack, next_stem, f),
+                }
+            }
+            Some(Right(leaf)) => {
+                let leaf_buffer = stack.struct_alloc::<(Noun, U)>(leaf.len);
+                for (pair_idx, pair) in leaf.to_mut_slice().iter().enumerate() {
+                    *leaf_buffer.add(pair_idx) = (pair.0, f(pair.1));
+                }
+                *buffer.add(idx) = Entry {
+                    leaf: Leaf {
+                        len: leaf.len,
+                        buffer: leaf_buffer,
+                    },
+                }
+            }
+        }
+        idx += 1;
+    }
+    Stem {
+        bitmap,
+        typemap: (*mut_stem).typemap,
+        buffer,
+    }
 }
 
 /**
@@ -280,6 +332,11 @@ impl<T: Copy + Preserve> Hamt<T> {
     pub fn is_null(&self) -> bool {
         unsafe { (*self.0).bitmap == 0 }
     }
+
+    /// Whether both handles are to the same HAMT in memory
+    pub fn ptr_eq(&self, other: &Hamt<T>) -> bool {
+        self.0 == other.0
+    }
     // Make a new, empty HAMT
     pub fn new(stack: &mut NockStack) -> Self {
         unsafe {
@@ -459,6 +516,126 @@ impl<T: Copy + Preserve> Hamt<T> {
             }
         }
     }
+
+    /**
+     * Visit every key-value pair in the HAMT in place
+     *
+     * # Safety
+     *
+     * The visitor may only replace a key or value with an equal one, as when unifying: the HAMT
+     * is not rebalanced afterwards.
+     */
+    pub unsafe fn for_each_pair<F: FnMut(*mut (Noun, T))>(&self, mut f: F) {
+        let mut depth: usize = 0;
+        let mut traversal = [Stem {
+            bitmap: 0,
+            typemap: 0,
+            buffer: null_mut(),
+        }; 6];
+        traversal[0] = *self.0;
+
+        loop {
+            assert!(depth < 6);
+            if traversal[depth].bitmap == 0 {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+                continue;
+            }
+
+            let next_chunk = traversal[depth].bitmap.trailing_zeros();
+            let next_type = traversal[depth].typemap & (1 << next_chunk) != 0;
+            let next_entry = *traversal[depth].buffer;
+            traversal[depth].bitmap >>= next_chunk + 1;
+            traversal[depth].typemap >>= next_chunk + 1;
+            traversal[depth].buffer = traversal[depth].buffer.add(1);
+
+            if next_type {
+                traversal[depth + 1] = next_entry.stem;
+                depth += 1;
+            } else {
+                let leaf = next_entry.leaf;
+                let mut idx = 0;
+                while idx < leaf.len {
+                    f(leaf.buffer.add(idx));
+                    idx += 1;
+                }
+            }
+        }
+    }
+
+    /**
+     * Visit every key-value pair in the HAMT, as [Hamt::for_each_pair] does, but first pass each
+     * block of HAMT memory to `valid` as a pointer and a length in bytes. Blocks it rejects are
+     * not read.
+     *
+     * # Safety
+     *
+     * `valid` must only accept readable memory. The visitor has the same restrictions as for
+     * [Hamt::for_each_pair].
+     */
+    pub unsafe fn for_each_pair_checked<V, F>(&self, valid: &V, mut f: F)
+    where
+        V: Fn(*const u8, usize) -> bool,
+        F: FnMut(*mut (Noun, T)),
+    {
+        if !valid(self.0 as *const u8, size_of::<Stem<T>>()) {
+            return;
+        }
+        let mut depth: usize = 0;
+        let mut traversal = [Stem {
+            bitmap: 0,
+            typemap: 0,
+            buffer: null_mut(),
+        }; 6];
+        traversal[0] = *self.0;
+        if !valid(
+            traversal[0].buffer as *const u8,
+            traversal[0].size() * size_of::<Entry<T>>(),
+        ) {
+            return;
+        }
+
+        loop {
+            if traversal[depth].bitmap == 0 {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+                continue;
+            }
+
+            let next_chunk = traversal[depth].bitmap.trailing_zeros();
+            let next_type = traversal[depth].typemap & (1 << next_chunk) != 0;
+            let next_entry = *traversal[depth].buffer;
+            traversal[depth].bitmap >>= next_chunk + 1;
+            traversal[depth].typemap >>= next_chunk + 1;
+            traversal[depth].buffer = traversal[depth].buffer.add(1);
+
+            if next_type {
+                let stem = next_entry.stem;
+                if depth + 1 < traversal.len()
+                    && valid(
+                        stem.buffer as *const u8,
+                        stem.size() * size_of::<Entry<T>>(),
+                    )
+                {
+                    traversal[depth + 1] = stem;
+                    depth += 1;
+                }
+            } else {
+                let leaf = next_entry.leaf;
+                if valid(leaf.buffer as *const u8, leaf.len * size_of::<(Noun, T)>()) {
+                    let mut idx = 0;
+                    while idx < leaf.len {
+                        f(leaf.buffer.add(idx));
+                        idx += 1;
+                    }
+                }
+            }
+        }
+    }
 }
 
 impl<T: Copy + Preserve> Preserve for Hamt<T> {
@@ -539,36 +716,30 @@ impl<T: Copy + Preserve> Preserve for Hamt<T> {
                             continue 'preserve;
                         }
                         match stem.entry(position) {
-                            None => {
+                            Some((Left(next_stem), idx)) if stack.is_in_frame(next_stem.buffer) => {
+                                let dest_buffer =
+                                    stack.struct_alloc_in_previous_frame(next_stem.size());
+                                copy_nonoverlapping(
+                                    next_stem.buffer,
+                                    dest_buffer,
+                                    next_stem.size(),
+                                );
+                                let new_stem = Stem {
+                                    bitmap: next_stem.bitmap,
+                                    typemap: next_stem.typemap,
+                                    buffer: dest_buffer,
+                                };
+                                *stem.buffer.add(idx) = Entry { stem: new_stem };
+                                assert!(traversal_depth <= 5); // will increment
+                                traversal_stack[traversal_depth - 1] = Some((stem, position + 1));
+                                traversal_stack[traversal_depth] = Some((new_stem, 0));
+                                traversal_depth += 1;
+                                continue 'preserve;
+                            }
+                            None | Some((Left(_), _)) => {
                                 position += 1;
                                 continue 'preserve_stem;
                             }
-                            Some((Left(next_stem), idx)) => {
-                                if stack.is_in_frame(next_stem.buffer) {
-                                    let dest_buffer =
-                                        stack.struct_alloc_in_previous_frame(next_stem.size());
-                                    copy_nonoverlapping(
-                                        next_stem.buffer,
-                                        dest_buffer,
-                                        next_stem.size(),
-                                    );
-                                    let new_stem = Stem {
-                                        bitmap: next_stem.bitmap,
-                                        typemap: next_stem.typemap,
-                                        buffer: dest_buffer,
-                                    };
-                                    *stem.buffer.add(idx) = Entry { stem: new_stem };
-                                    assert!(traversal_depth <= 5); // will increment
-                                    traversal_stack[traversal_depth - 1] =
-                                        Some((stem, position + 1));
-                                    traversal_stack[traversal_depth] = Some((new_stem, 0));
-                                    traversal_depth += 1;
-                                    continue 'preserve;
-                                } else {
-                                    position += 1;
-                                    continue 'preserve_stem;
-                                }
-                            }
                             Some((Right(leaf), idx)) => {
                                 if stack.is_in_frame(leaf.buffer) {
                                     let dest_buffer =
@@ -596,12 +767,12 @@ impl<T: Copy + Preserve> Preserve for Hamt<T> {
 }
 
 impl<T: Copy + Persist> Persist for Hamt<T> {
-    unsafe fn space_needed(&mut self, stack: &mut NockStack) -> usize {
-        if pma_contains(self.0, 1) {
+    unsafe fn space_needed(&mut self, stack: &mut NockStack, keep: Keep) -> usize {
+        if keep.keeps(self.0, 1) {
             return 0;
         }
         let mut bytes: usize = size_of::<Stem<T>>();
-        if pma_contains((*self.0).buffer, (*self.0).size()) {
+        if keep.keeps((*self.0).buffer, (*self.0).size()) {
             return bytes;
         };
 
@@ -637,7 +808,7 @@ impl<T: Copy + Persist> Persist for Hamt<T> {
                 // found another stem
                 traversal[depth + 1] = next_entry.stem;
 
-                if pma_contains(traversal[depth + 1].buffer, traversal[depth + 1].size()) {
+                if keep.keeps(traversal[depth + 1].buffer, traversal[depth + 1].size()) {
                     continue;
                 }
 
@@ -651,15 +822,15 @@ impl<T: Copy + Persist> Persist for Hamt<T> {
                     continue;
                 }
 
-                if pma_contains(leaf.buffer, leaf.len) {
+                if keep.keeps(leaf.buffer, leaf.len) {
                     continue;
                 }
 
                 bytes += size_of::<(Noun, T)>() * leaf.len;
 
                 while leaf.len > 0 {
-                    bytes += (*leaf.buffer).0.space_needed(stack);
-                    bytes += (*leaf.buffer).1.space_needed(stack);
+                    bytes += (*leaf.buffer).0.space_needed(stack, keep);
+                    bytes += (*leaf.buffer).1.space_needed(stack, keep);
                     leaf.buffer = leaf.buffer.add(1);
                     leaf.len -= 1;
                 }
@@ -667,8 +838,8 @@ impl<T: Copy + Persist> Persist for Hamt<T> {
         }
     }
 
-    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, buffer: &mut *mut u8) {
-        if pma_contains(self.0, 1) {
+    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, keep: Keep, buffer: &mut *mut u8) {
+        if keep.keeps(self.0, 1) {
             return;
         }
         let stem_ptr = *buffer as *mut Stem<T>;
@@ -677,7 +848,7 @@ impl<T: Copy + Persist> Persist for Hamt<T> {
         self.0 = stem_ptr;
 
         let stem_buffer_size = (*stem_ptr).size();
-        if pma_contains((*stem_ptr).buffer, stem_buffer_size) {
+        if keep.keeps((*stem_ptr).buffer, stem_buffer_size) {
             return;
         }
         let stem_buffer_ptr = *buffer as *mut Entry<T>;
@@ -718,7 +889,7 @@ impl<T: Copy + Persist> Persist for Hamt<T> {
                 let stem_ptr: *mut Stem<T> = &mut (*next_entry_ptr).stem;
                 let stem_size = (*stem_ptr).size();
 
-                if pma_contains((*stem_ptr).buffer, stem_size) {
+                if keep.keeps((*stem_ptr).buffer, stem_size) {
                     continue;
                 }
 
@@ -738,7 +909,7 @@ impl<T: Copy + Persist> Persist for Hamt<T> {
                     continue;
                 }
 
-                if pma_contains((*leaf_ptr).buffer, (*leaf_ptr).len) {
+                if keep.keeps((*leaf_ptr).buffer, (*leaf_ptr).len) {
                     continue;
                 }
 
@@ -754,10 +925,10 @@ impl<T: Copy + Persist> Persist for Hamt<T> {
                 while leaf_idx < (*leaf_ptr).len {
                     (*(*leaf_ptr).buffer.add(leaf_idx))
                         .0
-                        .copy_to_buffer(stack, buffer);
+                        .copy_to_buffer(stack, keep, buffer);
                     (*(*leaf_ptr).buffer.add(leaf_idx))
                         .1
-                        .copy_to_buffer(stack, buffer);
+                        .copy_to_buffer(stack, keep, buffer);
 
                     leaf_idx += 1;
                 }
@@ -1,28 +1,34 @@
 use crate::assert_acyclic;
 use crate::assert_no_forwarding_pointers;
 use crate::assert_no_junior_pointers;
+use crate::bytecode::{self, Code};
+use crate::debugger::{self, Debugger};
 use crate::flog;
 use crate::guard::call_with_guard;
 use crate::hamt::Hamt;
 use crate::jets::cold;
 use crate::jets::cold::Cold;
 use crate::jets::hot::Hot;
-use crate::jets::warm::Warm;
-use crate::jets::JetErr;
+use crate::jets::list::util::zing;
+use crate::jets::warm::{Jets, Warm};
+use crate::jets::{self, Jet, JetErr, JetTest};
 use crate::mem::NockStack;
 use crate::mem::Preserve;
 use crate::newt::Newt;
 use crate::noun;
 use crate::noun::{Atom, Cell, IndirectAtom, Noun, Slots, D, T};
-use crate::serf::TERMINATOR;
-use crate::trace::{write_nock_trace, TraceInfo, TraceStack};
+use crate::persist::pma_contains;
+use crate::serf::{interrupted, DEADLINE_PASSED, TERMINATOR};
+use crate::trace::{
+    self, write_jet_trace, write_nock_trace, JetStats, LiveCounters, Profile, TraceInfo, TraceStack,
+};
 use crate::unifying_equality::unifying_equality;
 use ares_macros::tas;
-use assert_no_alloc::{assert_no_alloc, ensure_alloc_counters};
+use assert_no_alloc::{assert_no_alloc, ensure_alloc_counters, permit_alloc};
 use bitvec::prelude::{BitSlice, Lsb0};
 use either::*;
+use intmap::IntMap;
 use std::result;
-use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::time::Instant;
 
@@ -257,9 +263,107 @@ enum NockWork {
     Work12(Nock12),
 }
 
+/// A formula decoded once and kept by [Formulas]
+struct Formula {
+    /// The work which computes the formula, with `tail` set wherever a tail call is allowed
+    work: NockWork,
+    /// The jets registered for the formula as an arm, as found in the warm state of epoch `found`,
+    /// or, if there are none, of generation `found`
+    jets: Jets,
+    found: u64,
+}
+
+impl Formula {
+    /// Find the jet for the formula as an arm of the subject. The registered jets are only looked
+    /// up again if the warm state has changed since they were last found, or, if some were found,
+    /// has moved.
+    fn find_jet(
+        &mut self,
+        stack: &mut NockStack,
+        warm: &Warm,
+        formula: &mut Noun,
+        subject: Noun,
+    ) -> Option<(Jet, Noun)> {
+        let current = if self.jets.is_none() {
+            warm.generation()
+        } else {
+            warm.epoch()
+        };
+        if self.found != current {
+            self.jets = warm.find_jets(stack, formula);
+            self.found = if self.jets.is_none() {
+                warm.generation()
+            } else {
+                warm.epoch()
+            };
+        }
+        self.jets.find_jet(stack, subject)
+    }
+}
+
+/**
+ * Decoded formulas, keyed by formula identity.
+ *
+ * Only formulas in the PMA are kept: they neither move nor change, so their address identifies
+ * them, including across [NockStack::flip_top_frame]. Each is decoded once, into a box on the
+ * Rust heap which stays put until the cache is cleared, and which refers only to nouns in the
+ * PMA.
+ *
+ * The PMA allocations are freed when it is packed, and the cache must be cleared then.
+ */
+pub struct Formulas(IntMap<Box<Formula>>);
+
+impl Formulas {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Formulas(IntMap::new())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.len() == 0
+    }
+
+    /// Forget every decoded formula
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Find the decoded formula, decoding it on first use, if it is kept
+    fn get(&mut self, formula: Noun) -> Option<*mut Formula> {
+        let cell = formula.as_cell().ok()?;
+        unsafe {
+            if !pma_contains(cell.to_raw_pointer(), 1) {
+                return None;
+            }
+            let key = formula.as_raw();
+            if let Some(decoded) = self.0.get_mut(key) {
+                return Some(&mut **decoded);
+            }
+            let work = decode(formula)?;
+            permit_alloc(|| {
+                let mut decoded = Box::new(Formula {
+                    work,
+                    jets: Jets::NONE,
+                    found: 0,
+                });
+                let ptr: *mut Formula = &mut *decoded;
+                self.0.insert(key, decoded);
+                Some(ptr)
+            })
+        }
+    }
+}
+
 pub struct ContextSnapshot {
     cold: Cold,
     warm: Warm,
+    mean_slot: *const Noun,
+    outer_means: Noun,
+    outer_trace: *const TraceStack,
 }
 
 pub struct Context {
@@ -268,9 +372,32 @@ pub struct Context {
     pub cold: Cold,
     pub warm: Warm,
     pub hot: Hot,
+    pub code: Code,
+    pub formulas: Formulas,
     pub cache: Hamt<Noun>,
     pub scry_stack: Noun,
     pub trace_info: Option<TraceInfo>,
+    pub jet_test: JetTest,
+    /// Where the running interpret call keeps its mean stack, as of its last call out of the
+    /// interpreter, so that an interpret call nested in it can find its stack trace
+    pub mean_slot: *const Noun,
+    /// The mean stacks of the interpret calls which the running one is nested in, innermost first
+    pub outer_means: Noun,
+    /// The top of the trace stack of the interpret call which the running one is nested in, as of
+    /// its last call out of the interpreter
+    pub outer_trace: *const TraceStack,
+    /// Hit counters for the %live hint
+    pub live: LiveCounters,
+    /// Sampling profile of the computation, if it is being profiled
+    pub profile: Option<Profile>,
+    /// Statistics on calls into cold-registered cores, if they are being kept
+    pub jet_stats: Option<JetStats>,
+    /// The debugger, if breakpoints may be set
+    pub debugger: Option<Debugger>,
+    /// Steps the computation may still take, if it is bounded: one for each Nock formula
+    /// evaluated, and [jets::cost] for each jet call. A bounded computation is never run as
+    /// compiled code, so that what it costs does not depend on whether a town is loaded.
+    pub gas: Option<u64>,
 }
 
 impl Context {
@@ -278,12 +405,47 @@ impl Context {
         ContextSnapshot {
             cold: self.cold,
             warm: self.warm,
+            mean_slot: self.mean_slot,
+            outer_means: self.outer_means,
+            outer_trace: self.outer_trace,
         }
     }
 
     pub fn restore(&mut self, saved: &ContextSnapshot) {
         self.cold = saved.cold;
         self.warm = saved.warm;
+        self.restore_means(saved);
+    }
+
+    fn restore_means(&mut self, saved: &ContextSnapshot) {
+        self.mean_slot = saved.mean_slot;
+        self.outer_means = saved.outer_means;
+        self.outer_trace = saved.outer_trace;
+    }
+
+    /// Note where the running interpret call keeps its mean stack, and the top of its trace stack,
+    /// before calling out of the interpreter to anything which may interpret again
+    pub fn suspend_mean(&mut self) {
+        unsafe {
+            self.mean_slot = self.stack.local_noun_pointer(0);
+            self.outer_trace = *(self.stack.local_noun_pointer(1) as *const *const TraceStack);
+        }
+    }
+
+    /// Whether calls into cold-registered cores are pushed onto the trace stack, which they are
+    /// only for the trace file, the profiler, the jet statistics and the debugger
+    pub fn traces_calls(&self) -> bool {
+        self.trace_info.is_some()
+            || self.profile.is_some()
+            || self.jet_stats.is_some()
+            || self.debugger.is_some()
+    }
+
+    /// The full stack trace, through every interpret call which the running one is nested in
+    pub fn full_mean(&mut self) -> Noun {
+        let mean = unsafe { *(self.stack.local_noun_pointer(0)) };
+        let means = T(&mut self.stack, &[mean, self.outer_means]);
+        zing(&mut self.stack, means).expect("serf: malformed mean stacks")
     }
 
     /**
@@ -301,10 +463,10 @@ impl Context {
     {
         self.stack.frame_push(slots);
         let mut ret = f(self);
-        ret.preserve(&mut self.stack);
-        self.cache.preserve(&mut self.stack);
-        self.cold.preserve(&mut self.stack);
-        self.warm.preserve(&mut self.stack);
+        self.stack.preserve(&mut ret);
+        self.stack.preserve(&mut self.cache);
+        self.stack.preserve(&mut self.cold);
+        self.stack.preserve(&mut self.warm);
         self.stack.frame_pop();
         ret
     }
@@ -316,6 +478,8 @@ pub enum Mote {
     Fail = tas!(b"fail") as isize,
     Intr = tas!(b"intr") as isize,
     Meme = tas!(b"meme") as isize,
+    /// The computation ran out of its step budget
+    Gas = tas!(b"gas") as isize,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -364,6 +528,19 @@ const BAIL_EXIT: Result = Err(Error::Deterministic(Mote::Exit, D(0)));
 const BAIL_FAIL: Result = Err(Error::NonDeterministic(Mote::Fail, D(0)));
 const BAIL_INTR: Result = Err(Error::NonDeterministic(Mote::Intr, D(0)));
 
+/// Spend `cost` steps of a budget, if there is one, failing with [Mote::Gas] once it runs out
+#[inline]
+pub fn spend(gas: &mut Option<u64>, cost: u64) -> result::Result<(), Error> {
+    if let Some(left) = gas {
+        if *left < cost {
+            *left = 0;
+            return Err(Error::NonDeterministic(Mote::Gas, D(0)));
+        }
+        *left -= cost;
+    }
+    Ok(())
+}
+
 #[allow(unused_variables)]
 fn debug_assertions(stack: &mut NockStack, noun: Noun) {
     assert_acyclic!(noun);
@@ -374,6 +551,7 @@ fn debug_assertions(stack: &mut NockStack, noun: Noun) {
 /** Interpret nock */
 pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Result {
     let terminator = Arc::clone(&TERMINATOR);
+    let deadline = Arc::clone(&DEADLINE_PASSED);
     let orig_subject = subject; // for debugging
     let snapshot = context.save();
     let virtual_frame: *const u64 = context.stack.get_frame_pointer();
@@ -385,8 +563,16 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
 
         // Bottom of mean stack
         *(context.stack.local_noun_pointer(0)) = D(0);
-        // Bottom of trace stack
-        *(context.stack.local_noun_pointer(1) as *mut *const TraceStack) = std::ptr::null();
+        // Bottom of trace stack: the top of that of the interpret call this one is nested in
+        *(context.stack.local_noun_pointer(1) as *mut *const TraceStack) = context.outer_trace;
+
+        // Stack trace of the interpret call this one is nested in
+        if !context.mean_slot.is_null() {
+            context.outer_means = T(
+                &mut context.stack,
+                &[*context.mean_slot, context.outer_means],
+            );
+        }
 
         *(context.stack.push()) = NockWork::Done;
     };
@@ -407,13 +593,20 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
             let stack_pp = context.stack.get_stack_pointer_pointer() as *const *const u64;
             let alloc_pp = context.stack.get_alloc_pointer_pointer() as *const *const u64;
             let work_f = &mut || unsafe {
-                push_formula(&mut context.stack, formula, true)?;
+                push_formula(
+                    &mut context.stack,
+                    &mut context.formulas,
+                    &mut context.gas,
+                    formula,
+                    true,
+                )?;
 
                 loop {
                     let work: NockWork = *context.stack.top();
                     match work {
                         NockWork::Done => {
                             write_trace(context);
+                            context.restore_means(&snapshot);
 
                             let stack = &mut context.stack;
                             debug_assertions(stack, orig_subject);
@@ -452,13 +645,25 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                             TodoCons::ComputeHead => {
                                 cons.todo = TodoCons::ComputeTail;
                                 *context.stack.top() = NockWork::WorkCons(cons);
-                                push_formula(&mut context.stack, cons.head, false)?;
+                                push_formula(
+                                    &mut context.stack,
+                                    &mut context.formulas,
+                                    &mut context.gas,
+                                    cons.head,
+                                    false,
+                                )?;
                             }
                             TodoCons::ComputeTail => {
                                 cons.todo = TodoCons::Cons;
                                 cons.head = res;
                                 *context.stack.top() = NockWork::WorkCons(cons);
-                                push_formula(&mut context.stack, cons.tail, false)?;
+                                push_formula(
+                                    &mut context.stack,
+                                    &mut context.formulas,
+                                    &mut context.gas,
+                                    cons.tail,
+                                    false,
+                                )?;
                             }
                             TodoCons::Cons => {
                                 let stack = &mut context.stack;
@@ -480,28 +685,76 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                             context.stack.pop::<NockWork>();
                         }
                         NockWork::Work2(mut vale) => {
-                            if (*terminator).load(Ordering::Relaxed) {
+                            if interrupted(&terminator, &deadline) {
                                 break BAIL_INTR;
                             }
+                            if context.profile.is_some() && trace::sample_requested() {
+                                trace::sample(context, None);
+                            }
 
                             match vale.todo {
                                 Todo2::ComputeSubject => {
                                     vale.todo = Todo2::ComputeFormula;
                                     *context.stack.top() = NockWork::Work2(vale);
-                                    push_formula(&mut context.stack, vale.subject, false)?;
+                                    push_formula(
+                                        &mut context.stack,
+                                        &mut context.formulas,
+                                        &mut context.gas,
+                                        vale.subject,
+                                        false,
+                                    )?;
                                 }
                                 Todo2::ComputeFormula => {
                                     vale.todo = Todo2::ComputeResult;
                                     vale.subject = res;
                                     *context.stack.top() = NockWork::Work2(vale);
-                                    push_formula(&mut context.stack, vale.formula, false)?;
+                                    push_formula(
+                                        &mut context.stack,
+                                        &mut context.formulas,
+                                        &mut context.gas,
+                                        vale.formula,
+                                        false,
+                                    )?;
                                 }
                                 Todo2::ComputeResult => {
+                                    // A bounded computation is charged for raw Nock
+                                    let arm = if context.gas.is_none() {
+                                        context.code.find(&mut context.stack, vale.subject, res)
+                                    } else {
+                                        None
+                                    };
+                                    if let Some(arm) = arm {
+                                        context.suspend_mean();
+                                        let code_res =
+                                            match bytecode::run(context, arm, vale.subject) {
+                                                // Rerun a crashed arm in the interpreter, for the trace its hints give
+                                                Err(Error::Deterministic(_, _))
+                                                | Err(Error::ScryCrashed(_)) => {
+                                                    bytecode::rerun(context, vale.subject, res)
+                                                }
+                                                code_res => code_res,
+                                            };
+                                        match code_res {
+                                            Ok(code_res) => {
+                                                res = code_res;
+                                                context.stack.pop::<NockWork>();
+                                                continue;
+                                            }
+                                            Err(err) => break Err(err),
+                                        }
+                                    }
+
                                     let stack = &mut context.stack;
                                     if vale.tail {
                                         stack.pop::<NockWork>();
                                         subject = vale.subject;
-                                        push_formula(stack, res, true)?;
+                                        push_formula(
+                                            stack,
+                                            &mut context.formulas,
+                                            &mut context.gas,
+                                            res,
+                                            true,
+                                        )?;
                                     } else {
                                         vale.todo = Todo2::RestoreSubject;
                                         std::mem::swap(&mut vale.subject, &mut subject);
@@ -513,7 +766,13 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
 
                                         mean_frame_push(stack, 0);
                                         *stack.push() = NockWork::Ret;
-                                        push_formula(stack, res, true)?;
+                                        push_formula(
+                                            stack,
+                                            &mut context.formulas,
+                                            &mut context.gas,
+                                            res,
+                                            true,
+                                        )?;
                                     }
                                 }
                                 Todo2::RestoreSubject => {
@@ -532,7 +791,13 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                             Todo3::ComputeChild => {
                                 thee.todo = Todo3::ComputeType;
                                 *context.stack.top() = NockWork::Work3(thee);
-                                push_formula(&mut context.stack, thee.child, false)?;
+                                push_formula(
+                                    &mut context.stack,
+                                    &mut context.formulas,
+                                    &mut context.gas,
+                                    thee.child,
+                                    false,
+                                )?;
                             }
                             Todo3::ComputeType => {
                                 res = if res.is_cell() { D(0) } else { D(1) };
@@ -543,7 +808,13 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                             Todo4::ComputeChild => {
                                 four.todo = Todo4::Increment;
                                 *context.stack.top() = NockWork::Work4(four);
-                                push_formula(&mut context.stack, four.child, false)?;
+                                push_formula(
+                                    &mut context.stack,
+                                    &mut context.formulas,
+                                    &mut context.gas,
+                                    four.child,
+                                    false,
+                                )?;
                             }
                             Todo4::Increment => {
                                 if let Ok(atom) = res.as_atom() {
@@ -559,13 +830,25 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                             Todo5::ComputeLeftChild => {
                                 five.todo = Todo5::ComputeRightChild;
                                 *context.stack.top() = NockWork::Work5(five);
-                                push_formula(&mut context.stack, five.left, false)?;
+                                push_formula(
+                                    &mut context.stack,
+                                    &mut context.formulas,
+                                    &mut context.gas,
+                                    five.left,
+                                    false,
+                                )?;
                             }
                             Todo5::ComputeRightChild => {
                                 five.todo = Todo5::TestEquals;
                                 five.left = res;
                                 *context.stack.top() = NockWork::Work5(five);
-                                push_formula(&mut context.stack, five.right, false)?;
+                                push_formula(
+                                    &mut context.stack,
+                                    &mut context.formulas,
+                                    &mut context.gas,
+                                    five.right,
+                                    false,
+                                )?;
                             }
                             Todo5::TestEquals => {
                                 let stack = &mut context.stack;
@@ -582,16 +865,34 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                             Todo6::ComputeTest => {
                                 cond.todo = Todo6::ComputeBranch;
                                 *context.stack.top() = NockWork::Work6(cond);
-                                push_formula(&mut context.stack, cond.test, false)?;
+                                push_formula(
+                                    &mut context.stack,
+                                    &mut context.formulas,
+                                    &mut context.gas,
+                                    cond.test,
+                                    false,
+                                )?;
                             }
                             Todo6::ComputeBranch => {
                                 let stack = &mut context.stack;
                                 stack.pop::<NockWork>();
                                 if let Left(direct) = res.as_either_direct_allocated() {
                                     if direct.data() == 0 {
-                                        push_formula(stack, cond.zero, cond.tail)?;
+                                        push_formula(
+                                            stack,
+                                            &mut context.formulas,
+                                            &mut context.gas,
+                                            cond.zero,
+                                            cond.tail,
+                                        )?;
                                     } else if direct.data() == 1 {
-                                        push_formula(stack, cond.once, cond.tail)?;
+                                        push_formula(
+                                            stack,
+                                            &mut context.formulas,
+                                            &mut context.gas,
+                                            cond.once,
+                                            cond.tail,
+                                        )?;
                                     } else {
                                         // Test branch of Nock 6 must return 0 or 1
                                         break BAIL_EXIT;
@@ -606,20 +907,38 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                             Todo7::ComputeSubject => {
                                 pose.todo = Todo7::ComputeResult;
                                 *context.stack.top() = NockWork::Work7(pose);
-                                push_formula(&mut context.stack, pose.subject, false)?;
+                                push_formula(
+                                    &mut context.stack,
+                                    &mut context.formulas,
+                                    &mut context.gas,
+                                    pose.subject,
+                                    false,
+                                )?;
                             }
                             Todo7::ComputeResult => {
                                 let stack = &mut context.stack;
                                 if pose.tail {
                                     stack.pop::<NockWork>();
                                     subject = res;
-                                    push_formula(stack, pose.formula, true)?;
+                                    push_formula(
+                                        stack,
+                                        &mut context.formulas,
+                                        &mut context.gas,
+                                        pose.formula,
+                                        true,
+                                    )?;
                                 } else {
                                     pose.todo = Todo7::RestoreSubject;
                                     pose.subject = subject;
                                     *stack.top() = NockWork::Work7(pose);
                                     subject = res;
-                                    push_formula(stack, pose.formula, false)?;
+                                    push_formula(
+                                        stack,
+                                        &mut context.formulas,
+                                        &mut context.gas,
+                                        pose.formula,
+                                        false,
+                                    )?;
                                 }
                             }
                             Todo7::RestoreSubject => {
@@ -631,20 +950,38 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                             Todo8::ComputeSubject => {
                                 pins.todo = Todo8::ComputeResult;
                                 *context.stack.top() = NockWork::Work8(pins);
-                                push_formula(&mut context.stack, pins.pin, false)?;
+                                push_formula(
+                                    &mut context.stack,
+                                    &mut context.formulas,
+                                    &mut context.gas,
+                                    pins.pin,
+                                    false,
+                                )?;
                             }
                             Todo8::ComputeResult => {
                                 let stack = &mut context.stack;
                                 if pins.tail {
                                     subject = T(stack, &[res, subject]);
                                     stack.pop::<NockWork>();
-                                    push_formula(stack, pins.formula, true)?;
+                                    push_formula(
+                                        stack,
+                                        &mut context.formulas,
+                                        &mut context.gas,
+                                        pins.formula,
+                                        true,
+                                    )?;
                                 } else {
                                     pins.todo = Todo8::RestoreSubject;
                                     pins.pin = subject;
                                     *stack.top() = NockWork::Work8(pins);
                                     subject = T(stack, &[res, subject]);
-                                    push_formula(stack, pins.formula, false)?;
+                                    push_formula(
+                                        stack,
+                                        &mut context.formulas,
+                                        &mut context.gas,
+                                        pins.formula,
+                                        false,
+                                    )?;
                                 }
                             }
                             Todo8::RestoreSubject => {
@@ -653,38 +990,87 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                             }
                         },
                         NockWork::Work9(mut kale) => {
-                            if (*terminator).load(Ordering::Relaxed) {
+                            if interrupted(&terminator, &deadline) {
                                 break BAIL_INTR;
                             }
+                            if context.profile.is_some() && trace::sample_requested() {
+                                trace::sample(context, None);
+                            }
 
                             match kale.todo {
                                 Todo9::ComputeCore => {
                                     kale.todo = Todo9::ComputeResult;
                                     *context.stack.top() = NockWork::Work9(kale);
-                                    push_formula(&mut context.stack, kale.core, false)?;
+                                    push_formula(
+                                        &mut context.stack,
+                                        &mut context.formulas,
+                                        &mut context.gas,
+                                        kale.core,
+                                        false,
+                                    )?;
                                 }
                                 Todo9::ComputeResult => {
                                     if let Ok(mut formula) = res.slot_atom(kale.axis) {
+                                        context.suspend_mean();
+                                        let decoded = context.formulas.get(formula);
+                                        let mut jetted = false;
                                         if !cfg!(feature = "sham_hints") {
-                                            if let Some((jet, _path)) = context.warm.find_jet(
-                                                &mut context.stack,
-                                                &mut res,
-                                                &mut formula,
-                                            ) {
-                                                match jet(context, res) {
-                                                    Ok(jet_res) => {
+                                            let jet = match decoded {
+                                                Some(decoded) => (*decoded).find_jet(
+                                                    &mut context.stack,
+                                                    &context.warm,
+                                                    &mut formula,
+                                                    res,
+                                                ),
+                                                None => context.warm.find_jet(
+                                                    &mut context.stack,
+                                                    &mut res,
+                                                    &mut formula,
+                                                ),
+                                            };
+                                            if let Some((jet, path)) = jet {
+                                                jetted = true;
+                                                match apply_jet(context, jet, path, res, formula) {
+                                                    Some(Ok(jet_res)) => {
                                                         res = jet_res;
                                                         context.stack.pop::<NockWork>();
                                                         continue;
                                                     }
-                                                    Err(JetErr::Punt) => {}
-                                                    Err(err) => {
-                                                        break Err(err.into());
-                                                    }
+                                                    Some(Err(err)) => break Err(err),
+                                                    None => {}
                                                 }
                                             }
                                         };
 
+                                        // The debugger can only stop in raw Nock, and a
+                                        // bounded computation is charged for raw Nock
+                                        let arm = if context.debugger.is_none()
+                                            && context.gas.is_none()
+                                        {
+                                            context.code.find(&mut context.stack, res, formula)
+                                        } else {
+                                            None
+                                        };
+                                        if let Some(arm) = arm {
+                                            let code_res = match bytecode::run(context, arm, res) {
+                                                // Rerun a crashed arm in the interpreter, for the trace its hints give
+                                                Err(Error::Deterministic(_, _))
+                                                | Err(Error::ScryCrashed(_)) => {
+                                                    bytecode::rerun(context, res, formula)
+                                                }
+                                                code_res => code_res,
+                                            };
+                                            match code_res {
+                                                Ok(code_res) => {
+                                                    res = code_res;
+                                                    context.stack.pop::<NockWork>();
+                                                    continue;
+                                                }
+                                                Err(err) => break Err(err),
+                                            }
+                                        }
+
+                                        let traced = context.traces_calls();
                                         let stack = &mut context.stack;
                                         if kale.tail {
                                             stack.pop::<NockWork>();
@@ -692,16 +1078,29 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                                             // We could trace on 2 as well, but 2 only comes from Hoon via
                                             // '.*', so we can assume it's never directly used to invoke
                                             // jetted code.
-                                            if context.trace_info.is_some() {
-                                                if let Some(path) =
-                                                    context.cold.matches(stack, &mut res)
-                                                {
-                                                    append_trace(stack, path);
-                                                };
+                                            let path = if traced {
+                                                trace_call(
+                                                    stack,
+                                                    &mut context.cold,
+                                                    &mut context.jet_stats,
+                                                    &mut res,
+                                                    jetted,
+                                                )
+                                            } else {
+                                                None
                                             };
 
                                             subject = res;
-                                            push_formula(stack, formula, true)?;
+                                            push_decoded(
+                                                stack,
+                                                &mut context.gas,
+                                                decoded,
+                                                formula,
+                                                true,
+                                            )?;
+                                            if let Some(path) = path {
+                                                debugger::call(context, res, formula, path);
+                                            }
                                         } else {
                                             kale.todo = Todo9::RestoreSubject;
                                             kale.core = subject;
@@ -714,17 +1113,27 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                                             subject = res;
                                             mean_frame_push(stack, 0);
                                             *stack.push() = NockWork::Ret;
-                                            push_formula(stack, formula, true)?;
+                                            push_decoded(
+                                                stack,
+                                                &mut context.gas,
+                                                decoded,
+                                                formula,
+                                                true,
+                                            )?;
 
                                             // We could trace on 2 as well, but 2 only comes from Hoon via
                                             // '.*', so we can assume it's never directly used to invoke
                                             // jetted code.
-                                            if context.trace_info.is_some() {
-                                                if let Some(path) =
-                                                    context.cold.matches(stack, &mut res)
-                                                {
-                                                    append_trace(stack, path);
-                                                };
+                                            if traced {
+                                                if let Some(path) = trace_call(
+                                                    stack,
+                                                    &mut context.cold,
+                                                    &mut context.jet_stats,
+                                                    &mut res,
+                                                    jetted,
+                                                ) {
+                                                    debugger::call(context, res, formula, path);
+                                                }
                                             };
                                         }
                                     } else {
@@ -749,13 +1158,25 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                                 Todo10::ComputeTree => {
                                     diet.todo = Todo10::ComputePatch; // should we compute patch then tree?
                                     *context.stack.top() = NockWork::Work10(diet);
-                                    push_formula(&mut context.stack, diet.tree, false)?;
+                                    push_formula(
+                                        &mut context.stack,
+                                        &mut context.formulas,
+                                        &mut context.gas,
+                                        diet.tree,
+                                        false,
+                                    )?;
                                 }
                                 Todo10::ComputePatch => {
                                     diet.todo = Todo10::Edit;
                                     diet.tree = res;
                                     *context.stack.top() = NockWork::Work10(diet);
-                                    push_formula(&mut context.stack, diet.patch, false)?;
+                                    push_formula(
+                                        &mut context.stack,
+                                        &mut context.formulas,
+                                        &mut context.gas,
+                                        diet.patch,
+                                        false,
+                                    )?;
                                 }
                                 Todo10::Edit => {
                                     res = edit(
@@ -770,6 +1191,7 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                         }
                         NockWork::Work11D(mut dint) => match dint.todo {
                             Todo11D::ComputeHint => {
+                                context.suspend_mean();
                                 if let Some(ret) = hint::match_pre_hint(
                                     context, subject, dint.tag, dint.hint, dint.body,
                                 ) {
@@ -785,15 +1207,22 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                                 } else {
                                     dint.todo = Todo11D::ComputeResult;
                                     *context.stack.top() = NockWork::Work11D(dint);
-                                    push_formula(&mut context.stack, dint.hint, false)?;
+                                    push_formula(
+                                        &mut context.stack,
+                                        &mut context.formulas,
+                                        &mut context.gas,
+                                        dint.hint,
+                                        false,
+                                    )?;
                                 }
                             }
                             Todo11D::ComputeResult => {
+                                context.suspend_mean();
                                 if let Some(ret) = hint::match_pre_nock(
                                     context,
                                     subject,
                                     dint.tag,
-                                    Some((dint.hint, res)),
+                                    Some((dint.hint, &mut res)),
                                     dint.body,
                                 ) {
                                     match ret {
@@ -813,10 +1242,17 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                                         dint.hint = res;
                                         *context.stack.top() = NockWork::Work11D(dint);
                                     }
-                                    push_formula(&mut context.stack, dint.body, dint.tail)?;
+                                    push_formula(
+                                        &mut context.stack,
+                                        &mut context.formulas,
+                                        &mut context.gas,
+                                        dint.body,
+                                        dint.tail,
+                                    )?;
                                 }
                             }
                             Todo11D::Done => {
+                                context.suspend_mean();
                                 if let Some(found) = hint::match_post_nock(
                                     context,
                                     subject,
@@ -832,6 +1268,7 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                         },
                         NockWork::Work11S(mut sint) => match sint.todo {
                             Todo11S::ComputeResult => {
+                                context.suspend_mean();
                                 if let Some(ret) = hint::match_pre_nock(
                                     context, subject, sint.tag, None, sint.body,
                                 ) {
@@ -851,10 +1288,17 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                                         sint.todo = Todo11S::Done;
                                         *context.stack.top() = NockWork::Work11S(sint);
                                     }
-                                    push_formula(&mut context.stack, sint.body, sint.tail)?;
+                                    push_formula(
+                                        &mut context.stack,
+                                        &mut context.formulas,
+                                        &mut context.gas,
+                                        sint.body,
+                                        sint.tail,
+                                    )?;
                                 }
                             }
                             Todo11S::Done => {
+                                context.suspend_mean();
                                 if let Some(found) = hint::match_post_nock(
                                     context, subject, sint.tag, None, sint.body, res,
                                 ) {
@@ -868,14 +1312,26 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                                 let stack = &mut context.stack;
                                 scry.todo = Todo12::ComputePath;
                                 *stack.top() = NockWork::Work12(scry);
-                                push_formula(stack, scry.reff, false)?;
+                                push_formula(
+                                    stack,
+                                    &mut context.formulas,
+                                    &mut context.gas,
+                                    scry.reff,
+                                    false,
+                                )?;
                             }
                             Todo12::ComputePath => {
                                 let stack = &mut context.stack;
                                 scry.todo = Todo12::Scry;
                                 scry.reff = res;
                                 *stack.top() = NockWork::Work12(scry);
-                                push_formula(stack, scry.path, false)?;
+                                push_formula(
+                                    stack,
+                                    &mut context.formulas,
+                                    &mut context.gas,
+                                    scry.path,
+                                    false,
+                                )?;
                             }
                             Todo12::Scry => {
                                 if let Some(cell) = context.scry_stack.cell() {
@@ -899,6 +1355,7 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
                                     // Alternately, we could use scry_core as the subject and [9 2 0 1] as
                                     // the formula. It's unclear if performance will be better with a purely
                                     // static formula.
+                                    context.suspend_mean();
                                     match interpret(context, D(0), scry_form) {
                                         Ok(noun) => match noun.as_either_atom_cell() {
                                             Left(atom) => {
@@ -964,217 +1421,257 @@ pub fn interpret(context: &mut Context, mut subject: Noun, formula: Noun) -> Res
     }
 }
 
-fn push_formula(stack: &mut NockStack, formula: Noun, tail: bool) -> Result {
+/// Push the work which computes a formula
+fn push_formula(
+    stack: &mut NockStack,
+    formulas: &mut Formulas,
+    gas: &mut Option<u64>,
+    formula: Noun,
+    tail: bool,
+) -> Result {
+    let decoded = formulas.get(formula);
+    push_decoded(stack, gas, decoded, formula, tail)
+}
+
+/// Push the work which computes a formula, decoding it unless it is already decoded, and spend a
+/// step of the budget on it
+fn push_decoded(
+    stack: &mut NockStack,
+    gas: &mut Option<u64>,
+    decoded: Option<*mut Formula>,
+    formula: Noun,
+    tail: bool,
+) -> Result {
+    spend(gas, 1)?;
+    let mut work = match decoded {
+        Some(decoded) => unsafe { (*decoded).work },
+        None => match decode(formula) {
+            Some(work) => work,
+            None => return BAIL_EXIT,
+        },
+    };
+    if !tail {
+        match work {
+            NockWork::Work2(ref mut vale) => vale.tail = false,
+            NockWork::Work6(ref mut cond) => cond.tail = false,
+            NockWork::Work7(ref mut pose) => pose.tail = false,
+            NockWork::Work8(ref mut pins) => pins.tail = false,
+            NockWork::Work9(ref mut kale) => kale.tail = false,
+            NockWork::Work11D(ref mut dint) => dint.tail = false,
+            NockWork::Work11S(ref mut sint) => sint.tail = false,
+            _ => {}
+        }
+    }
     unsafe {
-        if let Ok(formula_cell) = formula.as_cell() {
-            // Formula
-            match formula_cell.head().as_either_atom_cell() {
-                Right(_cell) => {
-                    *stack.push() = NockWork::WorkCons(NockCons {
-                        todo: TodoCons::ComputeHead,
-                        head: formula_cell.head(),
-                        tail: formula_cell.tail(),
-                    });
-                }
-                Left(atom) => {
-                    if let Ok(direct) = atom.as_direct() {
-                        match direct.data() {
-                            0 => {
-                                if let Ok(axis_atom) = formula_cell.tail().as_atom() {
-                                    *stack.push() = NockWork::Work0(Nock0 { axis: axis_atom });
-                                } else {
-                                    // Axis for Nock 0 must be an atom
-                                    return BAIL_EXIT;
-                                }
-                            }
-                            1 => {
-                                *stack.push() = NockWork::Work1(Nock1 {
-                                    noun: formula_cell.tail(),
-                                });
-                            }
-                            2 => {
-                                if let Ok(arg_cell) = formula_cell.tail().as_cell() {
-                                    *stack.push() = NockWork::Work2(Nock2 {
-                                        todo: Todo2::ComputeSubject,
-                                        subject: arg_cell.head(),
-                                        formula: arg_cell.tail(),
-                                        tail,
-                                    });
-                                } else {
-                                    // Argument to Nock 2 must be cell
-                                    return BAIL_EXIT;
-                                };
-                            }
-                            3 => {
-                                *stack.push() = NockWork::Work3(Nock3 {
-                                    todo: Todo3::ComputeChild,
-                                    child: formula_cell.tail(),
-                                });
+        *stack.push() = work;
+    }
+    Ok(D(0))
+}
+
+/**
+ * Decode a formula into the work which computes it, or [None] if it is not a valid formula.
+ *
+ * The `tail` flag of the work is set wherever the formula allows a tail call, and is cleared by
+ * [push_formula] if the formula is not itself in tail position.
+ */
+fn decode(formula: Noun) -> Option<NockWork> {
+    if let Ok(formula_cell) = formula.as_cell() {
+        // Formula
+        match formula_cell.head().as_either_atom_cell() {
+            Right(_cell) => Some(NockWork::WorkCons(NockCons {
+                todo: TodoCons::ComputeHead,
+                head: formula_cell.head(),
+                tail: formula_cell.tail(),
+            })),
+            Left(atom) => {
+                if let Ok(direct) = atom.as_direct() {
+                    match direct.data() {
+                        0 => {
+                            if let Ok(axis_atom) = formula_cell.tail().as_atom() {
+                                Some(NockWork::Work0(Nock0 { axis: axis_atom }))
+                            } else {
+                                // Axis for Nock 0 must be an atom
+                                None
                             }
-                            4 => {
-                                *stack.push() = NockWork::Work4(Nock4 {
-                                    todo: Todo4::ComputeChild,
-                                    child: formula_cell.tail(),
-                                });
+                        }
+                        1 => Some(NockWork::Work1(Nock1 {
+                            noun: formula_cell.tail(),
+                        })),
+                        2 => {
+                            if let Ok(arg_cell) = formula_cell.tail().as_cell() {
+                                Some(NockWork::Work2(Nock2 {
+                                    todo: Todo2::ComputeSubject,
+                                    subject: arg_cell.head(),
+                                    formula: arg_cell.tail(),
+                                    tail: true,
+                                }))
+                            } else {
+                                // Argument to Nock 2 must be cell
+                                None
                             }
-                            5 => {
-                                if let Ok(arg_cell) = formula_cell.tail().as_cell() {
-                                    *stack.push() = NockWork::Work5(Nock5 {
-                                        todo: Todo5::ComputeLeftChild,
-                                        left: arg_cell.head(),
-                                        right: arg_cell.tail(),
-                                    });
-                                } else {
-                                    // Argument to Nock 5 must be cell
-                                    return BAIL_EXIT;
-                                };
+                        }
+                        3 => Some(NockWork::Work3(Nock3 {
+                            todo: Todo3::ComputeChild,
+                            child: formula_cell.tail(),
+                        })),
+                        4 => Some(NockWork::Work4(Nock4 {
+                            todo: Todo4::ComputeChild,
+                            child: formula_cell.tail(),
+                        })),
+                        5 => {
+                            if let Ok(arg_cell) = formula_cell.tail().as_cell() {
+                                Some(NockWork::Work5(Nock5 {
+                                    todo: Todo5::ComputeLeftChild,
+                                    left: arg_cell.head(),
+                                    right: arg_cell.tail(),
+                                }))
+                            } else {
+                                // Argument to Nock 5 must be cell
+                                None
                             }
-                            6 => {
-                                if let Ok(arg_cell) = formula_cell.tail().as_cell() {
-                                    if let Ok(branch_cell) = arg_cell.tail().as_cell() {
-                                        *stack.push() = NockWork::Work6(Nock6 {
-                                            todo: Todo6::ComputeTest,
-                                            test: arg_cell.head(),
-                                            zero: branch_cell.head(),
-                                            once: branch_cell.tail(),
-                                            tail,
-                                        });
-                                    } else {
-                                        // Argument tail to Nock 6 must be cell
-                                        return BAIL_EXIT;
-                                    };
+                        }
+                        6 => {
+                            if let Ok(arg_cell) = formula_cell.tail().as_cell() {
+                                if let Ok(branch_cell) = arg_cell.tail().as_cell() {
+                                    Some(NockWork::Work6(Nock6 {
+                                        todo: Todo6::ComputeTest,
+                                        test: arg_cell.head(),
+                                        zero: branch_cell.head(),
+                                        once: branch_cell.tail(),
+                                        tail: true,
+                                    }))
                                 } else {
-                                    // Argument to Nock 6 must be cell
-                                    return BAIL_EXIT;
+                                    // Argument tail to Nock 6 must be cell
+                                    None
                                 }
+                            } else {
+                                // Argument to Nock 6 must be cell
+                                None
                             }
-                            7 => {
-                                if let Ok(arg_cell) = formula_cell.tail().as_cell() {
-                                    *stack.push() = NockWork::Work7(Nock7 {
-                                        todo: Todo7::ComputeSubject,
-                                        subject: arg_cell.head(),
-                                        formula: arg_cell.tail(),
-                                        tail,
-                                    });
-                                } else {
-                                    // Argument to Nock 7 must be cell
-                                    return BAIL_EXIT;
-                                };
+                        }
+                        7 => {
+                            if let Ok(arg_cell) = formula_cell.tail().as_cell() {
+                                Some(NockWork::Work7(Nock7 {
+                                    todo: Todo7::ComputeSubject,
+                                    subject: arg_cell.head(),
+                                    formula: arg_cell.tail(),
+                                    tail: true,
+                                }))
+                            } else {
+                                // Argument to Nock 7 must be cell
+                                None
                             }
-                            8 => {
-                                if let Ok(arg_cell) = formula_cell.tail().as_cell() {
-                                    *stack.push() = NockWork::Work8(Nock8 {
-                                        todo: Todo8::ComputeSubject,
-                                        pin: arg_cell.head(),
-                                        formula: arg_cell.tail(),
-                                        tail,
-                                    });
+                        }
+                        8 => {
+                            if let Ok(arg_cell) = formula_cell.tail().as_cell() {
+                                Some(NockWork::Work8(Nock8 {
+                                    todo: Todo8::ComputeSubject,
+                                    pin: arg_cell.head(),
+                                    formula: arg_cell.tail(),
+                                    tail: true,
+                                }))
+                            } else {
+                                // Argument to Nock 8 must be cell
+                                None
+                            }
+                        }
+                        9 => {
+                            if let Ok(arg_cell) = formula_cell.tail().as_cell() {
+                                if let Ok(axis_atom) = arg_cell.head().as_atom() {
+                                    Some(NockWork::Work9(Nock9 {
+                                        todo: Todo9::ComputeCore,
+                                        axis: axis_atom,
+                                        core: arg_cell.tail(),
+                                        tail: true,
+                                    }))
                                 } else {
-                                    // Argument to Nock 8 must be cell
-                                    return BAIL_EXIT;
-                                };
+                                    // Axis for Nock 9 must be an atom
+                                    None
+                                }
+                            } else {
+                                // Argument to Nock 9 must be cell
+                                None
                             }
-                            9 => {
-                                if let Ok(arg_cell) = formula_cell.tail().as_cell() {
-                                    if let Ok(axis_atom) = arg_cell.head().as_atom() {
-                                        *stack.push() = NockWork::Work9(Nock9 {
-                                            todo: Todo9::ComputeCore,
+                        }
+                        10 => {
+                            if let Ok(arg_cell) = formula_cell.tail().as_cell() {
+                                if let Ok(patch_cell) = arg_cell.head().as_cell() {
+                                    if let Ok(axis_atom) = patch_cell.head().as_atom() {
+                                        Some(NockWork::Work10(Nock10 {
+                                            todo: Todo10::ComputeTree,
                                             axis: axis_atom,
-                                            core: arg_cell.tail(),
-                                            tail,
-                                        });
+                                            tree: arg_cell.tail(),
+                                            patch: patch_cell.tail(),
+                                        }))
                                     } else {
-                                        // Axis for Nock 9 must be an atom
-                                        return BAIL_EXIT;
+                                        // Axis for Nock 10 must be an atom
+                                        None
                                     }
                                 } else {
-                                    // Argument to Nock 9 must be cell
-                                    return BAIL_EXIT;
-                                };
-                            }
-                            10 => {
-                                if let Ok(arg_cell) = formula_cell.tail().as_cell() {
-                                    if let Ok(patch_cell) = arg_cell.head().as_cell() {
-                                        if let Ok(axis_atom) = patch_cell.head().as_atom() {
-                                            *stack.push() = NockWork::Work10(Nock10 {
-                                                todo: Todo10::ComputeTree,
-                                                axis: axis_atom,
-                                                tree: arg_cell.tail(),
-                                                patch: patch_cell.tail(),
-                                            });
-                                        } else {
-                                            // Axis for Nock 10 must be an atom
-                                            return BAIL_EXIT;
-                                        }
-                                    } else {
-                                        // Head of argument to Nock 10 must be a cell
-                                        return BAIL_EXIT;
-                                    };
-                                } else {
-                                    // Argument to Nock 10 must be a cell
-                                    return BAIL_EXIT;
-                                };
+                                    // Head of argument to Nock 10 must be a cell
+                                    None
+                                }
+                            } else {
+                                // Argument to Nock 10 must be a cell
+                                None
                             }
-                            11 => {
-                                if let Ok(arg_cell) = formula_cell.tail().as_cell() {
-                                    match arg_cell.head().as_either_atom_cell() {
-                                        Left(tag_atom) => {
-                                            *stack.push() = NockWork::Work11S(Nock11S {
-                                                todo: Todo11S::ComputeResult,
+                        }
+                        11 => {
+                            if let Ok(arg_cell) = formula_cell.tail().as_cell() {
+                                match arg_cell.head().as_either_atom_cell() {
+                                    Left(tag_atom) => Some(NockWork::Work11S(Nock11S {
+                                        todo: Todo11S::ComputeResult,
+                                        tag: tag_atom,
+                                        body: arg_cell.tail(),
+                                        tail: hint::is_tail(tag_atom),
+                                    })),
+                                    Right(hint_cell) => {
+                                        if let Ok(tag_atom) = hint_cell.head().as_atom() {
+                                            Some(NockWork::Work11D(Nock11D {
+                                                todo: Todo11D::ComputeHint,
                                                 tag: tag_atom,
+                                                hint: hint_cell.tail(),
                                                 body: arg_cell.tail(),
-                                                tail: tail && hint::is_tail(tag_atom),
-                                            });
-                                        }
-                                        Right(hint_cell) => {
-                                            if let Ok(tag_atom) = hint_cell.head().as_atom() {
-                                                *stack.push() = NockWork::Work11D(Nock11D {
-                                                    todo: Todo11D::ComputeHint,
-                                                    tag: tag_atom,
-                                                    hint: hint_cell.tail(),
-                                                    body: arg_cell.tail(),
-                                                    tail: tail && hint::is_tail(tag_atom),
-                                                });
-                                            } else {
-                                                // Hint tag must be an atom
-                                                return BAIL_EXIT;
-                                            }
+                                                tail: hint::is_tail(tag_atom),
+                                            }))
+                                        } else {
+                                            // Hint tag must be an atom
+                                            None
                                         }
-                                    };
-                                } else {
-                                    // Argument for Nock 11 must be cell
-                                    return BAIL_EXIT;
-                                };
-                            }
-                            12 => {
-                                if let Ok(arg_cell) = formula_cell.tail().as_cell() {
-                                    *stack.push() = NockWork::Work12(Nock12 {
-                                        todo: Todo12::ComputeReff,
-                                        reff: arg_cell.head(),
-                                        path: arg_cell.tail(),
-                                    });
-                                } else {
-                                    // Argument for Nock 12 must be cell
-                                    return BAIL_EXIT;
+                                    }
                                 }
+                            } else {
+                                // Argument for Nock 11 must be cell
+                                None
                             }
-                            _ => {
-                                // Invalid formula opcode
-                                return BAIL_EXIT;
+                        }
+                        12 => {
+                            if let Ok(arg_cell) = formula_cell.tail().as_cell() {
+                                Some(NockWork::Work12(Nock12 {
+                                    todo: Todo12::ComputeReff,
+                                    reff: arg_cell.head(),
+                                    path: arg_cell.tail(),
+                                }))
+                            } else {
+                                // Argument for Nock 12 must be cell
+                                None
                             }
                         }
-                    } else {
-                        // Formula opcode must be direct atom
-                        return BAIL_EXIT;
+                        _ => {
+                            // Invalid formula opcode
+                            None
+                        }
                     }
+                } else {
+                    // Formula opcode must be direct atom
+                    None
                 }
             }
-        } else {
-            // Bad formula: atoms are not formulas
-            return BAIL_EXIT;
         }
+    } else {
+        // Bad formula: atoms are not formulas
+        None
     }
-    Ok(D(0))
 }
 
 fn exit(
@@ -1222,9 +1719,10 @@ fn exit(
 fn mean_frame_push(stack: &mut NockStack, slots: usize) {
     unsafe {
         let trace = *(stack.local_noun_pointer(0));
+        let trace_stack = *(stack.local_noun_pointer(1) as *const *const TraceStack);
         stack.frame_push(slots + 2);
         *(stack.local_noun_pointer(0)) = trace;
-        *(stack.local_noun_pointer(1) as *mut *const TraceStack) = std::ptr::null();
+        *(stack.local_noun_pointer(1) as *mut *const TraceStack) = trace_stack;
     }
 }
 
@@ -1333,8 +1831,84 @@ fn append_trace(stack: &mut NockStack, path: Noun) {
     }
 }
 
-/// Write fast-hinted traces to trace file
+/// Push a call into `core` onto the trace stack, if it is cold-registered, and count it in the jet
+/// statistics if it has no jet. Produces the cold path of the core, if it has one.
+fn trace_call(
+    stack: &mut NockStack,
+    cold: &mut Cold,
+    jet_stats: &mut Option<JetStats>,
+    core: &mut Noun,
+    jetted: bool,
+) -> Option<Noun> {
+    let path = cold.matches(stack, core)?;
+    if let Some(stats) = jet_stats.as_mut() {
+        unsafe {
+            let trace_stack = *(stack.local_noun_pointer(1) as *const *const TraceStack);
+            stats.lap(stack, trace_stack);
+        }
+        if !jetted {
+            stats.unjetted(stack, path);
+        }
+    }
+    append_trace(stack, path);
+    Some(path)
+}
+
+/// Run a jet matched for a Nock 9 call, charging for it, testing it, and recording it in the jet
+/// statistics, trace and profile. Returns `None` if the jet punts, to run the arm as Nock.
+pub fn apply_jet(
+    context: &mut Context,
+    jet: Jet,
+    path: Noun,
+    core: Noun,
+    formula: Noun,
+) -> Option<Result> {
+    if let Err(err) = spend(&mut context.gas, jets::cost(core)) {
+        return Some(Err(err));
+    }
+    if context.jet_test.matches(path) {
+        return Some(jets::test_jet(context, jet, path, core, formula));
+    }
+    if let Some(stats) = context.jet_stats.as_mut() {
+        unsafe {
+            let trace_stack = *(context.stack.local_noun_pointer(1) as *const *const TraceStack);
+            stats.lap(&mut context.stack, trace_stack);
+        }
+    }
+    let start = (context.jet_stats.is_some() || context.trace_info.is_some()).then(Instant::now);
+    let jet_res = jet(context, core);
+    if let Some(start) = start {
+        if let Some(stats) = context.jet_stats.as_mut() {
+            stats.jet(&mut context.stack, path, &jet_res, start.elapsed());
+        }
+        write_jet_trace_safe(context, path, start);
+    }
+    if context.profile.is_some() && trace::sample_requested() {
+        unsafe { trace::sample(context, Some(path)) };
+    }
+    match jet_res {
+        Ok(res) => Some(Ok(res)),
+        Err(JetErr::Punt) => None,
+        Err(err) => Some(Err(err.into())),
+    }
+}
+
+/// Write a jet call to the trace file, if tracing
+fn write_jet_trace_safe(context: &mut Context, path: Noun, start: Instant) {
+    if let Some(info) = context.trace_info.as_mut() {
+        if let Err(e) = write_jet_trace(&mut context.stack, info, path, start) {
+            flog!(context, "\rserf: error writing jet trace to file: {:?}", e);
+            context.trace_info = None;
+        }
+    }
+}
+
+/// Write fast-hinted traces to trace file, and add their time to the jet statistics
 unsafe fn write_trace(context: &mut Context) {
+    if let Some(stats) = context.jet_stats.as_mut() {
+        let trace_stack = *(context.stack.local_noun_pointer(1) as *mut *const TraceStack);
+        stats.time_calls(&mut context.stack, trace_stack);
+    }
     if let Some(ref mut info) = &mut context.trace_info {
         let trace_stack = *(context.stack.local_noun_pointer(1) as *mut *const TraceStack);
         // Abort writing to trace file if we encountered an error. This should
@@ -1352,29 +1926,72 @@ unsafe fn write_trace(context: &mut Context) {
 
 mod hint {
     use super::*;
+    use crate::flog::nock_fmt;
     use crate::jets;
+    use crate::jets::bits::util::rip;
     use crate::jets::cold;
-    use crate::jets::nock::util::{mook, LEAF};
-    use crate::noun::{tape, Atom, Cell, Noun, D, T};
-    use crate::serf::TERMINATOR;
-    use crate::unifying_equality::unifying_equality;
-    use ares_macros::tas;
-    use std::sync::atomic::Ordering;
-    use std::sync::Arc;
+    use crate::jets::list::util::flop;
+    use crate::jets::nock::util::{mook, LEAF, ROSE};
+    use crate::mass::{Mass, Report};
+    use crate::mug::met3_usize;
+    use crate::noun::{tape, Atom, Cell, Noun, D, T, YES};
+    use crate::serf::{interrupted, DEADLINE_PASSED, TERMINATOR};
+    use std::fmt::Arguments;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    /// The hints matched by the interpreter. Tags are matched on their bytes, so that a tag too
+    /// long for a direct atom is matched like any other.
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    enum Hint {
+        Bout,
+        Fast,
+        Hand,
+        Hela,
+        Hunk,
+        Live,
+        Lose,
+        Mass,
+        Mean,
+        Meme,
+        Memo,
+        Nara,
+        Sham,
+        Slog,
+        Spot,
+        Xray,
+        Other,
+    }
 
-    pub fn is_tail(tag: Atom) -> bool {
-        //  XX: handle IndirectAtom tags
-        match tag.direct() {
-            #[allow(clippy::match_like_matches_macro)]
-            Some(dtag) => match dtag.data() {
-                tas!(b"fast") => false,
-                tas!(b"memo") => false,
-                _ => true,
-            },
-            None => true,
+    impl Hint {
+        fn of(tag: Atom) -> Hint {
+            match &tag.as_bytes()[..met3_usize(tag)] {
+                b"bout" => Hint::Bout,
+                b"fast" => Hint::Fast,
+                b"hand" => Hint::Hand,
+                b"hela" => Hint::Hela,
+                b"hunk" => Hint::Hunk,
+                b"live" => Hint::Live,
+                b"lose" => Hint::Lose,
+                b"mass" => Hint::Mass,
+                b"mean" => Hint::Mean,
+                b"meme" => Hint::Meme,
+                b"memo" => Hint::Memo,
+                b"nara" => Hint::Nara,
+                b"sham" => Hint::Sham,
+                b"slog" => Hint::Slog,
+                b"spot" => Hint::Spot,
+                b"xray" => Hint::Xray,
+                _ => Hint::Other,
+            }
         }
     }
 
+    /// Whether the hinted formula may be evaluated in tail position, which it may not if the hint
+    /// has anything to do once the formula is evaluated
+    pub fn is_tail(tag: Atom) -> bool {
+        !matches!(Hint::of(tag), Hint::Bout | Hint::Fast | Hint::Memo)
+    }
+
     /** Match dynamic hints before the hint formula is evaluated */
     pub fn match_pre_hint(
         context: &mut Context,
@@ -1383,69 +2000,39 @@ mod hint {
         hint: Noun,
         body: Noun,
     ) -> Option<Result> {
-        //  XX: handle IndirectAtom tags
-        match tag.direct()?.data() {
-            tas!(b"sham") => {
+        match Hint::of(tag) {
+            Hint::Sham => {
                 if cfg!(feature = "sham_hints") {
                     let jet_formula = hint.cell()?;
                     // XX: what is the head here?
                     let jet_name = jet_formula.tail();
 
                     if let Some(jet) = jets::get_jet(context, jet_name) {
-                        match jet(context, subject) {
-                            Ok(mut jet_res) => {
-                                //  XX: simplify this by moving jet test mode into the 11 code in interpret, or into its own function?
-                                // if in test mode, check that the jet returns the same result as the raw nock
-                                if jets::get_jet_test_mode(jet_name) {
-                                    //  XX: we throw away trace, which might matter for non-deterministic errors
-                                    //      maybe mook and slog it?
-                                    match interpret(context, subject, body) {
-                                        Ok(mut nock_res) => {
-                                            let stack = &mut context.stack;
-                                            if unsafe {
-                                                !unifying_equality(
-                                                    stack,
-                                                    &mut nock_res,
-                                                    &mut jet_res,
-                                                )
-                                            } {
-                                                //  XX: need NockStack allocated string interpolation
-                                                // let tape = tape(stack, "jet mismatch in {}, raw: {}, jetted: {}", jet_name, nock_res, jet_res);
-                                                // let mean = T(stack, &[D(tas!(b"mean")), tape]);
-                                                // mean_push(stack, mean);
-                                                Some(BAIL_EXIT)
-                                            } else {
-                                                Some(Ok(nock_res))
-                                            }
-                                        }
-                                        Err(error) => {
-                                            //  XX: need NockStack allocated string interpolation
-                                            // let stack = &mut context.stack;
-                                            // let tape = tape(stack, "jet mismatch in {}, raw: {}, jetted: {}", jet_name, err, jet_res);
-                                            // let mean = T(stack, &[D(tas!(b"mean")), tape]);
-                                            // mean_push(stack, mean);
-
-                                            match error {
-                                                Error::NonDeterministic(mote, _) => {
-                                                    Some(Err(Error::NonDeterministic(mote, D(0))))
-                                                }
-                                                _ => Some(BAIL_EXIT),
-                                            }
-                                        }
-                                    }
-                                } else {
-                                    Some(Ok(jet_res))
+                        if let Err(err) = spend(&mut context.gas, jets::cost(subject)) {
+                            return Some(Err(err));
+                        }
+                        //  Only make the jet's path if jets are being tested
+                        let tested = if context.jet_test.is_off() {
+                            None
+                        } else {
+                            let path = T(&mut context.stack, &[jet_name, D(0)]);
+                            context.jet_test.matches(path).then_some(path)
+                        };
+                        if let Some(path) = tested {
+                            Some(jets::test_jet(context, jet, path, subject, body))
+                        } else {
+                            match jet(context, subject) {
+                                Ok(jet_res) => Some(Ok(jet_res)),
+                                Err(JetErr::Punt) => None,
+                                Err(err) => {
+                                    //  XX: need NockStack allocated string interpolation
+                                    // let stack = &mut context.stack;
+                                    // let tape = tape(stack, "{} jet error in {}", err, jet_name);
+                                    // let mean = T(stack, &[D(tas!(b"mean")), tape]);
+                                    // mean_push(stack, mean);
+                                    Some(Err(err.into()))
                                 }
                             }
-                            Err(JetErr::Punt) => None,
-                            Err(err) => {
-                                //  XX: need NockStack allocated string interpolation
-                                // let stack = &mut context.stack;
-                                // let tape = tape(stack, "{} jet error in {}", err, jet_name);
-                                // let mean = T(stack, &[D(tas!(b"mean")), tape]);
-                                // mean_push(stack, mean);
-                                Some(Err(err.into()))
-                            }
                         }
                     } else {
                         None
@@ -1454,7 +2041,7 @@ mod hint {
                     None
                 }
             }
-            tas!(b"memo") => {
+            Hint::Memo => {
                 let stack = &mut context.stack;
                 let mut key = Cell::new(stack, subject, body).as_noun();
                 context.cache.lookup(stack, &mut key).map(Ok)
@@ -1463,17 +2050,20 @@ mod hint {
         }
     }
 
-    /** Match static and dynamic hints before the nock formula is evaluated */
+    /** Match static and dynamic hints before the nock formula is evaluated
+     *
+     * A dynamic hint may replace its clue with whatever it needs once the formula is evaluated.
+     */
     pub fn match_pre_nock(
         context: &mut Context,
-        _subject: Noun,
+        subject: Noun,
         tag: Atom,
-        hint: Option<(Noun, Noun)>,
-        _body: Noun,
+        hint: Option<(Noun, &mut Noun)>,
+        body: Noun,
     ) -> Option<Result> {
-        //  XX: handle IndirectAtom tags
-        match tag.direct()?.data() {
-            tas!(b"slog") => {
+        let hint_tag = Hint::of(tag);
+        match hint_tag {
+            Hint::Slog => {
                 let stack = &mut context.stack;
                 let newt = &mut context.newt;
 
@@ -1484,24 +2074,59 @@ mod hint {
 
                 newt.slog(stack, pri, tank);
             }
-            tas!(b"hand") | tas!(b"hunk") | tas!(b"lose") | tas!(b"mean") | tas!(b"spot") => {
-                let terminator = Arc::clone(&TERMINATOR);
-                if (*terminator).load(Ordering::Relaxed) {
+            Hint::Hand | Hint::Hunk | Hint::Lose | Hint::Mean | Hint::Spot => {
+                if interrupted(&TERMINATOR, &DEADLINE_PASSED) {
                     return Some(BAIL_INTR);
                 }
 
                 let stack = &mut context.stack;
                 let (_form, clue) = hint?;
-                let noun = T(stack, &[tag.as_noun(), clue]);
+                let noun = T(stack, &[tag.as_noun(), *clue]);
                 mean_push(stack, noun);
+                if hint_tag == Hint::Spot && context.debugger.is_some() {
+                    debugger::spot(context, subject, body, *clue);
+                }
+            }
+            Hint::Bout => {
+                // Keep the time alongside the clue, for %bout to print after the formula
+                let (_form, clue) = hint?;
+                *clue = T(&mut context.stack, &[*clue, D(now())]);
+            }
+            Hint::Meme => {
+                let free = context.stack.free() << 3;
+                let used = (context.stack.size() << 3) - free;
+                slog_fmt(
+                    context,
+                    0,
+                    format_args!("meme: {} bytes used, {} bytes free", used, free),
+                );
             }
-            tas!(b"hela") => {
-                //  XX: This only prints the trace down to the bottom of THIS
-                //      interpret call, making this neither a %nara nor a %hela
-                //      hint, as Vere understands them. We'll need to
-                //      recursively work down frames to get the stack trace all
-                //      the way to the root.
-                let mean = unsafe { *(context.stack.local_noun_pointer(0)) };
+            Hint::Mass => {
+                permit_alloc(|| {
+                    let mut mass = Mass::new(&context.stack);
+                    let subject = mass.part("subject", subject);
+                    let report = Report::new(context, &mut mass, vec![subject]);
+                    report.slog(&mut context.stack, &mut context.newt);
+                });
+            }
+            Hint::Xray => {
+                xray(context, body);
+            }
+            Hint::Live => {
+                let (_form, clue) = hint?;
+                let clue = clue.cell()?;
+                if unsafe { clue.head().raw_equals(YES) } {
+                    context.live.hit(clue.tail().atom()?);
+                }
+            }
+            Hint::Hela | Hint::Nara => {
+                // %hela prints the whole stack trace, through every interpret call this one is
+                // nested in, and %nara only the frames of this interpret call
+                let mean = if hint_tag == Hint::Hela {
+                    context.full_mean()
+                } else {
+                    unsafe { *(context.stack.local_noun_pointer(0)) }
+                };
                 let tone = Cell::new(&mut context.stack, D(2), mean);
 
                 match mook(context, tone, true) {
@@ -1561,84 +2186,97 @@ mod hint {
         let hot = &context.hot;
         let cache = &mut context.cache;
 
-        //  XX: handle IndirectAtom tags
-        match tag.direct()?.data() {
-            tas!(b"memo") => {
+        match Hint::of(tag) {
+            Hint::Memo => {
                 let mut key = Cell::new(stack, subject, body).as_noun();
                 context.cache = cache.insert(stack, &mut key, res);
             }
-            tas!(b"hand") | tas!(b"hunk") | tas!(b"lose") | tas!(b"mean") | tas!(b"spot") => {
+            Hint::Hand | Hint::Hunk | Hint::Lose | Hint::Mean | Hint::Spot => {
                 mean_pop(stack);
             }
-            tas!(b"fast") => {
-                if !cfg!(feature = "sham_hints") {
-                    if let Some(clue) = hint {
-                        let cold_res: cold::Result = {
-                            let chum = clue.slot(2).ok()?;
-
-                            let mut parent = clue.slot(6).ok()?;
-                            loop {
-                                if let Ok(parent_cell) = parent.as_cell() {
-                                    if unsafe { parent_cell.head().raw_equals(D(11)) } {
-                                        match parent.slot(7) {
-                                            Ok(noun) => {
-                                                parent = noun;
-                                            }
-                                            Err(_) => {
-                                                return None;
-                                            }
+            Hint::Bout => {
+                let bout = hint?.cell()?;
+                let took = now().saturating_sub(bout.tail().as_direct().ok()?.data());
+                let took = format_args!("took ms/{}.{:03}", took / 1000, took % 1000);
+                match bout.head().cell() {
+                    // [pri tank]: print the time with the tank, at that priority
+                    Some(clue) => {
+                        let pri = clue.head().direct()?.data();
+                        let took = leaf_fmt(context, took)?;
+                        let stack = &mut context.stack;
+                        let sep = tape(stack, ": ");
+                        let trel = T(stack, &[sep, D(0), D(0)]);
+                        let tank = T(stack, &[ROSE, trel, took, clue.tail(), D(0)]);
+                        context.newt.slog(stack, pri, tank);
+                    }
+                    None => slog_fmt(context, 0, took),
+                }
+            }
+            Hint::Fast if !cfg!(feature = "sham_hints") => {
+                if let Some(clue) = hint {
+                    let cold_res: cold::Result = {
+                        let chum = clue.slot(2).ok()?;
+
+                        let mut parent = clue.slot(6).ok()?;
+                        loop {
+                            if let Ok(parent_cell) = parent.as_cell() {
+                                if unsafe { parent_cell.head().raw_equals(D(11)) } {
+                                    match parent.slot(7) {
+                                        Ok(noun) => {
+                                            parent = noun;
+                                        }
+                                        Err(_) => {
+                                            return None;
                                         }
-                                    } else {
-                                        break;
                                     }
                                 } else {
-                                    return None;
-                                }
-                            }
-                            let parent_formula_op = parent.slot(2).ok()?.atom()?.direct()?;
-                            let parent_formula_ax = parent.slot(3).ok()?.atom()?;
-
-                            if parent_formula_op.data() == 1 {
-                                if parent_formula_ax.direct()?.data() == 0 {
-                                    cold.register(stack, res, parent_formula_ax, chum)
-                                } else {
-                                    //  XX: Need better message in slog; need better slogging tools
-                                    //      format!("invalid root parent axis: {} {}", chum, parent_formula_ax)
-                                    let tape = tape(
-                                        stack,
-                                        "serf: cold: register: invalid root parent axis",
-                                    );
-                                    slog_leaf(stack, newt, tape);
-                                    Ok(false)
+                                    break;
                                 }
                             } else {
-                                cold.register(stack, res, parent_formula_ax, chum)
+                                return None;
                             }
-                        };
+                        }
+                        let parent_formula_op = parent.slot(2).ok()?.atom()?.direct()?;
+                        let parent_formula_ax = parent.slot(3).ok()?.atom()?;
 
-                        match cold_res {
-                            Ok(true) => context.warm = Warm::init(stack, cold, hot),
-                            Err(cold::Error::NoParent) => {
-                                //  XX: Need better message in slog; need better slogging tools
-                                //      format!("could not find parent battery at given axis: {} {}", chum, parent_formula_ax)
-                                let tape = tape(
-                                    stack,
-                                    "serf: cold: register: could not find parent battery at given axis",
-                                );
-                                slog_leaf(stack, newt, tape);
-                            }
-                            Err(cold::Error::BadNock) => {
+                        if parent_formula_op.data() == 1 {
+                            if parent_formula_ax.direct()?.data() == 0 {
+                                cold.register(stack, res, parent_formula_ax, chum)
+                            } else {
                                 //  XX: Need better message in slog; need better slogging tools
-                                //      format!("bad clue formula: {}", clue)
-                                let tape = tape(stack, "serf: cold: register: bad clue formula");
+                                //      format!("invalid root parent axis: {} {}", chum, parent_formula_ax)
+                                let tape =
+                                    tape(stack, "serf: cold: register: invalid root parent axis");
                                 slog_leaf(stack, newt, tape);
+                                Ok(false)
                             }
-                            _ => {}
+                        } else {
+                            cold.register(stack, res, parent_formula_ax, chum)
                         }
-                    } else {
-                        let tape = tape(stack, "serf: cold: register: no clue for %fast");
-                        slog_leaf(stack, newt, tape);
+                    };
+
+                    match cold_res {
+                        Ok(true) => context.warm = Warm::init(stack, cold, hot),
+                        Err(cold::Error::NoParent) => {
+                            //  XX: Need better message in slog; need better slogging tools
+                            //      format!("could not find parent battery at given axis: {} {}", chum, parent_formula_ax)
+                            let tape = tape(
+                                stack,
+                                "serf: cold: register: could not find parent battery at given axis",
+                            );
+                            slog_leaf(stack, newt, tape);
+                        }
+                        Err(cold::Error::BadNock) => {
+                            //  XX: Need better message in slog; need better slogging tools
+                            //      format!("bad clue formula: {}", clue)
+                            let tape = tape(stack, "serf: cold: register: bad clue formula");
+                            slog_leaf(stack, newt, tape);
+                        }
+                        _ => {}
                     }
+                } else {
+                    let tape = tape(stack, "serf: cold: register: no clue for %fast");
+                    slog_leaf(stack, newt, tape);
                 }
             }
             _ => {}
@@ -1651,6 +2289,145 @@ mod hint {
         let tank = T(stack, &[LEAF, tape]);
         newt.slog(stack, 0u64, tank);
     }
+
+    /// Microseconds since the Unix epoch
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_micros() as u64)
+    }
+
+    /// A %leaf tank, formatted on the NockStack
+    fn leaf_fmt(context: &mut Context, fmt: Arguments<'_>) -> Option<Noun> {
+        let cord = nock_fmt(context, fmt).ok()?;
+        let stack = &mut context.stack;
+        let tape = rip(stack, 3, 1, cord).ok()?;
+        Some(T(stack, &[LEAF, tape]))
+    }
+
+    fn slog_fmt(context: &mut Context, pri: u64, fmt: Arguments<'_>) {
+        if let Some(tank) = leaf_fmt(context, fmt) {
+            context.newt.slog(&mut context.stack, pri, tank);
+        }
+    }
+
+    /// Print the structure of a formula, an operator to a line, with its operands indented below
+    fn xray(context: &mut Context, formula: Noun) {
+        let mut tang = xray_tang(context, formula);
+        while let Some(cell) = tang.cell() {
+            context.newt.slog(&mut context.stack, 0, cell.head());
+            tang = cell.tail();
+        }
+    }
+
+    /// The lines [xray] prints, as a tang. The formula is walked with a work stack of operands and
+    /// their depths, in a frame of its own, so that deep formulas don't overflow the Rust stack.
+    fn xray_tang(context: &mut Context, formula: Noun) -> Noun {
+        context.stack.frame_push(0);
+        unsafe {
+            *(context.stack.push::<(Noun, usize)>()) = (formula, 0);
+        }
+        // Newest line first, flopped once the walk is done
+        let mut lines = D(0);
+        while !context.stack.stack_is_empty() {
+            let (formula, depth) = unsafe { *(context.stack.top::<(Noun, usize)>()) };
+            unsafe {
+                context.stack.pop::<(Noun, usize)>();
+            }
+            let (line, operands) = xray_line(context, formula, depth);
+            if let Some(tank) = line {
+                lines = T(&mut context.stack, &[tank, lines]);
+            }
+            // Last operand first, so that they're popped in order
+            for operand in operands.iter().rev().flatten() {
+                unsafe {
+                    *(context.stack.push::<(Noun, usize)>()) = (*operand, depth + 1);
+                }
+            }
+        }
+        let mut tang = flop(&mut context.stack, lines).unwrap_or(D(0));
+        unsafe {
+            context.stack.preserve(&mut tang);
+            context.stack.frame_pop();
+        }
+        tang
+    }
+
+    /// The line [xray] prints for a formula at `depth`, if any, and the operands to print below it
+    fn xray_line(
+        context: &mut Context,
+        formula: Noun,
+        depth: usize,
+    ) -> (Option<Noun>, [Option<Noun>; 3]) {
+        let indent = depth * 2;
+        let Some(cell) = formula.cell() else {
+            let line = leaf_fmt(context, format_args!("{:indent$}!! {}", "", formula));
+            return (line, [None; 3]);
+        };
+        let op = match cell.head().as_either_atom_cell() {
+            Left(op) => op,
+            Right(_) => {
+                let line = leaf_fmt(context, format_args!("{:indent$}cons", ""));
+                return (line, [Some(cell.head()), Some(cell.tail()), None]);
+            }
+        };
+        let arg = cell.tail();
+        let axes: &[u64] =
+            match op.as_direct().map(|op| op.data()) {
+                Ok(0) => {
+                    let line = leaf_fmt(context, format_args!("{:indent$}0 /{}", "", arg));
+                    return (line, [None; 3]);
+                }
+                Ok(1) => {
+                    let line = match arg.atom() {
+                        Some(atom) => leaf_fmt(context, format_args!("{:indent$}1 {}", "", atom)),
+                        None => leaf_fmt(context, format_args!("{:indent$}1 [...]", "")),
+                    };
+                    return (line, [None; 3]);
+                }
+                Ok(3 | 4) => &[1],
+                Ok(2 | 5 | 7 | 8 | 12) => &[2, 3],
+                Ok(6) => &[2, 6, 7],
+                Ok(9) => {
+                    let line = arg.slot(2).ok().and_then(|axis| {
+                        leaf_fmt(context, format_args!("{:indent$}9 /{}", "", axis))
+                    });
+                    return (line, [arg.slot(3).ok(), None, None]);
+                }
+                Ok(10) => {
+                    let line = arg.slot(4).ok().and_then(|axis| {
+                        leaf_fmt(context, format_args!("{:indent$}10 /{}", "", axis))
+                    });
+                    return (line, [arg.slot(5).ok(), arg.slot(3).ok(), None]);
+                }
+                Ok(11) => {
+                    let Ok(hint) = arg.slot(2) else {
+                        return (None, [None; 3]);
+                    };
+                    let body = arg.slot(3).ok();
+                    return match hint.as_either_atom_cell() {
+                        Left(tag) => (
+                            leaf_fmt(context, format_args!("{:indent$}11 {}", "", tag)),
+                            [body, None, None],
+                        ),
+                        Right(hint) => (
+                            leaf_fmt(context, format_args!("{:indent$}11 {}", "", hint.head())),
+                            [Some(hint.tail()), body, None],
+                        ),
+                    };
+                }
+                _ => {
+                    let line = leaf_fmt(context, format_args!("{:indent$}!! {}", "", op));
+                    return (line, [None; 3]);
+                }
+            };
+        let line = leaf_fmt(context, format_args!("{:indent$}{}", "", op));
+        let mut operands = [None; 3];
+        for (operand, axis) in operands.iter_mut().zip(axes) {
+            *operand = arg.slot(*axis).ok();
+        }
+        (line, operands)
+    }
 }
 
 mod debug {
@@ -1686,7 +2463,7 @@ mod debug {
                 }
             }
             Right(cell) => {
-                if !depth.is_some_and(|d| d == 0) {
+                if depth.is_none_or(|d| d != 0) {
                     let new_depth = depth.map(|x| x - 1);
                     assert_normalized_helper(cell.head(), path, new_depth);
                     assert_normalized_helper(cell.tail(), path, new_depth);
@@ -1695,3 +2472,456 @@ mod debug {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jets::util::test::{assert_noun_eq, init_context};
+    use crate::noun::{tape, NO, YES};
+    use crate::persist::{pma_open_for_test, Keep, Persist};
+    use std::time::Duration;
+
+    /// Copy a noun into the PMA, where its formulas are decoded once
+    fn persist(context: &mut Context, mut noun: Noun) -> Noun {
+        unsafe {
+            let handle = noun.save_to_pma(&mut context.stack, Keep::Pma);
+            Noun::handle_from_u64(handle)
+        }
+    }
+
+    /// Send the pleas of `c` to a scratch file, to be read back with [slogged]
+    fn slog_to_file(c: &mut Context, name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("ares-{}-{}", name, std::process::id()));
+        c.newt = Newt::from_io(std::io::empty(), std::fs::File::create(&path).unwrap());
+        path
+    }
+
+    /// The priorities and tanks of the slogs among the pleas written to `path`, which is removed
+    fn slogged(stack: &mut NockStack, path: &std::path::Path) -> Vec<(u64, Noun)> {
+        let mut pleas = std::fs::File::open(path).unwrap();
+        let mut slogs = Vec::new();
+        while let Some((_, jammed)) = crate::newt::read_frame(stack, &mut pleas).unwrap() {
+            let plea = crate::serialization::cue(stack, jammed);
+            if plea.slot(2).unwrap().as_direct().unwrap().data() == tas!(b"slog") {
+                let pri = plea.slot(6).unwrap().as_direct().unwrap().data();
+                slogs.push((pri, plea.slot(7).unwrap()));
+            }
+        }
+        std::fs::remove_file(path).unwrap();
+        slogs
+    }
+
+    /// The text of a %leaf tank
+    fn leaf_text(tank: Noun) -> String {
+        assert_eq!(
+            tank.slot(2).unwrap().as_direct().unwrap().data(),
+            tas!(b"leaf")
+        );
+        let mut text = String::new();
+        let mut tape = tank.slot(3).unwrap();
+        while let Ok(cell) = tape.as_cell() {
+            text.push(cell.head().as_direct().unwrap().data() as u8 as char);
+            tape = cell.tail();
+        }
+        text
+    }
+
+    /// Print the stack trace with `tag` from a scry handler, run in an interpret call nested in the
+    /// one which runs the scry, and count the lines printed
+    fn nested_trace(tag: u64) -> usize {
+        let c = &mut init_context();
+        let path = slog_to_file(c, &format!("nested-trace-{}", tag));
+
+        //  [11 [%mean 1 %inner] 11 tag 1 0 0 42]: print the trace, then produce [~ ~ 42]
+        let found = T(&mut c.stack, &[D(1), D(0), D(0), D(42)]);
+        let print = T(&mut c.stack, &[D(11), D(tag), found]);
+        let inner = T(&mut c.stack, &[D(1), D(tas!(b"inner"))]);
+        let inner = T(&mut c.stack, &[D(tas!(b"mean")), inner]);
+        let arm = T(&mut c.stack, &[D(11), inner, print]);
+        let gate = T(&mut c.stack, &[arm, D(0), D(0)]);
+        c.scry_stack = T(&mut c.stack, &[gate, D(0)]);
+
+        //  [11 [%mean 1 %outer] 12 [1 0] 1 0]
+        let zero = T(&mut c.stack, &[D(1), D(0)]);
+        let scry = T(&mut c.stack, &[D(12), zero, zero]);
+        let outer = T(&mut c.stack, &[D(1), D(tas!(b"outer"))]);
+        let outer = T(&mut c.stack, &[D(tas!(b"mean")), outer]);
+        let f = T(&mut c.stack, &[D(11), outer, scry]);
+
+        let r = interpret(c, D(0), f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+        assert!(c.mean_slot.is_null());
+        assert_noun_eq(&mut c.stack, c.outer_means, D(0));
+
+        slogged(&mut c.stack, &path).len()
+    }
+
+    #[test]
+    fn nested_stack_traces() {
+        assert_eq!(nested_trace(tas!(b"nara")), 1);
+        assert_eq!(nested_trace(tas!(b"hela")), 2);
+    }
+
+    #[test]
+    fn long_hint_tags() {
+        let c = &mut init_context();
+        let tag = unsafe { IndirectAtom::new_raw_bytes_ref(&mut c.stack, b"long-hint-tag") };
+        let tag = tag.as_noun();
+        assert!(hint::is_tail(tag.as_atom().unwrap()));
+        assert!(!hint::is_tail(D(tas!(b"bout")).as_atom().unwrap()));
+        assert!(!hint::is_tail(D(tas!(b"memo")).as_atom().unwrap()));
+
+        //  [11 tag 4 0 1] and [11 [tag 1 0] 4 0 1]
+        let inc = T(&mut c.stack, &[D(4), D(0), D(1)]);
+        let f = T(&mut c.stack, &[D(11), tag, inc]);
+        let r = interpret(c, D(41), f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+        let hint = T(&mut c.stack, &[tag, D(1), D(0)]);
+        let f = T(&mut c.stack, &[D(11), hint, inc]);
+        let r = interpret(c, D(41), f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+    }
+
+    #[test]
+    fn bout_hint() {
+        let c = &mut init_context();
+        let path = slog_to_file(c, "bout-hint");
+
+        //  [11 [%bout 1 1 %leaf "inc"] 4 0 1]
+        let text = tape(&mut c.stack, "inc");
+        let clue = T(&mut c.stack, &[D(1), D(1), D(tas!(b"leaf")), text]);
+        let hint = T(&mut c.stack, &[D(tas!(b"bout")), clue]);
+        let inc = T(&mut c.stack, &[D(4), D(0), D(1)]);
+        let f = T(&mut c.stack, &[D(11), hint, inc]);
+        let r = interpret(c, D(41), f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+
+        let slogs = slogged(&mut c.stack, &path);
+        assert_eq!(slogs.len(), 1);
+        let (pri, tank) = slogs[0];
+        assert_eq!(pri, 1);
+        //  [%rose [": " "" ""] [%leaf "took ms/..."] [%leaf "inc"] ~]
+        assert_eq!(
+            tank.slot(2).unwrap().as_direct().unwrap().data(),
+            tas!(b"rose")
+        );
+        assert!(leaf_text(tank.slot(14).unwrap()).starts_with("took ms/"));
+        assert_eq!(leaf_text(tank.slot(30).unwrap()), "inc");
+    }
+
+    #[test]
+    fn meme_hint() {
+        let c = &mut init_context();
+        let path = slog_to_file(c, "meme-hint");
+
+        //  [11 %meme 4 0 1]
+        let inc = T(&mut c.stack, &[D(4), D(0), D(1)]);
+        let f = T(&mut c.stack, &[D(11), D(tas!(b"meme")), inc]);
+        let r = interpret(c, D(41), f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+
+        let slogs = slogged(&mut c.stack, &path);
+        assert_eq!(slogs.len(), 1);
+        let meme = leaf_text(slogs[0].1);
+        assert!(meme.starts_with("meme: "), "{}", meme);
+        assert!(meme.ends_with(" bytes free"), "{}", meme);
+    }
+
+    #[test]
+    fn mass_hint() {
+        let c = &mut init_context();
+        let path = slog_to_file(c, "mass-hint");
+
+        //  [11 %mass 4 0 3]
+        let inc = T(&mut c.stack, &[D(4), D(0), D(3)]);
+        let f = T(&mut c.stack, &[D(11), D(tas!(b"mass")), inc]);
+        let subject = T(&mut c.stack, &[D(0), D(41)]);
+        let r = interpret(c, subject, f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+
+        let lines: Vec<String> = slogged(&mut c.stack, &path)
+            .into_iter()
+            .map(|(_, tank)| leaf_text(tank))
+            .collect();
+        assert_eq!(lines[0], "subject: B/24");
+        assert_eq!(&lines[1..4], ["cold: B/0", "warm: B/0", "cache: B/0"]);
+        assert_eq!(lines[4], "total: B/24");
+        assert!(lines[5].starts_with("stack used: "), "{}", lines[5]);
+    }
+
+    #[test]
+    fn xray_hint() {
+        let c = &mut init_context();
+        let path = slog_to_file(c, "xray-hint");
+
+        //  [11 %xray 8 [1 0] 4 0 3]
+        let push = T(&mut c.stack, &[D(1), D(0)]);
+        let body = T(&mut c.stack, &[D(8), push, D(4), D(0), D(3)]);
+        let f = T(&mut c.stack, &[D(11), D(tas!(b"xray")), body]);
+        let r = interpret(c, D(41), f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+
+        let lines: Vec<String> = slogged(&mut c.stack, &path)
+            .into_iter()
+            .map(|(_, tank)| leaf_text(tank))
+            .collect();
+        assert_eq!(lines, ["8", "  1 0", "  4", "    0 /3"]);
+    }
+
+    #[test]
+    fn xray_operand_order() {
+        let c = &mut init_context();
+        let path = slog_to_file(c, "xray-operand-order");
+
+        //  [11 %xray 6 [1 0] [[1 1] 0 1] 11 [%foo 1 0] 0 1]
+        let no = T(&mut c.stack, &[D(1), D(0)]);
+        let one = T(&mut c.stack, &[D(1), D(1)]);
+        let axis = T(&mut c.stack, &[D(0), D(1)]);
+        let cons = T(&mut c.stack, &[one, axis]);
+        let hint = T(&mut c.stack, &[D(tas!(b"foo")), no]);
+        let hinted = T(&mut c.stack, &[D(11), hint, axis]);
+        let body = T(&mut c.stack, &[D(6), no, cons, hinted]);
+        let f = T(&mut c.stack, &[D(11), D(tas!(b"xray")), body]);
+        let r = interpret(c, D(41), f).unwrap();
+        let expected = T(&mut c.stack, &[D(1), D(41)]);
+        assert_noun_eq(&mut c.stack, r, expected);
+
+        let lines: Vec<String> = slogged(&mut c.stack, &path)
+            .into_iter()
+            .map(|(_, tank)| leaf_text(tank))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "6",
+                "  1 0",
+                "  cons",
+                "    1 1",
+                "    0 /1",
+                "  11 %foo",
+                "    1 0",
+                "    0 /1"
+            ]
+        );
+    }
+
+    #[test]
+    fn live_hint() {
+        let c = &mut init_context();
+
+        //  [11 [%live 1 flag %inc] 4 0 1]
+        let live = |c: &mut Context, flag: Noun| {
+            let clue = T(&mut c.stack, &[D(1), flag, D(tas!(b"inc"))]);
+            let hint = T(&mut c.stack, &[D(tas!(b"live")), clue]);
+            let inc = T(&mut c.stack, &[D(4), D(0), D(1)]);
+            let f = T(&mut c.stack, &[D(11), hint, inc]);
+            let r = interpret(c, D(41), f).unwrap();
+            assert_noun_eq(&mut c.stack, r, D(42));
+        };
+        live(c, YES);
+        live(c, YES);
+        live(c, NO);
+        assert_eq!(c.live.get(b"inc"), 2);
+        assert_eq!(c.live.get(b"dec"), 0);
+    }
+
+    #[test]
+    fn profile_samples() {
+        let c = &mut init_context();
+        c.profile = Some(Profile::new(None));
+
+        //  [2 [0 1] 1 4 0 1]
+        let inc = T(&mut c.stack, &[D(4), D(0), D(1)]);
+        let quote = T(&mut c.stack, &[D(1), inc]);
+        let one = T(&mut c.stack, &[D(0), D(1)]);
+        let f = T(&mut c.stack, &[D(2), one, quote]);
+        trace::request_sample();
+        let r = interpret(c, D(41), f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+        assert!(!trace::sample_requested());
+
+        // Within a call to /add/one, tail-called twice, and then in a jet from the interpreter
+        // nested in it
+        c.stack.frame_push(2);
+        unsafe {
+            *(c.stack.local_noun_pointer(0)) = D(0);
+            *(c.stack.local_noun_pointer(1) as *mut *const TraceStack) = std::ptr::null();
+            let add = T(&mut c.stack, &[D(tas!(b"add")), D(tas!(b"one")), D(0)]);
+            append_trace(&mut c.stack, add);
+            append_trace(&mut c.stack, add);
+            c.suspend_mean();
+            trace::request_sample();
+            let r = interpret(c, D(41), f).unwrap();
+            assert_noun_eq(&mut c.stack, r, D(42));
+            let dec = T(&mut c.stack, &[D(tas!(b"dec")), D(0)]);
+            trace::request_sample();
+            trace::sample(c, Some(dec));
+            c.stack.frame_pop();
+        }
+        c.mean_slot = std::ptr::null();
+        c.outer_trace = std::ptr::null();
+
+        let profile = c.profile.as_ref().unwrap();
+        assert_eq!(profile.nock_samples, 2);
+        assert_eq!(profile.jet_samples, 1);
+        assert_eq!(
+            profile.folded(),
+            "nock 1\nnock;/add/one 1\nnock;/add/one;/dec_[j] 1\n"
+        );
+    }
+
+    #[test]
+    fn jet_stats() {
+        let c = &mut init_context();
+        let mut stats = JetStats::new();
+        let add = T(&mut c.stack, &[D(tas!(b"add")), D(tas!(b"one")), D(0)]);
+        let dec = T(&mut c.stack, &[D(tas!(b"dec")), D(0)]);
+        stats.jet(&mut c.stack, add, &Ok(D(0)), Duration::from_micros(3));
+        stats.jet(
+            &mut c.stack,
+            add,
+            &Err(JetErr::Punt),
+            Duration::from_micros(4),
+        );
+        stats.jet(&mut c.stack, add, &Ok(D(0)), Duration::ZERO);
+        stats.unjetted(&mut c.stack, dec);
+
+        // The raw Nock of a call on the trace stack counts towards its time
+        c.stack.frame_push(2);
+        unsafe {
+            *(c.stack.local_noun_pointer(1) as *mut *const TraceStack) = std::ptr::null();
+            append_trace(&mut c.stack, dec);
+            let trace_stack = *(c.stack.local_noun_pointer(1) as *mut *const TraceStack);
+            stats.time_calls(&mut c.stack, trace_stack);
+            c.stack.frame_pop();
+        }
+
+        let add = stats.get("/add/one").unwrap();
+        assert_eq!((add.hits, add.punts, add.fails, add.unjetted), (2, 1, 0, 0));
+        assert_eq!(add.time, Duration::from_micros(7));
+        let dec = stats.get("/dec").unwrap();
+        assert_eq!((dec.hits, dec.unjetted), (0, 1));
+        assert!(stats.get("/sub/one").is_none());
+
+        let report = stats.report();
+        assert_eq!(report.len(), 2);
+        assert_eq!(
+            report[0],
+            "/add/one: 2 hits, 1 punts, 0 fails, 0 unjetted, 7us"
+        );
+        assert!(report[1].starts_with("/dec: 0 hits, 0 punts, 0 fails, 1 unjetted, "));
+    }
+
+    #[test]
+    fn jet_stats_recursion() {
+        // A call which recurses into its own path is timed once, not once for each frame
+        let c = &mut init_context();
+        let mut stats = JetStats::new();
+        let dec = T(&mut c.stack, &[D(tas!(b"dec")), D(0)]);
+        let pause = || std::thread::sleep(Duration::from_millis(20));
+        let start = Instant::now();
+        unsafe {
+            c.stack.frame_push(2);
+            *(c.stack.local_noun_pointer(1) as *mut *const TraceStack) = std::ptr::null();
+            stats.lap(&mut c.stack, std::ptr::null());
+            append_trace(&mut c.stack, dec);
+            pause();
+
+            let outer = *(c.stack.local_noun_pointer(1) as *const *const TraceStack);
+            c.stack.frame_push(2);
+            *(c.stack.local_noun_pointer(1) as *mut *const TraceStack) = outer;
+            stats.lap(&mut c.stack, outer);
+            append_trace(&mut c.stack, dec);
+            pause();
+            let inner = *(c.stack.local_noun_pointer(1) as *const *const TraceStack);
+            stats.time_calls(&mut c.stack, inner);
+            c.stack.frame_pop();
+
+            pause();
+            stats.time_calls(&mut c.stack, outer);
+            c.stack.frame_pop();
+        }
+        let time = stats.get("/dec").unwrap().time;
+        assert!(time >= Duration::from_millis(60));
+        assert!(time <= start.elapsed());
+    }
+
+    #[test]
+    fn formulas_decoded_once() {
+        let _pma = pma_open_for_test();
+        let c = &mut init_context();
+
+        //  [8 [1 0] 4 0 3]: push 0, then increment the original subject
+        let push = T(&mut c.stack, &[D(1), D(0)]);
+        let f = T(&mut c.stack, &[D(8), push, D(4), D(0), D(3)]);
+        let r = interpret(c, D(41), f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+        assert!(c.formulas.is_empty());
+
+        let f = persist(c, f);
+        let r = interpret(c, D(41), f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+        //  [8 ...], [1 0], [4 0 3], and [0 3]
+        assert_eq!(c.formulas.len(), 4);
+
+        let r = interpret(c, D(41), f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+        assert_eq!(c.formulas.len(), 4);
+
+        c.formulas.clear();
+        assert!(c.formulas.is_empty());
+    }
+
+    #[test]
+    fn decoded_formulas_out_of_tail_position() {
+        let _pma = pma_open_for_test();
+        let c = &mut init_context();
+
+        //  [7 [4 0 1] 0 1] may be a tail call on its own, but not as the head of a cell
+        let inc = T(&mut c.stack, &[D(4), D(0), D(1)]);
+        let seven = T(&mut c.stack, &[D(7), inc, D(0), D(1)]);
+        let seven = persist(c, seven);
+        let r = interpret(c, D(41), seven).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+
+        let one = T(&mut c.stack, &[D(0), D(1)]);
+        let f = T(&mut c.stack, &[seven, one]);
+        let r = interpret(c, D(41), f).unwrap();
+        let expected = T(&mut c.stack, &[D(42), D(41)]);
+        assert_noun_eq(&mut c.stack, r, expected);
+    }
+
+    #[test]
+    fn no_jets_found_survives_moves() {
+        let _pma = pma_open_for_test();
+        let c = &mut init_context();
+
+        //  [9 2 0 1] against [[4 0 3] 41]: an arm with no jets registered
+        let arm = T(&mut c.stack, &[D(4), D(0), D(3)]);
+        let arm = persist(c, arm);
+        let f = T(&mut c.stack, &[D(9), D(2), D(0), D(1)]);
+        let core = T(&mut c.stack, &[arm, D(41)]);
+        let r = interpret(c, core, f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+        let decoded = c.formulas.get(arm).unwrap();
+        assert_eq!(unsafe { (*decoded).found }, c.warm.generation());
+
+        //  Moving the warm state, as at the end of an event, leaves what was found valid
+        let epoch = c.warm.epoch();
+        unsafe {
+            c.stack.preserve(&mut c.cache);
+            c.stack.preserve(&mut c.cold);
+            c.stack.preserve(&mut c.warm);
+            c.stack.preserve(&mut c.hot);
+            c.stack.preserve(&mut c.code);
+            c.stack.flip_top_frame(0);
+        }
+        assert_ne!(c.warm.epoch(), epoch);
+        assert_eq!(unsafe { (*decoded).found }, c.warm.generation());
+
+        let f = T(&mut c.stack, &[D(9), D(2), D(0), D(1)]);
+        let core = T(&mut c.stack, &[arm, D(41)]);
+        let r = interpret(c, core, f).unwrap();
+        assert_noun_eq(&mut c.stack, r, D(42));
+    }
+}
@@ -16,7 +16,7 @@ pub mod sort;
 pub mod tree;
 
 use crate::flog;
-use crate::interpreter::{Context, Error, Mote};
+use crate::interpreter::{interpret, Context, Error, Mote};
 use crate::jets::bits::*;
 use crate::jets::cold::Cold;
 use crate::jets::form::*;
@@ -36,9 +36,12 @@ use crate::jets::sort::*;
 use crate::jets::tree::*;
 use crate::jets::warm::Warm;
 use crate::mem::{NockStack, Preserve};
+use crate::mug::met3_usize;
 use crate::newt::Newt;
-use crate::noun::{self, Noun, Slots, D};
+use crate::noun::{self, Noun, Slots, D, T};
+use crate::unifying_equality::unifying_equality;
 use ares_macros::tas;
+use assert_no_alloc::permit_alloc;
 
 crate::gdb!();
 
@@ -46,6 +49,23 @@ crate::gdb!();
 pub type Result = std::result::Result<Noun, JetErr>;
 pub type Jet = fn(&mut Context, Noun) -> Result;
 
+/// The budget cost of a jet call on `subject`: a step, and a step for each word of the sample if it
+/// is an atom, or of the atoms at the head and tail of the sample, which is what jets on large atoms
+/// take their time over
+pub fn cost(subject: Noun) -> u64 {
+    let words = |noun: Noun| {
+        noun.atom()
+            .map_or(0, |atom| (met3_usize(atom) as u64 + 7) >> 3)
+    };
+    match subject.slot(6) {
+        Ok(sample) => match sample.cell() {
+            Some(cell) => 1 + words(cell.head()) + words(cell.tail()),
+            None => 1 + words(sample),
+        },
+        Err(_) => 1,
+    }
+}
+
 /**
  * Only return a deterministic error if the Nock would have deterministically
  * crashed.
@@ -173,14 +193,161 @@ pub fn get_jet(context: &mut Context, jet_name: Noun) -> Option<Jet> {
     }
 }
 
-pub fn get_jet_test_mode(_jet_name: Noun) -> bool {
-    /*
-    match jet_name.as_direct().unwrap().data() {
-        tas!(b"cut") => true,
-        _ => false,
+/**
+ * Which jets are run in test mode: alongside the raw Nock of their arms, reporting any difference
+ * between the two.
+ *
+ * A jet is selected by a path which its own path ends with, written from the root and separated by
+ * `/`, such as `add` or `one/add`. A versioned path component such as `[%k 139]` is written `k139`.
+ */
+pub struct JetTest {
+    all: bool,
+    paths: Vec<Vec<Vec<u8>>>, // components innermost first, like jet paths
+    /// Number of differences found
+    pub mismatches: u64,
+}
+
+impl JetTest {
+    /// No jets are tested
+    pub fn off() -> Self {
+        JetTest {
+            all: false,
+            paths: Vec::new(),
+            mismatches: 0,
+        }
+    }
+
+    /// Parse a comma-separated list of jet paths, or `all`
+    pub fn parse(spec: &str) -> Self {
+        let mut test = JetTest::off();
+        for path in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+        {
+            if path == "all" {
+                test.all = true;
+            } else {
+                test.paths.push(
+                    path.split('/')
+                        .filter(|component| !component.is_empty())
+                        .rev()
+                        .map(|component| component.as_bytes().to_vec())
+                        .collect(),
+                );
+            }
+        }
+        test
+    }
+
+    /// Read the jets to test from `ARES_JET_TEST`, if it is set
+    pub fn from_env() -> Self {
+        match std::env::var("ARES_JET_TEST") {
+            Ok(spec) => JetTest::parse(&spec),
+            Err(_) => JetTest::off(),
+        }
+    }
+
+    pub fn is_off(&self) -> bool {
+        !self.all && self.paths.is_empty()
+    }
+
+    /// Whether the jet with this path is tested
+    pub fn matches(&self, path: Noun) -> bool {
+        if self.all {
+            return true;
+        }
+        'paths: for test_path in &self.paths {
+            let mut rest = path;
+            for component in test_path {
+                let Ok(cell) = rest.as_cell() else {
+                    continue 'paths;
+                };
+                if !component_matches(component, cell.head()) {
+                    continue 'paths;
+                }
+                rest = cell.tail();
+            }
+            return true;
+        }
+        false
     }
-    */
-    false
+}
+
+fn component_matches(component: &[u8], noun: Noun) -> bool {
+    fn trim(bytes: &[u8]) -> &[u8] {
+        let len = bytes.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+        &bytes[..len]
+    }
+
+    if let Ok(atom) = noun.as_atom() {
+        return trim(atom.as_bytes()) == component;
+    }
+    let Ok(cell) = noun.as_cell() else {
+        return false;
+    };
+    let (Ok(name), Some(version)) = (
+        cell.head().as_atom(),
+        cell.tail().atom().and_then(|a| a.as_u64().ok()),
+    ) else {
+        return false;
+    };
+    let name = trim(name.as_bytes());
+    component.starts_with(name)
+        && std::str::from_utf8(&component[name.len()..])
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            == Some(version)
+}
+
+/**
+ * Run a jet in test mode: run both it and the raw Nock of its arm, and if they differ, count the
+ * mismatch and slog the path, the sample, and both results. The result is that of the raw Nock.
+ */
+pub fn test_jet(
+    context: &mut Context,
+    jet: Jet,
+    path: Noun,
+    subject: Noun,
+    formula: Noun,
+) -> std::result::Result<Noun, Error> {
+    let jet_res = jet(context, subject);
+    if let Err(JetErr::Punt) = jet_res {
+        return interpret(context, subject, formula);
+    }
+    let nock_res = interpret(context, subject, formula);
+    let agree = match (jet_res, nock_res) {
+        (Ok(mut jet_noun), Ok(mut nock_noun)) => unsafe {
+            unifying_equality(&mut context.stack, &mut jet_noun, &mut nock_noun)
+        },
+        (Ok(_), Err(Error::Deterministic(_, _)))
+        | (Err(JetErr::Fail(Error::Deterministic(_, _))), Ok(_)) => false,
+        // Both crashed, or one was interrupted or blocked, which says nothing about the jet
+        _ => true,
+    };
+    if !agree {
+        context.jet_test.mismatches += 1;
+        let sample = subject.slot(6).unwrap_or(subject);
+        let stack = &mut context.stack;
+        let msg = permit_alloc(|| {
+            let jet_res = match jet_res {
+                Ok(noun) => format!("{}", noun),
+                Err(err) => format!("{:?}", err),
+            };
+            let nock_res = match nock_res {
+                Ok(noun) => format!("{}", noun),
+                Err(err) => format!("{:?}", err),
+            };
+            let msg = format!(
+                "jet mismatch in {}: sample {}, jet {}, nock {}",
+                path, sample, jet_res, nock_res
+            );
+            noun::tape(stack, &msg)
+        });
+        let leaf = T(stack, &[D(tas!(b"leaf")), msg]);
+        context.newt.slog(stack, 0, leaf);
+    }
+    nock_res
 }
 
 pub mod util {
@@ -307,9 +474,12 @@ pub mod util {
 
     pub mod test {
         use super::*;
+        use crate::bytecode::Code;
         use crate::hamt::Hamt;
+        use crate::interpreter::Formulas;
         use crate::mem::NockStack;
         use crate::noun::{Atom, Noun, D, T};
+        use crate::trace::LiveCounters;
         use crate::unifying_equality::unifying_equality;
         use assert_no_alloc::assert_no_alloc;
         use ibig::UBig;
@@ -320,6 +490,7 @@ pub mod util {
             let cold = Cold::new(&mut stack);
             let warm = Warm::new(&mut stack);
             let hot = Hot::init(&mut stack, URBIT_HOT_STATE);
+            let code = Code::new(&mut stack);
             let cache = Hamt::<Noun>::new(&mut stack);
 
             Context {
@@ -328,9 +499,20 @@ pub mod util {
                 cold,
                 warm,
                 hot,
+                code,
+                formulas: Formulas::new(),
                 cache,
                 scry_stack: D(0),
                 trace_info: None,
+                jet_test: JetTest::off(),
+                mean_slot: std::ptr::null(),
+                outer_means: D(0),
+                outer_trace: std::ptr::null(),
+                live: LiveCounters::new(),
+                profile: None,
+                jet_stats: None,
+                gas: None,
+                debugger: None,
             }
         }
 
@@ -464,3 +646,92 @@ pub mod util {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::inc;
+    use crate::jets::util::test::{assert_noun_eq, init_context};
+    use crate::jets::util::BAIL_EXIT;
+    use crate::noun::IndirectAtom;
+
+    fn add_path(stack: &mut NockStack) -> Noun {
+        let k139 = T(stack, &[D(tas!(b"k")), D(139)]);
+        T(stack, &[D(tas!(b"add")), D(tas!(b"one")), k139, D(0)])
+    }
+
+    fn jet_inc(context: &mut Context, subject: Noun) -> Result {
+        Ok(inc(&mut context.stack, subject.as_atom()?).as_noun())
+    }
+
+    fn jet_inc_twice(context: &mut Context, subject: Noun) -> Result {
+        let once = inc(&mut context.stack, subject.as_atom()?);
+        Ok(inc(&mut context.stack, once).as_noun())
+    }
+
+    fn jet_crash(_context: &mut Context, _subject: Noun) -> Result {
+        Err(BAIL_EXIT)
+    }
+
+    #[test]
+    fn jet_cost() {
+        let c = &mut init_context();
+        let big = unsafe { IndirectAtom::new_raw_bytes_ref(&mut c.stack, &[1; 20]) }.as_noun();
+
+        let subject = T(&mut c.stack, &[D(0), D(7), D(0)]);
+        assert_eq!(cost(subject), 2);
+        let subject = T(&mut c.stack, &[D(0), big, D(0)]);
+        assert_eq!(cost(subject), 4);
+        let sample = T(&mut c.stack, &[big, big]);
+        let subject = T(&mut c.stack, &[D(0), sample, D(0)]);
+        assert_eq!(cost(subject), 7);
+        // Only the atoms at the head and tail of the sample count
+        let sample = T(&mut c.stack, &[D(0), big, D(0)]);
+        let subject = T(&mut c.stack, &[D(0), sample, D(0)]);
+        assert_eq!(cost(subject), 1);
+        assert_eq!(cost(D(0)), 1);
+    }
+
+    #[test]
+    fn jet_test_paths() {
+        let c = &mut init_context();
+        let path = add_path(&mut c.stack);
+
+        assert!(JetTest::off().is_off());
+        assert!(!JetTest::off().matches(path));
+        assert!(JetTest::parse("all").matches(path));
+        assert!(JetTest::parse("add").matches(path));
+        assert!(JetTest::parse("dec, one/add").matches(path));
+        assert!(JetTest::parse("k139/one/add").matches(path));
+        assert!(!JetTest::parse("k140/one/add").matches(path));
+        assert!(!JetTest::parse("two/add").matches(path));
+        assert!(!JetTest::parse("dec").matches(path));
+        assert!(!JetTest::parse("z/k139/one/add").matches(path));
+        assert!(JetTest::parse("").is_off());
+    }
+
+    #[test]
+    fn jet_test_mismatches() {
+        let c = &mut init_context();
+        let path = add_path(&mut c.stack);
+        let inc = T(&mut c.stack, &[D(4), D(0), D(1)]);
+
+        let res = test_jet(c, jet_inc, path, D(41), inc).unwrap();
+        assert_noun_eq(&mut c.stack, res, D(42));
+        assert_eq!(c.jet_test.mismatches, 0);
+
+        // The raw Nock wins
+        let res = test_jet(c, jet_inc_twice, path, D(41), inc).unwrap();
+        assert_noun_eq(&mut c.stack, res, D(42));
+        assert_eq!(c.jet_test.mismatches, 1);
+
+        let res = test_jet(c, jet_crash, path, D(41), inc).unwrap();
+        assert_noun_eq(&mut c.stack, res, D(42));
+        assert_eq!(c.jet_test.mismatches, 2);
+
+        // Both crash
+        let cell = T(&mut c.stack, &[D(1), D(2)]);
+        assert!(test_jet(c, jet_crash, path, cell, inc).is_err());
+        assert_eq!(c.jet_test.mismatches, 2);
+    }
+}
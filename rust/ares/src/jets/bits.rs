@@ -344,7 +344,7 @@ pub mod util {
     }
 
     pub fn rip(stack: &mut NockStack, bloq: usize, step: usize, atom: Atom) -> Result {
-        let len = (met(bloq, atom) + step - 1) / step;
+        let len = met(bloq, atom).div_ceil(step);
         let mut list = D(0);
         for i in (0..len).rev() {
             let new_atom = unsafe {
@@ -2,7 +2,7 @@ use crate::hamt::Hamt;
 use crate::mem::{NockStack, Preserve};
 use crate::noun;
 use crate::noun::{Atom, DirectAtom, Noun, Slots, D, T};
-use crate::persist::{pma_contains, Persist};
+use crate::persist::{Keep, Persist};
 use crate::unifying_equality::unifying_equality;
 use std::mem::size_of;
 use std::ptr::copy_nonoverlapping;
@@ -35,7 +35,7 @@ struct BatteriesMem {
 }
 
 impl Persist for Batteries {
-    unsafe fn space_needed(&mut self, stack: &mut NockStack) -> usize {
+    unsafe fn space_needed(&mut self, stack: &mut NockStack, keep: Keep) -> usize {
         let mut bytes = 0;
         let mut batteries = *self;
 
@@ -43,24 +43,24 @@ impl Persist for Batteries {
             if batteries.0.is_null() {
                 break;
             }
-            if pma_contains(batteries.0, 1) {
+            if keep.keeps(batteries.0, 1) {
                 break;
             }
             bytes += size_of::<BatteriesMem>();
-            bytes += (*batteries.0).battery.space_needed(stack);
-            bytes += (*batteries.0).parent_axis.space_needed(stack);
+            bytes += (*batteries.0).battery.space_needed(stack, keep);
+            bytes += (*batteries.0).parent_axis.space_needed(stack, keep);
             batteries = (*batteries.0).parent_batteries;
         }
         bytes
     }
 
-    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, buffer: &mut *mut u8) {
+    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, keep: Keep, buffer: &mut *mut u8) {
         let mut dest = self;
         loop {
             if dest.0.is_null() {
                 break;
             }
-            if pma_contains(dest.0, 1) {
+            if keep.keeps(dest.0, 1) {
                 break;
             }
 
@@ -68,10 +68,12 @@ impl Persist for Batteries {
             copy_nonoverlapping(dest.0, batteries_mem_ptr, 1);
             *buffer = batteries_mem_ptr.add(1) as *mut u8;
 
-            (*batteries_mem_ptr).battery.copy_to_buffer(stack, buffer);
+            (*batteries_mem_ptr)
+                .battery
+                .copy_to_buffer(stack, keep, buffer);
             (*batteries_mem_ptr)
                 .parent_axis
-                .copy_to_buffer(stack, buffer);
+                .copy_to_buffer(stack, keep, buffer);
 
             dest.0 = batteries_mem_ptr;
             dest = &mut (*dest.0).parent_batteries;
@@ -200,32 +202,32 @@ struct BatteriesListMem {
 }
 
 impl Persist for BatteriesList {
-    unsafe fn space_needed(&mut self, stack: &mut NockStack) -> usize {
+    unsafe fn space_needed(&mut self, stack: &mut NockStack, keep: Keep) -> usize {
         let mut bytes = 0;
         let mut list = *self;
         loop {
             if list.0.is_null() {
                 break;
             }
-            if pma_contains(list.0, 1) {
+            if keep.keeps(list.0, 1) {
                 break;
             }
             bytes += size_of::<BatteriesListMem>();
-            bytes += (*list.0).batteries.space_needed(stack);
+            bytes += (*list.0).batteries.space_needed(stack, keep);
 
             list = (*list.0).next;
         }
         bytes
     }
 
-    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, buffer: &mut *mut u8) {
+    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, keep: Keep, buffer: &mut *mut u8) {
         let mut dest = self;
 
         loop {
             if dest.0.is_null() {
                 break;
             }
-            if pma_contains(dest.0, 1) {
+            if keep.keeps(dest.0, 1) {
                 break;
             }
 
@@ -234,7 +236,7 @@ impl Persist for BatteriesList {
             *buffer = list_mem_ptr.add(1) as *mut u8;
             dest.0 = list_mem_ptr;
 
-            (*dest.0).batteries.copy_to_buffer(stack, buffer);
+            (*dest.0).batteries.copy_to_buffer(stack, keep, buffer);
             dest = &mut (*dest.0).next;
         }
     }
@@ -321,7 +323,7 @@ struct NounListMem {
 }
 
 impl Persist for NounList {
-    unsafe fn space_needed(&mut self, stack: &mut NockStack) -> usize {
+    unsafe fn space_needed(&mut self, stack: &mut NockStack, keep: Keep) -> usize {
         let mut bytes: usize = 0;
         let mut list = *self;
 
@@ -329,26 +331,26 @@ impl Persist for NounList {
             if list.0.is_null() {
                 break;
             }
-            if pma_contains(list.0, 1) {
+            if keep.keeps(list.0, 1) {
                 break;
             }
 
             bytes += size_of::<NounListMem>();
-            bytes += (*list.0).element.space_needed(stack);
+            bytes += (*list.0).element.space_needed(stack, keep);
 
             list = (*list.0).next;
         }
         bytes
     }
 
-    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, buffer: &mut *mut u8) {
+    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, keep: Keep, buffer: &mut *mut u8) {
         let mut dest = self;
 
         loop {
             if dest.0.is_null() {
                 break;
             }
-            if pma_contains(dest.0, 1) {
+            if keep.keeps(dest.0, 1) {
                 break;
             }
 
@@ -357,7 +359,7 @@ impl Persist for NounList {
             *buffer = noun_list_mem_ptr.add(1) as *mut u8;
 
             dest.0 = noun_list_mem_ptr;
-            (*dest.0).element.copy_to_buffer(stack, buffer);
+            (*dest.0).element.copy_to_buffer(stack, keep, buffer);
 
             dest = &mut (*dest.0).next;
         }
@@ -450,20 +452,20 @@ struct ColdMem {
 }
 
 impl Persist for Cold {
-    unsafe fn space_needed(&mut self, stack: &mut NockStack) -> usize {
-        if pma_contains(self.0, 1) {
+    unsafe fn space_needed(&mut self, stack: &mut NockStack, keep: Keep) -> usize {
+        if keep.keeps(self.0, 1) {
             return 0;
         }
 
         let mut bytes = size_of::<ColdMem>();
-        bytes += (*self.0).battery_to_paths.space_needed(stack);
-        bytes += (*self.0).root_to_paths.space_needed(stack);
-        bytes += (*self.0).path_to_batteries.space_needed(stack);
+        bytes += (*self.0).battery_to_paths.space_needed(stack, keep);
+        bytes += (*self.0).root_to_paths.space_needed(stack, keep);
+        bytes += (*self.0).path_to_batteries.space_needed(stack, keep);
         bytes
     }
 
-    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, buffer: &mut *mut u8) {
-        if pma_contains(self.0, 1) {
+    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, keep: Keep, buffer: &mut *mut u8) {
+        if keep.keeps(self.0, 1) {
             return;
         }
 
@@ -473,9 +475,13 @@ impl Persist for Cold {
 
         self.0 = cold_mem_ptr;
 
-        (*self.0).battery_to_paths.copy_to_buffer(stack, buffer);
-        (*self.0).root_to_paths.copy_to_buffer(stack, buffer);
-        (*self.0).path_to_batteries.copy_to_buffer(stack, buffer);
+        (*self.0)
+            .battery_to_paths
+            .copy_to_buffer(stack, keep, buffer);
+        (*self.0).root_to_paths.copy_to_buffer(stack, keep, buffer);
+        (*self.0)
+            .path_to_batteries
+            .copy_to_buffer(stack, keep, buffer);
     }
 
     unsafe fn handle_to_u64(&self) -> u64 {
@@ -537,6 +543,205 @@ impl Cold {
         }
     }
 
+    /** Visit every noun slot in the cold state: keys, registered paths, and batteries
+     *
+     * # Safety
+     *
+     * The visitor may only replace a noun with an equal one, as when unifying.
+     */
+    pub unsafe fn for_each_noun<F: FnMut(*mut Noun)>(&mut self, mut f: F) {
+        (*(self.0)).battery_to_paths.for_each_pair(|pair| {
+            f(&mut (*pair).0);
+            (*pair).1.for_each(&mut f);
+        });
+        (*(self.0)).root_to_paths.for_each_pair(|pair| {
+            f(&mut (*pair).0);
+            (*pair).1.for_each(&mut f);
+        });
+        (*(self.0)).path_to_batteries.for_each_pair(|pair| {
+            f(&mut (*pair).0);
+            for batteries in (*pair).1 {
+                for (battery, _parent_axis) in batteries {
+                    f(battery);
+                }
+            }
+        });
+    }
+
+    /** Visit every noun slot in the cold state, as [Cold::for_each_noun] does, but first pass each
+     * block of cold state memory to `valid` as a pointer and a length in bytes. Blocks it rejects
+     * are not read. Parent axes are also visited, as copies.
+     *
+     * # Safety
+     *
+     * `valid` must only accept readable memory. The visitor has the same restrictions as for
+     * [Cold::for_each_noun].
+     */
+    pub unsafe fn for_each_noun_checked<V, F>(&mut self, valid: &V, mut f: F)
+    where
+        V: Fn(*const u8, usize) -> bool,
+        F: FnMut(*mut Noun),
+    {
+        if !valid(self.0 as *const u8, size_of::<ColdMem>()) {
+            return;
+        }
+        let noun_list = |mut list: NounList, f: &mut F| {
+            while !list.0.is_null() && valid(list.0 as *const u8, size_of::<NounListMem>()) {
+                f(&mut (*list.0).element);
+                list = (*list.0).next;
+            }
+        };
+        (*(self.0))
+            .battery_to_paths
+            .for_each_pair_checked(valid, |pair| {
+                f(&mut (*pair).0);
+                noun_list((*pair).1, &mut f);
+            });
+        (*(self.0))
+            .root_to_paths
+            .for_each_pair_checked(valid, |pair| {
+                f(&mut (*pair).0);
+                noun_list((*pair).1, &mut f);
+            });
+        (*(self.0))
+            .path_to_batteries
+            .for_each_pair_checked(valid, |pair| {
+                f(&mut (*pair).0);
+                let mut list = (*pair).1;
+                while !list.0.is_null() && valid(list.0 as *const u8, size_of::<BatteriesListMem>())
+                {
+                    let mut batteries = (*list.0).batteries;
+                    while !batteries.0.is_null()
+                        && valid(batteries.0 as *const u8, size_of::<BatteriesMem>())
+                    {
+                        f(&mut (*batteries.0).battery);
+                        let mut parent_axis = (*batteries.0).parent_axis.as_noun();
+                        f(&mut parent_axis);
+                        batteries = (*batteries.0).parent_batteries;
+                    }
+                    list = (*list.0).next;
+                }
+            });
+    }
+
+    /** Serialize the cold state as a noun, independent of its memory layout
+     *
+     * The noun is `[battery-to-paths root-to-paths path-to-batteries]`, each a list of key-value
+     * pairs. Paths lists are lists of nouns, and batteries lists are lists of
+     * `(list [battery parent-axis])`, from the core outward to its root.
+     */
+    pub fn to_noun(&mut self, stack: &mut NockStack) -> Noun {
+        unsafe {
+            let mut battery_to_paths = D(0);
+            (*(self.0)).battery_to_paths.for_each_pair(|pair| {
+                let paths = noun_list_to_noun(stack, (*pair).1);
+                let kv = T(stack, &[(*pair).0, paths]);
+                battery_to_paths = T(stack, &[kv, battery_to_paths]);
+            });
+
+            let mut root_to_paths = D(0);
+            (*(self.0)).root_to_paths.for_each_pair(|pair| {
+                let paths = noun_list_to_noun(stack, (*pair).1);
+                let kv = T(stack, &[(*pair).0, paths]);
+                root_to_paths = T(stack, &[kv, root_to_paths]);
+            });
+
+            let mut path_to_batteries = D(0);
+            (*(self.0)).path_to_batteries.for_each_pair(|pair| {
+                let mut lists = Vec::new();
+                for batteries in (*pair).1 {
+                    let mut chain = Vec::new();
+                    for (battery, parent_axis) in batteries {
+                        chain.push(T(stack, &[*battery, parent_axis.as_noun()]));
+                    }
+                    lists.push(vec_to_list(stack, &chain));
+                }
+                let batteries_lists = vec_to_list(stack, &lists);
+                let kv = T(stack, &[(*pair).0, batteries_lists]);
+                path_to_batteries = T(stack, &[kv, path_to_batteries]);
+            });
+
+            T(stack, &[battery_to_paths, root_to_paths, path_to_batteries])
+        }
+    }
+
+    /// Rebuild a cold state serialized by [Cold::to_noun]
+    pub fn from_noun(stack: &mut NockStack, noun: Noun) -> std::result::Result<Cold, Error> {
+        let mut battery_to_paths = Hamt::new(stack);
+        let mut list = noun.slot(2)?;
+        while let Ok(cell) = list.as_cell() {
+            let mut battery = cell.head().slot(2)?;
+            let paths = noun_list_from_noun(stack, cell.head().slot(3)?);
+            battery_to_paths = battery_to_paths.insert(stack, &mut battery, paths);
+            list = cell.tail();
+        }
+
+        let mut root_to_paths = Hamt::new(stack);
+        let mut list = noun.slot(6)?;
+        while let Ok(cell) = list.as_cell() {
+            let mut root = cell.head().slot(2)?;
+            let paths = noun_list_from_noun(stack, cell.head().slot(3)?);
+            root_to_paths = root_to_paths.insert(stack, &mut root, paths);
+            list = cell.tail();
+        }
+
+        let mut path_to_batteries = Hamt::new(stack);
+        let mut list = noun.slot(7)?;
+        while let Ok(cell) = list.as_cell() {
+            let mut path = cell.head().slot(2)?;
+            let mut lists = Vec::new();
+            let mut batteries_lists = cell.head().slot(3)?;
+            while let Ok(batteries_cell) = batteries_lists.as_cell() {
+                let mut chain = Vec::new();
+                let mut batteries = batteries_cell.head();
+                while let Ok(battery_cell) = batteries.as_cell() {
+                    let battery = battery_cell.head().slot(2)?;
+                    let parent_axis = battery_cell.head().slot(3)?.as_atom()?;
+                    chain.push((battery, parent_axis));
+                    batteries = battery_cell.tail();
+                }
+                lists.push(chain);
+                batteries_lists = batteries_cell.tail();
+            }
+
+            let mut batteries_list = BATTERIES_LIST_NIL;
+            for chain in lists.into_iter().rev() {
+                let mut parent_batteries = NO_BATTERIES;
+                for (battery, parent_axis) in chain.into_iter().rev() {
+                    unsafe {
+                        let batteries_mem_ptr: *mut BatteriesMem = stack.struct_alloc(1);
+                        *batteries_mem_ptr = BatteriesMem {
+                            battery,
+                            parent_axis,
+                            parent_batteries,
+                        };
+                        parent_batteries = Batteries(batteries_mem_ptr);
+                    }
+                }
+                unsafe {
+                    let batteries_list_mem_ptr: *mut BatteriesListMem = stack.struct_alloc(1);
+                    *batteries_list_mem_ptr = BatteriesListMem {
+                        batteries: parent_batteries,
+                        next: batteries_list,
+                    };
+                    batteries_list = BatteriesList(batteries_list_mem_ptr);
+                }
+            }
+            path_to_batteries = path_to_batteries.insert(stack, &mut path, batteries_list);
+            list = cell.tail();
+        }
+
+        unsafe {
+            let cold_mem_ptr: *mut ColdMem = stack.struct_alloc(1);
+            *cold_mem_ptr = ColdMem {
+                battery_to_paths,
+                root_to_paths,
+                path_to_batteries,
+            };
+            Ok(Cold(cold_mem_ptr))
+        }
+    }
+
     /** Try to match a core directly to the cold state, print the resulting path if found
      */
     pub fn matches(&mut self, stack: &mut NockStack, core: &mut Noun) -> Option<Noun> {
@@ -767,3 +972,72 @@ impl Cold {
         }
     }
 }
+
+fn noun_list_to_noun(stack: &mut NockStack, list: NounList) -> Noun {
+    let elements: Vec<Noun> = list.map(|element| unsafe { *element }).collect();
+    vec_to_list(stack, &elements)
+}
+
+fn noun_list_from_noun(stack: &mut NockStack, mut noun: Noun) -> NounList {
+    let mut elements = Vec::new();
+    while let Ok(cell) = noun.as_cell() {
+        elements.push(cell.head());
+        noun = cell.tail();
+    }
+
+    let mut list = NOUN_LIST_NIL;
+    for element in elements.into_iter().rev() {
+        unsafe {
+            let noun_list_mem_ptr: *mut NounListMem = stack.struct_alloc(1);
+            *noun_list_mem_ptr = NounListMem {
+                element,
+                next: list,
+            };
+            list = NounList(noun_list_mem_ptr);
+        }
+    }
+    list
+}
+
+fn vec_to_list(stack: &mut NockStack, elements: &[Noun]) -> Noun {
+    elements
+        .iter()
+        .rev()
+        .fold(D(0), |list, element| T(stack, &[*element, list]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ares_macros::tas;
+
+    #[test]
+    fn noun_round_trip() {
+        let mut stack = NockStack::new(8 << 10 << 10, 0);
+        let mut cold = Cold::new(&mut stack);
+
+        let root = T(&mut stack, &[D(1), D(2)]);
+        let root_axis = D(0).as_atom().unwrap();
+        assert!(matches!(
+            cold.register(&mut stack, root, root_axis, D(tas!(b"root"))),
+            Ok(true)
+        ));
+        let battery = T(&mut stack, &[D(0), D(6)]);
+        let mut core = T(&mut stack, &[battery, D(0), root]);
+        let parent_axis = D(7).as_atom().unwrap();
+        assert!(matches!(
+            cold.register(&mut stack, core, parent_axis, D(tas!(b"core"))),
+            Ok(true)
+        ));
+
+        let mut noun = cold.to_noun(&mut stack);
+        let mut copy = Cold::from_noun(&mut stack, noun).ok().unwrap();
+        let mut copy_noun = copy.to_noun(&mut stack);
+        assert!(unsafe { unifying_equality(&mut stack, &mut noun, &mut copy_noun) });
+
+        let mut path = copy.matches(&mut stack, &mut core).unwrap();
+        let mut expected = T(&mut stack, &[D(tas!(b"core")), D(tas!(b"root")), D(0)]);
+        assert!(unsafe { unifying_equality(&mut stack, &mut path, &mut expected) });
+        assert!(copy.find(&mut stack, &mut path).next().is_some());
+    }
+}
@@ -202,6 +202,27 @@ pub mod util {
         }
     }
 
+    /// +mink with a budget of `gas` steps, which also count against any budget of the caller.
+    /// Produces the $tone and the steps left over. A computation which runs out of steps fails
+    /// with [Mote::Gas], which, being nondeterministic, is not caught as a %2 $tone.
+    pub fn mink_gas(
+        context: &mut Context,
+        subject: Noun,
+        formula: Noun,
+        scry: Noun,
+        gas: u64,
+    ) -> Result<(Noun, u64), Error> {
+        let outer = context.gas;
+        let budget = outer.map_or(gas, |outer| outer.min(gas));
+
+        context.gas = Some(budget);
+        let tone = mink(context, subject, formula, scry);
+        let left = context.gas.unwrap_or(0);
+        context.gas = outer.map(|outer| outer - (budget - left));
+
+        Ok((tone?, left))
+    }
+
     /** Consume $tone, produce $toon
      */
     //  XX: should write a jet_mook wrapper for this function
@@ -371,7 +392,9 @@ pub mod util {
                 list = cell.tail();
             }
 
-            *dest = D(0);
+            if !flop {
+                *dest = D(0);
+            }
             let toon = Cell::new(&mut context.stack, D(2), res);
             Ok(toon)
         }
@@ -406,10 +429,11 @@ pub mod util {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::jets::util::test::{assert_jet, init_context};
+    use crate::interpreter::{Context, Error, Mote};
+    use crate::jets::util::test::{assert_jet, assert_noun_eq, init_context};
     use crate::mem::NockStack;
     use crate::noun::{D, T};
-    use crate::serf::TERMINATOR;
+    use crate::serf::{DEADLINE_PASSED, TERMINATOR};
     use std::sync::Arc;
 
     #[test]
@@ -423,6 +447,47 @@ mod tests {
         // since we're already using single-threaded test mode to avoid race conditions
         // (because Rust doesn't support test order dependencies either).
         let _ = Arc::clone(&TERMINATOR);
+        let _ = Arc::clone(&DEADLINE_PASSED);
+    }
+
+    /// [mink_gas] on `[0 [4 4 0 1]]`, which evaluates three formulas
+    fn mink_inc_inc(context: &mut Context, gas: u64) -> std::result::Result<(Noun, u64), Error> {
+        let stack = &mut context.stack;
+        let one = T(stack, &[D(0), D(1)]);
+        let inc = T(stack, &[D(4), one]);
+        let form = T(stack, &[D(4), inc]);
+        util::mink_gas(context, D(0), form, D(0), gas)
+    }
+
+    #[test]
+    fn test_mink_gas() {
+        let context = &mut init_context();
+
+        let (tone, left) = mink_inc_inc(context, 10).unwrap();
+        let rest = T(&mut context.stack, &[D(0), D(2)]);
+        assert_noun_eq(&mut context.stack, tone, rest);
+        assert_eq!(left, 7);
+        assert!(context.gas.is_none());
+
+        let (_, left) = mink_inc_inc(context, 3).unwrap();
+        assert_eq!(left, 0);
+
+        assert!(matches!(
+            mink_inc_inc(context, 2),
+            Err(Error::NonDeterministic(Mote::Gas, _))
+        ));
+        assert!(context.gas.is_none());
+
+        // The steps of the inner computation count against the outer budget, which bounds it
+        context.gas = Some(5);
+        let (_, left) = mink_inc_inc(context, 10).unwrap();
+        assert_eq!(left, 2);
+        assert_eq!(context.gas, Some(2));
+        assert!(matches!(
+            mink_inc_inc(context, 10),
+            Err(Error::NonDeterministic(Mote::Gas, _))
+        ));
+        assert_eq!(context.gas, Some(0));
     }
 
     #[test]
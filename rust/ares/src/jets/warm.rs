@@ -5,17 +5,34 @@ use crate::jets::Jet;
 use crate::mem::{NockStack, Preserve};
 use crate::noun::{Noun, Slots};
 use std::ptr::{copy_nonoverlapping, null_mut};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 /// key = formula
 #[derive(Copy, Clone)]
-pub struct Warm(Hamt<WarmEntry>);
+pub struct Warm {
+    table: Hamt<WarmEntry>,
+    /// Changes whenever the table is changed, so that finding no jets for a formula can be cached
+    generation: u64,
+    /// Changes whenever the table is changed or moved, so that [Jets] found in it can be cached
+    epoch: u64,
+}
+
+static EPOCH: AtomicU64 = AtomicU64::new(1);
+
+fn next_epoch() -> u64 {
+    EPOCH.fetch_add(1, Ordering::Relaxed)
+}
 
 impl Preserve for Warm {
     unsafe fn assert_in_stack(&self, stack: &NockStack) {
-        self.0.assert_in_stack(stack);
+        self.table.assert_in_stack(stack);
     }
     unsafe fn preserve(&mut self, stack: &mut NockStack) {
-        self.0.preserve(stack);
+        let table = self.table;
+        self.table.preserve(stack);
+        if !self.table.ptr_eq(&table) {
+            self.epoch = next_epoch();
+        }
     }
 }
 
@@ -84,10 +101,50 @@ impl Iterator for WarmEntry {
     }
 }
 
+/// The jets registered for a formula, as found by [Warm::find_jets]. These point into the warm
+/// state, and are only valid while its [Warm::epoch] is unchanged, except that finding none stays
+/// valid while its [Warm::generation] is unchanged.
+#[derive(Copy, Clone)]
+pub struct Jets(WarmEntry);
+
+impl Jets {
+    pub const NONE: Jets = Jets(WARM_ENTRY_NIL);
+
+    /// Whether no jets are registered for the formula
+    pub fn is_none(self) -> bool {
+        self.0 .0.is_null()
+    }
+
+    /// Find the jet, if any, whose batteries match the subject
+    pub fn find_jet(self, stack: &mut NockStack, s: Noun) -> Option<(Jet, Noun)> {
+        for (path, batteries, jet) in self.0 {
+            if batteries.matches(stack, s) {
+                return Some((jet, path));
+            }
+        }
+        None
+    }
+}
+
 impl Warm {
     #[allow(clippy::new_without_default)]
     pub fn new(stack: &mut NockStack) -> Self {
-        Warm(Hamt::new(stack))
+        let epoch = next_epoch();
+        Warm {
+            table: Hamt::new(stack),
+            generation: epoch,
+            epoch,
+        }
+    }
+
+    /// Identifies the current contents of the warm state, wherever it is
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Identifies the current contents and location of the warm state
+    pub fn epoch(&self) -> u64 {
+        self.epoch
     }
 
     fn insert(
@@ -98,7 +155,7 @@ impl Warm {
         batteries: Batteries,
         jet: Jet,
     ) {
-        let current_warm_entry = self.0.lookup(stack, formula).unwrap_or(WARM_ENTRY_NIL);
+        let current_warm_entry = self.table.lookup(stack, formula).unwrap_or(WARM_ENTRY_NIL);
         unsafe {
             let warm_entry_mem_ptr: *mut WarmEntryMem = stack.struct_alloc(1);
             *warm_entry_mem_ptr = WarmEntryMem {
@@ -107,7 +164,11 @@ impl Warm {
                 path,
                 next: current_warm_entry,
             };
-            self.0 = self.0.insert(stack, formula, WarmEntry(warm_entry_mem_ptr));
+            self.table = self
+                .table
+                .insert(stack, formula, WarmEntry(warm_entry_mem_ptr));
+            self.epoch = next_epoch();
+            self.generation = self.epoch;
         }
     }
 
@@ -132,6 +193,16 @@ impl Warm {
         warm
     }
 
+    /// Whether any jet is registered for this formula, whatever the subject
+    pub fn is_jetted(&self, stack: &mut NockStack, formula: &mut Noun) -> bool {
+        self.table.lookup(stack, formula).is_some()
+    }
+
+    /// Find the jets registered for this formula, to be matched against subjects later
+    pub fn find_jets(&self, stack: &mut NockStack, formula: &mut Noun) -> Jets {
+        Jets(self.table.lookup(stack, formula).unwrap_or(WARM_ENTRY_NIL))
+    }
+
     /// Walk through the linked list of WarmEntry objects and do a partial check
     /// against the subject using Batteries (walk to root of parent batteries).
     /// If there's a match, then we've found a valid jet.
@@ -141,12 +212,25 @@ impl Warm {
         s: &mut Noun,
         f: &mut Noun,
     ) -> Option<(Jet, Noun)> {
-        let warm_it = self.0.lookup(stack, f)?;
-        for (path, batteries, jet) in warm_it {
-            if batteries.matches(stack, *s) {
-                return Some((jet, path));
+        self.find_jets(stack, f).find_jet(stack, *s)
+    }
+
+    /** Visit every noun in the warm state: each formula, and the path and batteries of each jet
+     * registered for it
+     *
+     * # Safety
+     *
+     * The warm state must not be changed while it is visited.
+     */
+    pub unsafe fn for_each_noun<F: FnMut(Noun)>(&self, mut f: F) {
+        self.table.for_each_pair(|pair| {
+            f((*pair).0);
+            for (path, batteries, _jet) in (*pair).1 {
+                f(path);
+                for (battery, _parent_axis) in batteries {
+                    f(*battery);
+                }
             }
-        }
-        None
+        });
     }
 }
@@ -3,18 +3,21 @@ extern crate num_derive;
 extern crate lazy_static;
 #[macro_use]
 extern crate static_assertions;
+pub mod bytecode;
+pub mod cli;
+pub mod debugger;
 pub mod flog;
 pub mod guard;
 pub mod hamt;
 pub mod interpreter;
 pub mod jets;
+pub mod mass;
 pub mod mem;
 pub mod mug;
 pub mod newt;
 pub mod noun;
-pub mod serf;
-//pub mod bytecode;
 pub mod persist;
+pub mod serf;
 pub mod serialization;
 pub mod trace;
 pub mod unifying_equality;
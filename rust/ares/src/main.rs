@@ -1,3 +1,5 @@
+use ares::cli;
+use ares::cli::pier::{boot, check, conform, export, import, replay, rollback, snapshots};
 use ares::jets::hot::URBIT_HOT_STATE;
 use ares::serf::serf;
 use std::env;
@@ -16,6 +18,9 @@ fn main() -> io::Result<()> {
     let filename = env::args().nth(1).expect("Must provide input filename");
 
     if filename == "see gdb! definition in lib.rs about this" {
+        ares::cli::use_gdb();
+        ares::cli::pier::use_gdb();
+        ares::debugger::use_gdb();
         ares::interpreter::use_gdb();
         ares::jets::use_gdb();
         ares::jets::bits::use_gdb();
@@ -23,6 +28,7 @@ fn main() -> io::Result<()> {
         ares::jets::math::use_gdb();
         ares::jets::nock::use_gdb();
         ares::jets::tree::use_gdb();
+        ares::mass::use_gdb();
         ares::mem::use_gdb();
         ares::mug::use_gdb();
         ares::newt::use_gdb();
@@ -31,9 +37,19 @@ fn main() -> io::Result<()> {
         ares::serialization::use_gdb();
     }
 
-    if filename == "serf" {
-        return serf(URBIT_HOT_STATE);
+    match filename.as_str() {
+        "serf" => serf(URBIT_HOT_STATE),
+        "boot" => boot(URBIT_HOT_STATE),
+        "replay" => replay(URBIT_HOT_STATE),
+        "conform" => conform(URBIT_HOT_STATE),
+        "snapshots" => snapshots(),
+        "rollback" => rollback(),
+        "check" => check(),
+        "export" => export(URBIT_HOT_STATE),
+        "import" => import(URBIT_HOT_STATE),
+        "nock" => cli::nock(URBIT_HOT_STATE),
+        "cue" => cli::cue_text(),
+        "jam" => cli::jam_text(),
+        _ => panic!("Usage: ares (serf | boot <pill> [pier] | replay <pill> <events> <pier> | conform <transcript> <pier> | conform <writs> <pleas> <pier> | snapshots <pier> | rollback <pier> <event> | check <pier> | export <pier> [file.jam] | import <file.jam> <pier> | nock <subject.jam> <formula.jam> | cue [file.jam] | jam [file.txt])"),
     }
-
-    panic!("Ares can only run as a serf!");
 }
@@ -0,0 +1,422 @@
+/** Mass: memory reports, like those of |mass
+ *
+ * A report counts the bytes of the nouns the serf holds, by part: arvo, broken down as it accounts
+ * for itself in its `%whey` scry, then the cold and warm jet states and the memo cache. Each
+ * allocation is counted once, against the first part to reach it, so the parts add up to the total.
+ * Alongside the nouns are the use of the NockStack and of the PMA file.
+ *
+ * Unlike [Noun::mass], nouns are not marked as they are counted, which would write to the PMA.
+ * Instead each allocation is recorded in a bitmap of the parts of the NockStack in use and of the
+ * PMA allocations, a bit to a word, so the bitmap is proportional to the memory in use rather than
+ * to the size of the loom.
+ */
+use crate::interpreter::Context;
+use crate::mem::{word_size_of, NockStack, PreserveStats};
+use crate::mug::met3_usize;
+use crate::newt::Newt;
+use crate::noun::{tape, Atom, CellMemory, Noun, Slots, D, T};
+use crate::persist::{
+    pma_allocations, pma_hash_cons_stats, pma_is_open, pma_usage, HashConsStats, PmaUsage,
+};
+use ares_macros::tas;
+use ares_pma::BT_PAGESIZE;
+use either::Either::{Left, Right};
+use std::collections::HashSet;
+
+crate::gdb!();
+
+/// A span of memory in which nouns may be allocated, with a bit for each word of it
+struct Region {
+    lo: *const u64,
+    hi: *const u64,
+    seen: Vec<u64>,
+}
+
+/// Counts the bytes of nouns, each allocation only the first time it is reached
+pub struct Mass {
+    /// The parts of the NockStack in use and the PMA allocations, in address order
+    regions: Vec<Region>,
+    /// Allocations found outside of every region
+    others: HashSet<*const u64>,
+    /// Nouns reached but not yet counted
+    work: Vec<Noun>,
+}
+
+impl Mass {
+    pub fn new(stack: &NockStack) -> Self {
+        let mut spans: Vec<_> = stack
+            .used_spans()
+            .iter()
+            .copied()
+            .filter(|(lo, hi)| lo < hi)
+            .collect();
+        if pma_is_open() {
+            for (lo, hi) in pma_allocations() {
+                spans.push((lo as *const u64, hi as *const u64));
+            }
+        }
+        spans.sort_by_key(|(lo, _)| *lo);
+        let regions = spans
+            .into_iter()
+            .map(|(lo, hi)| Region {
+                lo,
+                hi,
+                seen: vec![0; ((hi as usize - lo as usize) >> 3).div_ceil(64)],
+            })
+            .collect();
+        Mass {
+            regions,
+            others: HashSet::new(),
+            work: Vec::new(),
+        }
+    }
+
+    /// Record an allocation as counted, returning whether it already was
+    fn seen(&mut self, ptr: *const u64) -> bool {
+        let idx = self.regions.partition_point(|region| region.lo <= ptr);
+        if idx > 0 && ptr < self.regions[idx - 1].hi {
+            let region = &mut self.regions[idx - 1];
+            let word = (ptr as usize - region.lo as usize) >> 3;
+            let bit = 1 << (word & 63);
+            let seen = region.seen[word >> 6] & bit != 0;
+            region.seen[word >> 6] |= bit;
+            seen
+        } else {
+            !self.others.insert(ptr)
+        }
+    }
+
+    /// The bytes of a noun not already counted, counting them as [Noun::mass] does
+    pub fn noun(&mut self, noun: Noun) -> usize {
+        let mut words = 0;
+        self.work.push(noun);
+        while let Some(noun) = self.work.pop() {
+            let Ok(allocated) = noun.as_allocated() else {
+                continue;
+            };
+            if self.seen(unsafe { allocated.to_raw_pointer() }) {
+                continue;
+            }
+            match allocated.as_either() {
+                Left(indirect) => words += indirect.size() + 2,
+                Right(cell) => {
+                    words += word_size_of::<CellMemory>();
+                    self.work.push(cell.tail());
+                    self.work.push(cell.head());
+                }
+            }
+        }
+        words << 3
+    }
+
+    /// A part holding a noun, less whatever is already counted
+    pub fn part(&mut self, name: &str, noun: Noun) -> Part {
+        let bytes = self.noun(noun);
+        Part::new(name, bytes)
+    }
+
+    /** Arvo, broken down by the `(list mass)` produced by its `%whey` scry, if there is one
+     *
+     * A `mass` is `(pair cord (each * (list mass)))`. Whatever of arvo the list does not reach is
+     * counted as `other`. Without the list, arvo is broken down into its battery, sample and
+     * context.
+     */
+    pub fn arvo(&mut self, arvo: Noun, whey: Option<Noun>) -> Part {
+        let mut parts = whey.and_then(|list| self.masses(list)).unwrap_or_else(|| {
+            [("battery", 2), ("sample", 6), ("context", 7)]
+                .iter()
+                .filter_map(|(name, axis)| Some(self.part(name, arvo.slot(*axis).ok()?)))
+                .collect()
+        });
+        parts.push(self.part("other", arvo));
+        Part::with_parts("arvo", parts)
+    }
+
+    fn masses(&mut self, mut list: Noun) -> Option<Vec<Part>> {
+        let mut parts = Vec::new();
+        while let Ok(cell) = list.as_cell() {
+            let mass = cell.head().as_cell().ok()?;
+            let name = cord(mass.head().as_atom().ok()?);
+            let each = mass.tail().as_cell().ok()?;
+            match each.head().as_direct().ok()?.data() {
+                0 => parts.push(self.part(&name, each.tail())),
+                1 => parts.push(Part::with_parts(&name, self.masses(each.tail())?)),
+                _ => return None,
+            }
+            list = cell.tail();
+        }
+        Some(parts)
+    }
+}
+
+fn cord(atom: Atom) -> String {
+    String::from_utf8_lossy(&atom.as_bytes()[..met3_usize(atom)]).into_owned()
+}
+
+/// A named count of bytes, which may be broken down further
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Part {
+    pub name: String,
+    pub bytes: usize,
+    pub parts: Vec<Part>,
+}
+
+impl Part {
+    pub fn new(name: &str, bytes: usize) -> Self {
+        Part {
+            name: name.to_string(),
+            bytes,
+            parts: Vec::new(),
+        }
+    }
+
+    /// A part made up of others, and as large as them together
+    pub fn with_parts(name: &str, parts: Vec<Part>) -> Self {
+        Part {
+            name: name.to_string(),
+            bytes: parts.iter().map(|part| part.bytes).sum(),
+            parts,
+        }
+    }
+
+    /// Find a part by the path of names to it, from this one
+    pub fn find(&self, path: &[&str]) -> Option<&Part> {
+        match path.split_first() {
+            None => Some(self),
+            Some((name, rest)) => self
+                .parts
+                .iter()
+                .find(|part| part.name == *name)?
+                .find(rest),
+        }
+    }
+
+    fn lines(&self, depth: usize, lines: &mut Vec<String>) {
+        lines.push(format!(
+            "{:indent$}{}: {}",
+            "",
+            self.name,
+            memory(self.bytes),
+            indent = depth * 2
+        ));
+        for part in &self.parts {
+            part.lines(depth + 1, lines);
+        }
+    }
+}
+
+/// A memory report, as produced by |mass
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// The nouns held, by part
+    pub nouns: Part,
+    /// Bytes of the NockStack in use
+    pub stack_used: usize,
+    /// Bytes of the NockStack free
+    pub stack_free: usize,
+    /// Copying done by preserves on the NockStack so far
+    pub preserve: PreserveStats,
+    /// Use of the PMA file, if one is open
+    pub pma: Option<PmaUsage>,
+    /// Bytes persisted with hash-consing, if any have been
+    pub hash_cons: Option<HashConsStats>,
+}
+
+impl Report {
+    /** Report on the interpreter state, after the nouns of `parts`
+     *
+     * `mass` must have counted `parts` and nothing else. The cold and warm states and the memo
+     * cache are counted after them, less whatever they share.
+     */
+    pub fn new(context: &mut Context, mass: &mut Mass, mut parts: Vec<Part>) -> Self {
+        let mut cold = 0;
+        unsafe { context.cold.for_each_noun(|noun| cold += mass.noun(*noun)) };
+        parts.push(Part::new("cold", cold));
+
+        let mut warm = 0;
+        unsafe { context.warm.for_each_noun(|noun| warm += mass.noun(noun)) };
+        parts.push(Part::new("warm", warm));
+
+        let mut cache = 0;
+        unsafe {
+            context
+                .cache
+                .for_each_pair(|pair| cache += mass.noun((*pair).0) + mass.noun((*pair).1))
+        };
+        parts.push(Part::new("cache", cache));
+
+        let stack_free = context.stack.free() << 3;
+        Report {
+            nouns: Part::with_parts("total", parts),
+            stack_used: (context.stack.size() << 3) - stack_free,
+            stack_free,
+            preserve: context.stack.preserve_stats(),
+            pma: pma_usage(),
+            hash_cons: Some(pma_hash_cons_stats()).filter(|stats| stats.plain > 0),
+        }
+    }
+
+    /// The report as lines of text, the parts indented below what they are part of
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for part in &self.nouns.parts {
+            part.lines(0, &mut lines);
+        }
+        lines.push(format!("total: {}", memory(self.nouns.bytes)));
+        lines.push(format!("stack used: {}", memory(self.stack_used)));
+        lines.push(format!("stack free: {}", memory(self.stack_free)));
+        lines.push(format!(
+            "preserved: {} in {} calls, {} cells",
+            memory(self.preserve.bytes as usize),
+            self.preserve.preserves,
+            self.preserve.cells
+        ));
+        if !self.preserve.time.is_zero() {
+            lines.push(format!(
+                "preserve time: {} ms",
+                self.preserve.time.as_millis()
+            ));
+        }
+        if let Some(pma) = self.pma {
+            let page = BT_PAGESIZE as usize;
+            lines.push(format!(
+                "pma used: {} ({} pages)",
+                memory(pma.used() * page),
+                pma.used()
+            ));
+            lines.push(format!(
+                "pma free: {} ({} pages)",
+                memory(pma.free * page),
+                pma.free
+            ));
+            lines.push(format!(
+                "pma pending: {} ({} pages)",
+                memory(pma.pending * page),
+                pma.pending
+            ));
+        }
+        if let Some(stats) = self.hash_cons {
+            lines.push(format!(
+                "pma hash-consed: {} of {} ({:.2}x)",
+                memory(stats.consed as usize),
+                memory(stats.plain as usize),
+                stats.ratio()
+            ));
+        }
+        lines
+    }
+
+    /// Send the report to the king, a %leaf tank to a line
+    pub fn slog(&self, stack: &mut NockStack, newt: &mut Newt) {
+        for line in self.lines() {
+            let tape = tape(stack, &line);
+            let tank = T(stack, &[D(tas!(b"leaf")), tape]);
+            newt.slog(stack, 0, tank);
+        }
+    }
+}
+
+/// Bytes written as vere writes them, in groups of three digits after the largest unit
+pub fn memory(bytes: usize) -> String {
+    let (gib, mib, kib, bib) = (
+        bytes / 1_000_000_000,
+        (bytes / 1_000_000) % 1000,
+        (bytes / 1000) % 1000,
+        bytes % 1000,
+    );
+    if gib > 0 {
+        format!("GB/{}.{:03}.{:03}.{:03}", gib, mib, kib, bib)
+    } else if mib > 0 {
+        format!("MB/{}.{:03}.{:03}", mib, kib, bib)
+    } else if kib > 0 {
+        format!("KB/{}.{:03}", kib, bib)
+    } else {
+        format!("B/{}", bib)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jets::util::test::init_context;
+    use crate::noun::{Cell, IndirectAtom};
+
+    const CELL: usize = word_size_of::<CellMemory>() << 3;
+
+    #[test]
+    fn write_memory() {
+        assert_eq!(memory(0), "B/0");
+        assert_eq!(memory(999), "B/999");
+        assert_eq!(memory(1_024), "KB/1.024");
+        assert_eq!(memory(12_000_345), "MB/12.000.345");
+        assert_eq!(memory(3_004_005_006), "GB/3.004.005.006");
+    }
+
+    #[test]
+    fn count_once() {
+        let c = &mut init_context();
+        let big = unsafe { IndirectAtom::new_raw(&mut c.stack, 2, [1, 2].as_ptr()) }.as_noun();
+        let shared = T(&mut c.stack, &[big, D(1)]);
+        let one = T(&mut c.stack, &[shared, shared]);
+        let two = Cell::new(&mut c.stack, shared, D(2)).as_noun();
+
+        let mut mass = Mass::new(&c.stack);
+        assert_eq!(mass.noun(D(7)), 0);
+        assert_eq!(mass.noun(one), 2 * CELL + (2 + 2) * 8);
+        assert_eq!(mass.noun(two), CELL);
+        assert_eq!(mass.noun(one), 0);
+    }
+
+    #[test]
+    fn bitmap_of_stack_in_use() {
+        let c = &mut init_context();
+        T(&mut c.stack, &[D(1), D(2)]);
+        let mass = Mass::new(&c.stack);
+        let start = c.stack.get_start();
+        let end = unsafe { start.add(c.stack.size()) };
+        // Leave out the PMA, which another test may have open
+        let words: usize = mass
+            .regions
+            .iter()
+            .filter(|region| region.lo >= start && region.hi <= end)
+            .map(|region| (region.hi as usize - region.lo as usize) >> 3)
+            .sum();
+        assert_eq!(words, c.stack.size() - c.stack.free());
+        assert!(words < c.stack.size() / 2);
+    }
+
+    #[test]
+    fn break_down_arvo() {
+        let c = &mut init_context();
+        let zuse = T(&mut c.stack, &[D(1), D(2)]);
+        let vane = T(&mut c.stack, &[D(3), D(4)]);
+        let arvo = T(&mut c.stack, &[zuse, vane, D(0)]);
+
+        //  ~[[%zuse & zuse] [%vanes | ~[[%ames & vane] [%zuse & zuse]]]]
+        let zuse_mass = T(&mut c.stack, &[D(tas!(b"zuse")), D(0), zuse]);
+        let ames_mass = T(&mut c.stack, &[D(tas!(b"ames")), D(0), vane]);
+        let vanes = T(&mut c.stack, &[ames_mass, zuse_mass, D(0)]);
+        let vanes_mass = T(&mut c.stack, &[D(tas!(b"vanes")), D(1), vanes]);
+        let whey = T(&mut c.stack, &[zuse_mass, vanes_mass, D(0)]);
+
+        let mut mass = Mass::new(&c.stack);
+        let arvo = mass.arvo(arvo, Some(whey));
+        assert_eq!(arvo.bytes, 4 * CELL);
+        assert_eq!(arvo.find(&["zuse"]).unwrap().bytes, CELL);
+        assert_eq!(arvo.find(&["vanes", "ames"]).unwrap().bytes, CELL);
+        assert_eq!(arvo.find(&["vanes", "zuse"]).unwrap().bytes, 0);
+        assert_eq!(arvo.find(&["other"]).unwrap().bytes, 2 * CELL);
+
+        let report = Report::new(c, &mut mass, vec![arvo]);
+        assert_eq!(report.nouns.bytes, 4 * CELL);
+        assert!(report.stack_used > 0);
+        let lines = report.lines();
+        assert_eq!(lines[0], format!("arvo: {}", memory(4 * CELL)));
+        assert_eq!(lines[1], format!("  zuse: {}", memory(CELL)));
+        assert_eq!(lines[2], format!("  vanes: {}", memory(CELL)));
+        assert_eq!(lines[3], format!("    ames: {}", memory(CELL)));
+        assert_eq!(lines[4], "    zuse: B/0");
+        assert_eq!(lines[6], "cold: B/0");
+        assert_eq!(lines[9], format!("total: {}", memory(4 * CELL)));
+    }
+}
@@ -10,6 +10,7 @@ use std::alloc::Layout;
 use std::mem;
 use std::ptr;
 use std::ptr::copy_nonoverlapping;
+use std::time::{Duration, Instant};
 
 crate::gdb!();
 
@@ -32,6 +33,30 @@ fn indirect_raw_size(atom: IndirectAtom) -> usize {
     atom.size() + 2
 }
 
+/** Counts of the copying done by [NockStack::preserve] */
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PreserveStats {
+    /** Calls to [NockStack::preserve] */
+    pub preserves: u64,
+    /** Cells copied */
+    pub cells: u64,
+    /** Bytes copied into previous frames, counting cells, atoms and other structures */
+    pub bytes: u64,
+    /** Time spent preserving, which is only measured while preserves are traced */
+    pub time: Duration,
+}
+
+/** One call to [NockStack::preserve], as traced */
+#[derive(Copy, Clone, Debug)]
+pub struct PreserveSpan {
+    pub start: Instant,
+    pub time: Duration,
+    /** Depth of the frame preserved from, counting the top frame as 1 */
+    pub depth: usize,
+    pub cells: u64,
+    pub bytes: u64,
+}
+
 /** A stack for Nock computation, which supports stack allocation and delimited copying collection
  * for returned nouns
  */
@@ -52,10 +77,18 @@ pub struct NockStack {
     /** PMA from which we will copy into the NockStack */
     /** Whether or not pre_copy() has been called on the current stack frame. */
     pc: bool,
+    /** Number of frames, counting the top frame as 1 */
+    depth: usize,
+    /** Counts of the copying done by preserve() */
+    preserve_stats: PreserveStats,
+    /** The shortest preserve() to trace, if they are traced */
+    preserve_threshold: Option<Duration>,
+    /** Preserves traced since [NockStack::take_preserve_spans] */
+    preserve_spans: Vec<PreserveSpan>,
 }
 
 impl NockStack {
-    /**  Initialization
+    /*  Initialization
      * The initial frame is a west frame. When the stack is initialized, a number of slots is given.
      * We add three extra slots to store the “previous” frame, stack, and allocation pointer. For the
      * initial frame, the previous allocation pointer is set to the beginning (low boundary) of the
@@ -65,7 +98,12 @@ impl NockStack {
      * top_slots is how many slots to allocate to the top stack frame.
      */
     pub fn new(size: usize, top_slots: usize) -> NockStack {
-        let memory = MmapMut::map_anon(size << 3).expect("Mapping memory for nockstack failed");
+        Self::try_new(size, top_slots).expect("Mapping memory for nockstack failed")
+    }
+
+    /** As [NockStack::new], but fails if the memory can't be mapped. */
+    pub fn try_new(size: usize, top_slots: usize) -> std::io::Result<NockStack> {
+        let memory = MmapMut::map_anon(size << 3)?;
         let start = memory.as_ptr() as *const u64;
         // Here, frame_pointer < alloc_pointer, so the initial frame is West
         let frame_pointer = unsafe { start.add(RESERVED + top_slots) } as *mut u64;
@@ -76,7 +114,7 @@ impl NockStack {
             *frame_pointer.sub(STACK + 1) = ptr::null::<u64>() as u64; // "stack pointer" from "previous" frame
             *frame_pointer.sub(ALLOC + 1) = start as u64; // "alloc pointer" from "previous" frame
         };
-        NockStack {
+        Ok(NockStack {
             start,
             size,
             frame_pointer,
@@ -84,7 +122,11 @@ impl NockStack {
             alloc_pointer,
             memory,
             pc: false,
-        }
+            depth: 1,
+            preserve_stats: PreserveStats::default(),
+            preserve_threshold: None,
+            preserve_spans: Vec::new(),
+        })
     }
 
     /** Resets the NockStack but flipping the top-frame polarity and unsetting PC. Sets the alloc
@@ -129,6 +171,7 @@ impl NockStack {
         self.stack_pointer = self.frame_pointer;
         self.alloc_pointer = unsafe { self.start.add(self.size) } as *mut u64;
         self.pc = false;
+        self.depth = 1;
         unsafe {
             *self.frame_pointer.sub(FRAME + 1) = ptr::null::<u64>() as u64; // "frame pointer" from "previous" frame
             *self.frame_pointer.sub(STACK + 1) = ptr::null::<u64>() as u64; // "stack pointer" from "previous" frame
@@ -141,6 +184,26 @@ impl NockStack {
         self.pc
     }
 
+    /** Counts of the copying done by [NockStack::preserve] so far */
+    pub fn preserve_stats(&self) -> PreserveStats {
+        self.preserve_stats
+    }
+
+    /** Time each [NockStack::preserve], keeping those which take at least `threshold` to be
+     * traced, or stop if it is `None`
+     */
+    pub fn trace_preserves(&mut self, threshold: Option<Duration>) {
+        self.preserve_threshold = threshold;
+        if threshold.is_none() {
+            self.preserve_spans = Vec::new();
+        }
+    }
+
+    /** The preserves traced since the last call */
+    pub fn take_preserve_spans(&mut self) -> Vec<PreserveSpan> {
+        mem::take(&mut self.preserve_spans)
+    }
+
     /** Current frame pointer of this NockStack */
     pub fn get_frame_pointer(&self) -> *const u64 {
         self.frame_pointer
@@ -187,6 +250,32 @@ impl NockStack {
         self.size
     }
 
+    /** Free space **in 64-bit words** between the stack and the allocation arena of the current
+     * frame
+     */
+    pub fn free(&self) -> usize {
+        unsafe {
+            if self.is_west() {
+                self.alloc_pointer.offset_from(self.stack_pointer) as usize
+            } else {
+                self.stack_pointer.offset_from(self.alloc_pointer) as usize
+            }
+        }
+    }
+
+    /** The two ends of the NockStack in use, as `[lo, hi)` pairs: below and above the free space
+     * of the current frame
+     */
+    pub fn used_spans(&self) -> [(*const u64, *const u64); 2] {
+        let (lo, hi) = if self.is_west() {
+            (self.stack_pointer, self.alloc_pointer)
+        } else {
+            (self.alloc_pointer, self.stack_pointer)
+        };
+        let end = unsafe { self.start.add(self.size) };
+        [(self.start, lo as *const u64), (hi as *const u64, end)]
+    }
+
     /** Check to see if an allocation is in frame */
     #[inline]
     pub unsafe fn is_in_frame<T>(&self, ptr: *const T) -> bool {
@@ -278,7 +367,7 @@ impl NockStack {
         }
     }
 
-    /**  Allocation
+    /*  Allocation
      * In a west frame, the allocation pointer is higher than the frame pointer, and so the allocation
      * size is subtracted from the allocation pointer, and then the allocation pointer is returned as
      * the pointer to the newly allocated memory.
@@ -362,6 +451,7 @@ impl NockStack {
      * frame. */
     unsafe fn raw_alloc_in_previous_frame(&mut self, words: usize) -> *mut u64 {
         self.pre_copy();
+        self.preserve_stats.bytes += (words << 3) as u64;
         if self.is_west() {
             self.raw_alloc_in_previous_frame_west(words)
         } else {
@@ -389,7 +479,7 @@ impl NockStack {
         }
     }
 
-    /**  Copying and Popping
+    /*  Copying and Popping
      * Prior to any copying step, the saved frame, stack, and allocation pointers must
      * be moved out of the frame. A three-word allocation is made to hold the saved
      * frame, stack, and allocation pointers. After this they will be accessed by reference
@@ -498,6 +588,7 @@ impl NockStack {
                                         // Make space for the cell
                                         let alloc =
                                             self.struct_alloc_in_previous_frame::<CellMemory>(1);
+                                        self.preserve_stats.cells += 1;
 
                                         // Copy the cell metadata
                                         (*alloc).metadata = (*cell.to_raw_pointer()).metadata;
@@ -530,6 +621,50 @@ impl NockStack {
         assert_no_junior_pointers!(self, *noun);
     }
 
+    /** The depth of the frame whose allocations hold `ptr`, or 0 if it is not on the NockStack
+     *
+     * # Safety
+     *
+     * The frame pointers saved in each frame must be intact.
+     */
+    pub unsafe fn frame_depth_of<T>(&self, ptr: *const T) -> usize {
+        let ptr = ptr as *const u64;
+        let end = self.start.add(self.size);
+        let mut depth = self.depth;
+        let mut west = self.is_west();
+        let mut alloc_pointer = self.alloc_pointer as *const u64;
+        let mut prev_frame_pointer = *self.prev_frame_pointer_pointer() as *const u64;
+        let mut prev_stack_pointer = *self.prev_stack_pointer_pointer() as *const u64;
+        let mut prev_alloc_pointer = *self.prev_alloc_pointer_pointer() as *const u64;
+        loop {
+            let (lo, hi) = match (west, prev_stack_pointer.is_null()) {
+                (true, true) => (alloc_pointer, end),
+                (true, false) => (alloc_pointer, prev_stack_pointer),
+                (false, true) => (self.start, alloc_pointer),
+                (false, false) => (prev_stack_pointer, alloc_pointer),
+            };
+            if ptr >= lo && ptr < hi {
+                return depth;
+            }
+            if prev_frame_pointer.is_null() {
+                return 0;
+            }
+            let frame_pointer = prev_frame_pointer;
+            alloc_pointer = prev_alloc_pointer;
+            west = !west;
+            if west {
+                prev_frame_pointer = *(frame_pointer.sub(FRAME + 1)) as *const u64;
+                prev_stack_pointer = *(frame_pointer.sub(STACK + 1)) as *const u64;
+                prev_alloc_pointer = *(frame_pointer.sub(ALLOC + 1)) as *const u64;
+            } else {
+                prev_frame_pointer = *(frame_pointer.add(FRAME)) as *const u64;
+                prev_stack_pointer = *(frame_pointer.add(STACK)) as *const u64;
+                prev_alloc_pointer = *(frame_pointer.add(ALLOC)) as *const u64;
+            }
+            depth -= 1;
+        }
+    }
+
     pub unsafe fn assert_struct_is_in<T>(&self, ptr: *const T, count: usize) {
         let ap = (if self.pc {
             *(self.prev_alloc_pointer_pointer())
@@ -597,6 +732,8 @@ impl NockStack {
         self.stack_pointer = prev_stack_ptr;
         self.alloc_pointer = prev_alloc_ptr;
 
+        self.depth -= 1;
+
         if self.frame_pointer.is_null()
             || self.stack_pointer.is_null()
             || self.alloc_pointer.is_null()
@@ -613,10 +750,30 @@ impl NockStack {
     }
 
     pub unsafe fn preserve<T: Preserve>(&mut self, x: &mut T) {
-        x.preserve(self)
+        self.preserve_stats.preserves += 1;
+        let Some(threshold) = self.preserve_threshold else {
+            x.preserve(self);
+            return;
+        };
+
+        let before = self.preserve_stats;
+        let start = Instant::now();
+        x.preserve(self);
+        let time = start.elapsed();
+        self.preserve_stats.time += time;
+        if time >= threshold {
+            let span = PreserveSpan {
+                start,
+                time,
+                depth: self.depth,
+                cells: self.preserve_stats.cells - before.cells,
+                bytes: self.preserve_stats.bytes - before.bytes,
+            };
+            permit_alloc(|| self.preserve_spans.push(span));
+        }
     }
 
-    /**  Pushing
+    /*  Pushing
      *  When pushing, we swap the stack and alloc pointers, set the frame pointer to be the stack
      *  pointer, move both frame and stack pointer by number of locals (eastward for west frames,
      *  westward for east frame), and then save the old stack/frame/alloc pointers in slots
@@ -644,6 +801,7 @@ impl NockStack {
             *(self.slot_pointer(STACK)) = current_stack_pointer as u64;
             *(self.slot_pointer(ALLOC)) = current_alloc_pointer as u64;
         }
+        self.depth += 1;
     }
 
     /** Run a closure inside a frame, popping regardless of the value returned by the closure.
@@ -663,7 +821,7 @@ impl NockStack {
         ret
     }
 
-    /** Lightweight stack.
+    /* Lightweight stack.
      * The lightweight stack is a stack data structure present in each stack
      * frame, often used for noun traversal. During normal operation (self.pc
      * == false),a west frame has a "west-oriented" lightweight stack, which
@@ -851,7 +1009,7 @@ impl NockStack {
         }
     }
 
-    /**
+    /*
      * Debugging
      *
      * The below functions are useful for debugging NockStack issues. */
@@ -1029,3 +1187,9 @@ impl<T: Preserve, E: Preserve> Preserve for Result<T, E> {
         }
     }
 }
+
+impl Preserve for () {
+    unsafe fn preserve(&mut self, _stack: &mut NockStack) {}
+
+    unsafe fn assert_in_stack(&self, _stack: &NockStack) {}
+}
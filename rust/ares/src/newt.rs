@@ -13,6 +13,7 @@
  *               [%save eve=@]
  *               [%meld ~]
  *               [%pack ~]
+ *               [%mass ~]
  *       ==  ==
  *       [%peek mil=@ sam=*]  :: gang (each path $%([%once @tas @tas path] [%beam @tas beam]))
  *       [%play eve=@ lit=(list ?((pair @da ovum) *))]
@@ -49,36 +50,72 @@
  * haven't tested the same for stdin.
  *
  * It's important to not use io::Stdin and io::Stdout directly.  All printfs should use stderr.
+ *
+ * A newt can also record a transcript of the session: every writ and plea, in order, framed as on
+ * the wire but with the first byte of each frame replaced by [TRANSCRIPT_WRIT] or
+ * [TRANSCRIPT_PLEA]. Transcripts can be read back with [read_frame].
  */
 use crate::mem::NockStack;
-use crate::noun::{IndirectAtom, Noun, D, T};
+use crate::noun::{Atom, IndirectAtom, Noun, D, T};
 use crate::serialization::{cue, jam};
 use ares_macros::tas;
 use either::Either;
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use std::os::unix::prelude::FromRawFd;
 use std::ptr::copy_nonoverlapping;
 use std::slice::from_raw_parts_mut;
 
 crate::gdb!();
 
+/// Transcript frame tag for a writ, from king to serf
+pub const TRANSCRIPT_WRIT: u8 = b'w';
+/// Transcript frame tag for a plea, from serf to king
+pub const TRANSCRIPT_PLEA: u8 = b'p';
+
 pub struct Newt {
-    input: std::fs::File,
-    output: std::fs::File,
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+    transcript: Option<Box<dyn Write>>,
 }
 
 impl Newt {
     pub fn new() -> Newt {
-        Newt {
-            input: unsafe { std::fs::File::from_raw_fd(0) },
-            output: unsafe { std::fs::File::from_raw_fd(1) },
-        }
+        Newt::from_io(unsafe { std::fs::File::from_raw_fd(0) }, unsafe {
+            std::fs::File::from_raw_fd(1)
+        })
     }
 
     pub fn new_mock() -> Newt {
+        Newt::from_io(io::empty(), io::sink())
+    }
+
+    /** Read writs from `input` and write pleas to `output`.
+     *
+     * NB: `output` should be unbuffered, or the king may wait forever on a plea stuck in a buffer.
+     */
+    pub fn from_io<R: Read + 'static, W: Write + 'static>(input: R, output: W) -> Newt {
         Newt {
-            input: std::fs::File::open("/dev/null").expect("newt: could not open /dev/null"),
-            output: std::fs::File::open("/dev/null").expect("newt: could not open /dev/null"),
+            input: Box::new(input),
+            output: Box::new(output),
+            transcript: None,
+        }
+    }
+
+    /** Record every writ and plea from here on to a transcript.
+     *
+     * If writing the transcript fails, recording stops but the newt carries on.
+     */
+    pub fn record<W: Write + 'static>(&mut self, transcript: W) {
+        self.transcript = Some(Box::new(transcript));
+    }
+
+    fn record_frame(&mut self, tag: u8, bytes: &[u8]) {
+        if let Some(transcript) = self.transcript.as_mut() {
+            let res = write_frame(transcript, tag, bytes).and_then(|()| transcript.flush());
+            if let Err(e) = res {
+                eprintln!("\rnewt: error writing transcript: {:?}", e);
+                self.transcript = None;
+            }
         }
     }
 
@@ -115,6 +152,7 @@ impl Newt {
             },
         };
         self.output.write_all(buf).unwrap();
+        self.record_frame(TRANSCRIPT_PLEA, &buf[5..]);
     }
 
     /** Send %ripe, the first event.
@@ -235,31 +273,58 @@ impl Newt {
 
     /** Fetch next message. */
     pub fn next(&mut self, stack: &mut NockStack) -> Option<Noun> {
-        let mut header: Vec<u8> = vec![0; 5];
-        if let Err(err) = self.input.read_exact(&mut header) {
-            if err.kind() == std::io::ErrorKind::UnexpectedEof {
-                return None;
-            } else {
-                panic!("Error reading header: {}", err);
-            }
+        let atom = match read_frame(stack, &mut self.input) {
+            Ok(Some((_tag, atom))) => atom,
+            Ok(None) => return None,
+            Err(err) => panic!("Error reading writ: {}", err),
+        };
+        self.record_frame(TRANSCRIPT_WRIT, atom.as_bytes());
+
+        Some(cue(stack, atom))
+    }
+}
+
+/** Read a frame: a tag byte, a 32-bit little-endian length, and that many bytes of jammed noun.
+ *
+ * Returns the tag and the (still jammed) noun, or None at the end of the input.
+ */
+pub fn read_frame<R: Read + ?Sized>(
+    stack: &mut NockStack,
+    input: &mut R,
+) -> io::Result<Option<(u8, Atom)>> {
+    let mut header: Vec<u8> = vec![0; 5];
+    if let Err(err) = input.read_exact(&mut header) {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            return Ok(None);
+        } else {
+            return Err(err);
         }
+    }
 
-        let byte_len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    let byte_len = u32::from_le_bytes([header[1], header[2], header[3], header[4]]) as usize;
 
-        let atom = unsafe {
-            let (mut atom, dest) = IndirectAtom::new_raw_mut_bytes(stack, byte_len);
-            if let Err(err) = self.input.read_exact(dest) {
-                if err.kind() == std::io::ErrorKind::UnexpectedEof {
-                    return None;
-                } else {
-                    panic!("Error reading body: {}", err);
-                }
+    let atom = unsafe {
+        let (mut atom, dest) = IndirectAtom::new_raw_mut_bytes(stack, byte_len);
+        if let Err(err) = input.read_exact(dest) {
+            if err.kind() == io::ErrorKind::UnexpectedEof {
+                return Ok(None);
+            } else {
+                return Err(err);
             }
-            atom.normalize_as_atom()
-        };
+        }
+        atom.normalize_as_atom()
+    };
 
-        Some(cue(stack, atom))
-    }
+    Ok(Some((header[0], atom)))
+}
+
+/** Write a frame, as read by [read_frame]. */
+pub fn write_frame<W: Write + ?Sized>(output: &mut W, tag: u8, bytes: &[u8]) -> io::Result<()> {
+    let len = bytes.len() as u32;
+    let mut header = [tag; 5];
+    header[1..].copy_from_slice(&len.to_le_bytes());
+    output.write_all(&header)?;
+    output.write_all(bytes)
 }
 
 impl Default for Newt {
@@ -267,3 +332,50 @@ impl Default for Newt {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noun::Slots;
+    use std::fs::File;
+
+    #[test]
+    fn transcript_round_trip() {
+        let mut stack = NockStack::new(8 << 10 << 10, 0);
+        let path = std::env::temp_dir().join(format!("newt-transcript-{}", std::process::id()));
+
+        let writ = T(&mut stack, &[D(tas!(b"live")), D(tas!(b"meld")), D(0)]);
+        let jammed = jam(&mut stack, writ);
+        let mut input = Vec::new();
+        write_frame(&mut input, 0, jammed.as_bytes()).unwrap();
+
+        let mut newt = Newt::from_io(io::Cursor::new(input), io::sink());
+        newt.record(File::create(&path).unwrap());
+        let got = newt.next(&mut stack).expect("no writ");
+        assert_eq!(
+            got.slot(6).unwrap().as_direct().unwrap().data(),
+            tas!(b"meld")
+        );
+        newt.live(&mut stack);
+        assert!(newt.next(&mut stack).is_none());
+
+        let mut transcript = File::open(&path).unwrap();
+        let (tag, atom) = read_frame(&mut stack, &mut transcript).unwrap().unwrap();
+        assert_eq!(tag, TRANSCRIPT_WRIT);
+        let mut writ_copy = cue(&mut stack, atom);
+        let mut writ_orig = writ;
+        assert!(unsafe {
+            crate::unifying_equality::unifying_equality(&mut stack, &mut writ_copy, &mut writ_orig)
+        });
+        let (tag, atom) = read_frame(&mut stack, &mut transcript).unwrap().unwrap();
+        assert_eq!(tag, TRANSCRIPT_PLEA);
+        let plea = cue(&mut stack, atom);
+        assert_eq!(
+            plea.slot(2).unwrap().as_direct().unwrap().data(),
+            tas!(b"live")
+        );
+        assert!(read_frame(&mut stack, &mut transcript).unwrap().is_none());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
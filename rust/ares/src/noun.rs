@@ -20,13 +20,13 @@ const DIRECT_MASK: u64 = !(u64::MAX >> 1);
 pub const DIRECT_MAX: u64 = u64::MAX >> 1;
 
 /** Tag for an indirect atom. */
-const INDIRECT_TAG: u64 = u64::MAX & DIRECT_MASK;
+const INDIRECT_TAG: u64 = DIRECT_MASK;
 
 /** Tag mask for an indirect atom. */
 const INDIRECT_MASK: u64 = !(u64::MAX >> 2);
 
 /** Tag for a cell. */
-const CELL_TAG: u64 = u64::MAX & INDIRECT_MASK;
+const CELL_TAG: u64 = INDIRECT_MASK;
 
 /** Tag mask for a cell. */
 const CELL_MASK: u64 = !(u64::MAX >> 3);
@@ -50,7 +50,7 @@ const CELL_MASK: u64 = !(u64::MAX >> 3);
  */
 
 /** Tag for a forwarding pointer */
-const FORWARDING_TAG: u64 = u64::MAX & CELL_MASK;
+const FORWARDING_TAG: u64 = CELL_MASK;
 
 /** Tag mask for a forwarding pointer */
 const FORWARDING_MASK: u64 = CELL_MASK;
@@ -307,6 +307,7 @@ pub fn tape<A: NounAllocator>(allocator: &mut A, text: &str) -> Noun {
  * - first word: metadata
  * - second word: size in 64-bit words
  * - remaining words: data
+ *
  * Indirect atoms are always stored in little-endian byte order
  */
 #[derive(Copy, Clone)]
@@ -1,3 +1,4 @@
+use crate::hamt::{Hamt, MutHamt};
 use crate::mem::NockStack;
 use crate::noun::{Allocated, Atom, Cell, CellMemory, IndirectAtom, Noun};
 use ares_pma::*;
@@ -7,10 +8,11 @@ use std::ffi::{c_void, CString};
 use std::mem::size_of;
 use std::path::PathBuf;
 use std::ptr::copy_nonoverlapping;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::OnceLock;
 
 const PMA_MODE: mode_t = 0o600; // RW for user only
-const PMA_FLAGS: ULONG = 0; // ignored for now
+const PMA_FLAGS: ULONG = 0;
 
 const NOUN_MARKED: u64 = 1 << 63;
 
@@ -20,6 +22,13 @@ struct PMAState(u64); // this is idiotic but necessary for Rust to let us put th
 
 static PMA: OnceLock<PMAState> = OnceLock::new();
 
+/// The addresses the PMA is mapped between, kept so that [pma_contains] needn't cross into C
+static PMA_BOUNDS: OnceLock<(usize, usize)> = OnceLock::new();
+
+/// Totals of the [HashConsStats] from every call to [pma_hash_cons]
+static HASH_CONS_PLAIN: AtomicU64 = AtomicU64::new(0);
+static HASH_CONS_CONSED: AtomicU64 = AtomicU64::new(0);
+
 fn get_pma_state() -> Option<*mut BT_state> {
     PMA.get().map(|r| r.0 as *mut BT_state)
 }
@@ -30,18 +39,36 @@ fn pma_state_err() -> std::io::Error {
 
 #[cfg(unix)]
 pub fn pma_open(path: PathBuf) -> Result<(), std::io::Error> {
+    pma_open_flags(path, PMA_FLAGS)
+}
+
+/// Open an existing PMA without ever writing to its file, to inspect it: what is written in
+/// memory stays private to the process, and [pma_sync] does nothing.
+#[cfg(unix)]
+pub fn pma_open_read_only(path: PathBuf) -> Result<(), std::io::Error> {
+    pma_open_flags(path, PMA_FLAGS | BT_RDONLY as ULONG)
+}
+
+#[cfg(unix)]
+fn pma_open_flags(path: PathBuf, flags: ULONG) -> Result<(), std::io::Error> {
     let mut state: *mut BT_state = std::ptr::null_mut();
 
     // correct for Unix thus cfg gated
     let path_cstring = CString::new(path.into_os_string().as_encoded_bytes())?;
     unsafe {
         bt_state_new(&mut state);
-        let err = bt_state_open(state, path_cstring.as_ptr(), PMA_FLAGS, PMA_MODE);
+        let err = bt_state_open(state, path_cstring.as_ptr(), flags, PMA_MODE);
         if err == 0 {
             PMA.set(PMAState(state as u64))
                 .map_err(|state| state.0 as *mut BT_state)
                 .expect("PMA state already initialized to:");
             assert!(get_pma_state().is_some());
+            let mut lo = std::ptr::null_mut();
+            let mut hi = std::ptr::null_mut();
+            bt_bounds(state, &mut lo, &mut hi);
+            PMA_BOUNDS
+                .set((lo as usize, hi as usize))
+                .expect("PMA bounds already initialized");
             Ok(())
         } else {
             // XX need to free the state
@@ -50,11 +77,30 @@ pub fn pma_open(path: PathBuf) -> Result<(), std::io::Error> {
     }
 }
 
+/// Whether a PMA is open
+pub fn pma_is_open() -> bool {
+    get_pma_state().is_some()
+}
+
 #[cfg(windows)]
 pub fn pma_open(path: PathBuf) -> Result<Self, std::io::Error> {
     unimplemented!()
 }
 
+/// Open a scratch PMA shared by the tests in this process, and hold it: the PMA is not thread-safe
+#[cfg(test)]
+pub(crate) fn pma_open_for_test() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    static OPEN: std::sync::Once = std::sync::Once::new();
+    let guard = LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    OPEN.call_once(|| {
+        let path = std::env::temp_dir().join(format!("ares-test-pma-{}", std::process::id()));
+        std::fs::create_dir_all(&path).expect("test pma: could not create directory");
+        pma_open(path).expect("test pma: open failed");
+    });
+    guard
+}
+
 pub fn pma_close() -> Result<(), std::io::Error> {
     // XX need a way to free the state after
     let err = unsafe { bt_state_close(get_pma_state().ok_or_else(pma_state_err)?) };
@@ -75,10 +121,11 @@ pub fn pma_meta_set(field: usize, val: u64) {
     unsafe { bt_meta_set(get_pma_state().unwrap(), field, val) };
 }
 
+#[inline]
 pub unsafe fn pma_contains<T>(ptr: *const T, count: usize) -> bool {
-    if let Some(pma_state) = get_pma_state() {
-        bt_inbounds(pma_state, ptr as *mut c_void) != 0
-            && bt_inbounds(pma_state, ptr.add(count) as *mut c_void) != 0
+    if let Some((lo, hi)) = PMA_BOUNDS.get() {
+        let start = ptr as usize;
+        start >= *lo && (ptr.add(count) as usize) < *hi
     } else {
         false
     }
@@ -99,6 +146,381 @@ pub unsafe fn pma_dirty<T>(ptr: *mut T, count: usize) {
     assert!(e == 0);
 }
 
+/// List the allocated ranges of the PMA, in address order
+pub fn pma_allocations() -> Vec<(*mut c_void, *mut c_void)> {
+    let pma_state = get_pma_state().unwrap();
+    let mut allocations = Vec::new();
+    let mut cursor: *mut c_void = std::ptr::null_mut();
+    unsafe {
+        loop {
+            let mut lo: *mut c_void = std::ptr::null_mut();
+            let mut hi: *mut c_void = std::ptr::null_mut();
+            if bt_next_alloc(pma_state, cursor, &mut lo, &mut hi) != 0 {
+                break;
+            }
+            allocations.push((lo, hi));
+            cursor = hi;
+        }
+    }
+    allocations
+}
+
+/// Pages of the PMA file, of [BT_PAGESIZE] bytes each
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PmaUsage {
+    /// The size of the file
+    pub file: usize,
+    /// Pages free for new allocations
+    pub free: usize,
+    /// Pages freed since the last [pma_sync], which are reused once it is done
+    pub pending: usize,
+}
+
+impl PmaUsage {
+    /// Pages backing allocations, or the B-tree and meta pages
+    pub fn used(&self) -> usize {
+        self.file - self.free - self.pending
+    }
+}
+
+/// How much of the PMA file is in use, if a PMA is open
+pub fn pma_usage() -> Option<PmaUsage> {
+    let pma_state = get_pma_state()?;
+    let mut usage = PmaUsage {
+        file: 0,
+        free: 0,
+        pending: 0,
+    };
+    unsafe {
+        bt_usage(
+            pma_state,
+            &mut usage.file,
+            &mut usage.free,
+            &mut usage.pending,
+        )
+    };
+    Some(usage)
+}
+
+/// Check the meta pages and B-tree of a PMA by reading its file, before it is opened: opening a
+/// PMA whose current meta page or B-tree is corrupt aborts. Each problem found is reported on
+/// stderr, and the number found is returned.
+#[cfg(unix)]
+pub fn pma_check_file(path: PathBuf) -> Result<usize, std::io::Error> {
+    let path_cstring = CString::new(path.into_os_string().as_encoded_bytes())?;
+    Ok(unsafe { bt_check_file(path_cstring.as_ptr()) } as usize)
+}
+
+/// Check the integrity of the open PMA: both meta pages, the ordering of the B-tree, and the
+/// consistency of the free lists with it. Only the meta pages carry checksums, so the contents of
+/// data pages are not checked. Each problem found is reported on stderr, and the number found is
+/// returned.
+pub fn pma_check() -> usize {
+    unsafe { bt_check(get_pma_state().unwrap()) as usize }
+}
+
+/// Free an allocated range of the PMA. The range is unmapped immediately, but its backing pages
+/// are not reused until the next [pma_sync].
+///
+/// # Safety
+///
+/// Nothing may refer into the range once it is freed.
+pub unsafe fn pma_free(lo: *mut c_void, hi: *mut c_void) {
+    bt_free(get_pma_state().unwrap(), lo, hi);
+}
+
+/// Make every allocation in the PMA writable until the next [pma_sync]
+///
+/// # Safety
+///
+/// The PMA must be open.
+pub unsafe fn pma_dirty_all() {
+    let pma_state = get_pma_state().unwrap();
+    for (lo, hi) in pma_allocations() {
+        let e = bt_dirty(pma_state, lo, hi);
+        assert!(e == 0);
+    }
+}
+
+/**
+ * Compact the PMA.
+ *
+ * The closure must persist (with [Persist::save_to_pma]) everything which is live in the PMA, and
+ * return the new handles. It is passed [Keep::Nothing] to persist with, so that all of the live
+ * structure is copied into fresh allocations. Afterwards the old allocations, which now hold only
+ * forwarding pointers and garbage, are freed.
+ *
+ * # Safety
+ *
+ * Any pointers into the PMA held outside of what the closure persists are invalidated.
+ */
+pub unsafe fn pma_pack<F, R>(f: F) -> R
+where
+    F: FnOnce(Keep) -> R,
+{
+    let old_allocations = pma_allocations();
+
+    // Copying out of the old allocations leaves marks and forwarding pointers in them
+    pma_dirty_all();
+
+    let res = f(Keep::Nothing);
+
+    for (lo, hi) in old_allocations {
+        pma_free(lo, hi);
+    }
+
+    res
+}
+
+/// Bytes of nouns to persist, before and after [pma_hash_cons] unified them
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HashConsStats {
+    /// Bytes the nouns would need in the PMA as they were
+    pub plain: u64,
+    /// Bytes the nouns need in the PMA once equal nouns are unified
+    pub consed: u64,
+}
+
+impl HashConsStats {
+    /// How many times over the nouns would have been stored without hash-consing
+    pub fn ratio(&self) -> f64 {
+        if self.consed == 0 {
+            1.0
+        } else {
+            self.plain as f64 / self.consed as f64
+        }
+    }
+}
+
+/// What hash-consing has saved since the process started
+pub fn pma_hash_cons_stats() -> HashConsStats {
+    HashConsStats {
+        plain: HASH_CONS_PLAIN.load(Ordering::Relaxed),
+        consed: HASH_CONS_CONSED.load(Ordering::Relaxed),
+    }
+}
+
+/**
+ * Hash-cons nouns about to be persisted, so that [Persist::copy_to_buffer] stores each distinct
+ * subtree once.
+ *
+ * Every noun reachable from the roots which is not yet in the PMA is unified with an equal noun in
+ * `known`, a table of nouns already in the PMA, or else with an equal new noun found earlier. The
+ * mugs computed are cached in the new nouns, so persisted nouns keep them, and in the nouns
+ * already in the PMA which they refer to, whose pages are dirtied first.
+ *
+ * Returns the bytes the new nouns need in the PMA before and after, which are also added to the
+ * totals in [pma_hash_cons_stats].
+ *
+ * # Safety
+ *
+ * Each root must point to a noun, in a slot which may be written if the noun is not in the PMA.
+ * No noun may be marked, as [Persist::space_needed] leaves them.
+ */
+pub unsafe fn pma_hash_cons(
+    stack: &mut NockStack,
+    known: Option<Hamt<()>>,
+    roots: &[*mut Noun],
+) -> HashConsStats {
+    let plain = nouns_space_needed(stack, Keep::Pma, roots);
+
+    dirty_unmugged(stack, roots);
+    stack.frame_push(0);
+    pma_meld(stack, Keep::Pma, known, roots);
+    stack.frame_pop();
+
+    pma_hash_cons_record(HashConsStats {
+        plain,
+        consed: nouns_space_needed(stack, Keep::Pma, roots),
+    })
+}
+
+/// Add to the totals in [pma_hash_cons_stats]
+pub fn pma_hash_cons_record(stats: HashConsStats) -> HashConsStats {
+    HASH_CONS_PLAIN.fetch_add(stats.plain, Ordering::Relaxed);
+    HASH_CONS_CONSED.fetch_add(stats.consed, Ordering::Relaxed);
+    stats
+}
+
+/**
+ * Unify equal nouns reachable from the roots, so that one of each is left, and return a table of
+ * those left, keyed by the nouns and giving a slot which refers to each. The table is allocated in
+ * the current frame.
+ *
+ * Nouns which `keep` keeps where they are are left alone, along with what they refer to. A noun
+ * equal to one in `known` is unified with that one, and what it refers to is left alone.
+ *
+ * # Safety
+ *
+ * Each root must point to a noun in a slot which may be written. Any noun in the PMA which is
+ * walked must have a mug cached or be dirty, as unification may cache mugs in it.
+ */
+pub unsafe fn pma_meld(
+    stack: &mut NockStack,
+    keep: Keep,
+    known: Option<Hamt<()>>,
+    roots: &[*mut Noun],
+) -> MutHamt<*mut Noun> {
+    let table = MutHamt::<*mut Noun>::new(stack);
+    for root in roots.iter().copied() {
+        meld_noun(stack, keep, known, table, root);
+    }
+    table
+}
+
+/// Bytes the nouns would need in the PMA, each allocation counted once, leaving no marks
+///
+/// # Safety
+///
+/// Each root must point to a noun, and no noun may be marked. Nouns in the PMA which `keep` does
+/// not keep must be dirty.
+pub unsafe fn nouns_space_needed(stack: &mut NockStack, keep: Keep, roots: &[*mut Noun]) -> u64 {
+    let mut space = 0;
+    for root in roots.iter().copied() {
+        space += (*root).space_needed(stack, keep) as u64;
+    }
+    for root in roots.iter().copied() {
+        unmark_noun(stack, keep, *root);
+    }
+    space
+}
+
+/// Unmark every allocation reachable from the noun which [Persist::space_needed] marked
+unsafe fn unmark_noun(stack: &mut NockStack, keep: Keep, noun: Noun) {
+    stack.frame_push(0);
+    *(stack.push::<Noun>()) = noun;
+    while !stack.stack_is_empty() {
+        let noun = *(stack.top::<Noun>());
+        stack.pop::<Noun>();
+
+        if let Ok(allocated) = noun.as_allocated() {
+            if keep.keeps(allocated.to_raw_pointer(), 1)
+                || allocated.get_metadata() & NOUN_MARKED == 0
+            {
+                continue;
+            }
+            unmark(allocated);
+            if let Some(cell) = allocated.cell() {
+                *(stack.push::<Noun>()) = cell.tail();
+                *(stack.push::<Noun>()) = cell.head();
+            }
+        }
+    }
+    stack.frame_pop();
+}
+
+/// Dirty every noun in the PMA without a mug cached which the nouns refer to, so that the mugs
+/// can be cached. A noun with a mug cached has one cached for everything it refers to.
+unsafe fn dirty_unmugged(stack: &mut NockStack, roots: &[*mut Noun]) {
+    stack.frame_push(0);
+    for root in roots.iter().copied() {
+        *(stack.push::<Noun>()) = *root;
+    }
+    while !stack.stack_is_empty() {
+        let noun = *(stack.top::<Noun>());
+        stack.pop::<Noun>();
+
+        if let Ok(allocated) = noun.as_allocated() {
+            if allocated.get_cached_mug().is_some() {
+                continue;
+            }
+            let ptr = allocated.to_raw_pointer();
+            if pma_contains(ptr, 1) {
+                pma_dirty(ptr as *mut u64, 1);
+            }
+            if let Some(cell) = allocated.cell() {
+                *(stack.push::<Noun>()) = cell.tail();
+                *(stack.push::<Noun>()) = cell.head();
+            }
+        }
+    }
+    stack.frame_pop();
+}
+
+/// Unify every noun reachable from the slot with an equal noun already in `known` or the table,
+/// or else add it to the table
+unsafe fn meld_noun(
+    stack: &mut NockStack,
+    keep: Keep,
+    known: Option<Hamt<()>>,
+    table: MutHamt<*mut Noun>,
+    root: *mut Noun,
+) {
+    *(stack.push::<*mut Noun>()) = root;
+    while !stack.stack_is_empty() {
+        let dest = *(stack.top::<*mut Noun>());
+        stack.pop::<*mut Noun>();
+
+        let Ok(allocated) = (*dest).as_allocated() else {
+            continue;
+        };
+        if keep.keeps(allocated.to_raw_pointer(), 1) {
+            continue;
+        }
+        if known.is_some_and(|known| known.lookup(stack, &mut *dest).is_some()) {
+            continue;
+        }
+        if let Some(first) = table.lookup(stack, &mut *dest) {
+            // Unification keeps whichever noun is lower in memory within a frame, which may be
+            // this one, leaving the slots which found the other noun earlier alone. So within a
+            // frame the noun found first is kept, and only a more senior noun replaces it.
+            let (kept, other) = (*first, *dest);
+            if !kept.raw_equals(other) {
+                if stack.frame_depth_of(noun_pointer(other))
+                    < stack.frame_depth_of(noun_pointer(kept))
+                {
+                    set_slot(first, other);
+                } else {
+                    set_slot(dest, kept);
+                }
+            }
+            continue;
+        }
+        table.insert(stack, &mut *dest, dest);
+
+        if let Some(cell) = allocated.cell() {
+            *(stack.push::<*mut Noun>()) = cell.tail_as_mut();
+            *(stack.push::<*mut Noun>()) = cell.head_as_mut();
+        }
+    }
+}
+
+unsafe fn noun_pointer(noun: Noun) -> *const u64 {
+    noun.as_allocated()
+        .map_or(std::ptr::null(), |allocated| allocated.to_raw_pointer())
+}
+
+/// Write a noun into a slot, which may be in the PMA
+unsafe fn set_slot(slot: *mut Noun, noun: Noun) {
+    if pma_contains(slot, 1) {
+        pma_dirty(slot, 1);
+    }
+    *slot = noun;
+}
+
+/// Which existing structures [Persist] leaves where they are, rather than copying into the PMA
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Keep {
+    /// Whatever is already in the PMA
+    Pma,
+    /// Nothing: everything is copied into fresh allocations, as [pma_pack] needs
+    Nothing,
+}
+
+impl Keep {
+    /// Whether the range is left where it is
+    ///
+    /// # Safety
+    ///
+    /// As for [pma_contains].
+    pub unsafe fn keeps<T>(self, ptr: *const T, count: usize) -> bool {
+        match self {
+            Keep::Pma => pma_contains(ptr, count),
+            Keep::Nothing => false,
+        }
+    }
+}
+
 /**
  * This trait defines operations for copying a structure into the PMA.
  *
@@ -114,19 +536,19 @@ pub unsafe fn pma_dirty<T>(ptr: *mut T, count: usize) {
  * the [copy_to_buffer] method for the keys and values as it copies its own structures in.
  */
 pub trait Persist {
-    /// Count how much space is needed, in bytes. May set marks so long as marks are cleaned up by
-    /// [copy_into_buffer]
-    unsafe fn space_needed(&mut self, stack: &mut NockStack) -> usize;
+    /// Count how much space is needed, in bytes, for whatever `keep` does not keep where it is.
+    /// May set marks so long as marks are cleaned up by [copy_into_buffer]
+    unsafe fn space_needed(&mut self, stack: &mut NockStack, keep: Keep) -> usize;
 
     /// Copy into the provided buffer, which may be assumed to be at least as large as the size
-    /// returned by [space_needed] on the same structure.
-    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, buffer: &mut *mut u8);
+    /// returned by [space_needed] on the same structure with the same `keep`.
+    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, keep: Keep, buffer: &mut *mut u8);
 
     /// Persist an object into the PMA using [space_needed] and [copy_to_buffer], returning
     /// a [u64] (probably a pointer or tagged pointer) that can be saved into metadata.
-    unsafe fn save_to_pma(&mut self, stack: &mut NockStack) -> u64 {
+    unsafe fn save_to_pma(&mut self, stack: &mut NockStack, keep: Keep) -> u64 {
         unsafe {
-            let space = self.space_needed(stack);
+            let space = self.space_needed(stack, keep);
 
             if space == 0 {
                 return self.handle_to_u64();
@@ -136,7 +558,7 @@ pub trait Persist {
 
             let mut buffer = bt_malloc(get_pma_state().unwrap(), space_as_pages) as *mut u8;
             let orig_buffer = buffer;
-            self.copy_to_buffer(stack, &mut buffer);
+            self.copy_to_buffer(stack, keep, &mut buffer);
             let space_isize: isize = space.try_into().unwrap();
             assert!(buffer.offset_from(orig_buffer) == space_isize);
             self.handle_to_u64()
@@ -161,20 +583,20 @@ unsafe fn unmark(a: Allocated) {
 }
 
 impl Persist for Atom {
-    unsafe fn space_needed(&mut self, _stack: &mut NockStack) -> usize {
+    unsafe fn space_needed(&mut self, _stack: &mut NockStack, keep: Keep) -> usize {
         if let Ok(indirect) = self.as_indirect() {
             let count = indirect.raw_size();
-            if !pma_contains(indirect.to_raw_pointer(), count) && !mark(indirect.as_allocated()) {
+            if !keep.keeps(indirect.to_raw_pointer(), count) && !mark(indirect.as_allocated()) {
                 return count * size_of::<u64>();
             }
         }
         0
     }
 
-    unsafe fn copy_to_buffer(&mut self, _stack: &mut NockStack, buffer: &mut *mut u8) {
+    unsafe fn copy_to_buffer(&mut self, _stack: &mut NockStack, keep: Keep, buffer: &mut *mut u8) {
         if let Ok(mut indirect) = self.as_indirect() {
             let count = indirect.raw_size();
-            if !pma_contains(indirect.to_raw_pointer(), count) {
+            if !keep.keeps(indirect.to_raw_pointer(), count) {
                 if let Some(forward) = indirect.forwarding_pointer() {
                     *self = forward.as_atom();
                 } else {
@@ -200,7 +622,7 @@ impl Persist for Atom {
 }
 
 impl Persist for Noun {
-    unsafe fn space_needed(&mut self, stack: &mut NockStack) -> usize {
+    unsafe fn space_needed(&mut self, stack: &mut NockStack, keep: Keep) -> usize {
         let mut space = 0usize;
         stack.frame_push(0);
         *(stack.push::<Noun>()) = *self;
@@ -213,10 +635,10 @@ impl Persist for Noun {
 
             match noun.as_either_atom_cell() {
                 Left(mut atom) => {
-                    space += atom.space_needed(stack);
+                    space += atom.space_needed(stack, keep);
                 }
                 Right(cell) => {
-                    if !pma_contains(cell.to_raw_pointer(), 1) && !mark(cell.as_allocated()) {
+                    if !keep.keeps(cell.to_raw_pointer(), 1) && !mark(cell.as_allocated()) {
                         space += size_of::<CellMemory>();
                         (*stack.push::<Noun>()) = cell.tail();
                         (*stack.push::<Noun>()) = cell.head();
@@ -228,7 +650,7 @@ impl Persist for Noun {
         space
     }
 
-    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, buffer: &mut *mut u8) {
+    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, keep: Keep, buffer: &mut *mut u8) {
         let mut buffer_u64 = (*buffer) as *mut u64;
         stack.frame_push(0);
         *(stack.push::<*mut Noun>()) = self as *mut Noun;
@@ -252,7 +674,7 @@ impl Persist for Noun {
                     match allocated.as_either() {
                         Left(mut indirect) => {
                             let count = indirect.raw_size();
-                            if pma_contains(indirect.to_raw_pointer(), count) {
+                            if keep.keeps(indirect.to_raw_pointer(), count) {
                                 continue;
                             }
 
@@ -263,7 +685,7 @@ impl Persist for Noun {
                             buffer_u64 = buffer_u64.add(count);
                         }
                         Right(mut cell) => {
-                            if pma_contains(cell.to_raw_pointer(), 1) {
+                            if keep.keeps(cell.to_raw_pointer(), 1) {
                                 continue;
                             }
 
@@ -297,6 +719,26 @@ impl Persist for Noun {
     }
 }
 
+impl Persist for () {
+    unsafe fn space_needed(&mut self, _stack: &mut NockStack, _keep: Keep) -> usize {
+        0
+    }
+
+    unsafe fn copy_to_buffer(
+        &mut self,
+        _stack: &mut NockStack,
+        _keep: Keep,
+        _buffer: &mut *mut u8,
+    ) {
+    }
+
+    unsafe fn handle_to_u64(&self) -> u64 {
+        0
+    }
+
+    unsafe fn handle_from_u64(_meta_handle: u64) -> Self {}
+}
+
 /** Mask to mask out pointer bits not aligned with a BT_PAGESIZE page */
 const BT_PAGEBITS_MASK_OUT: u64 = !((1 << BT_PAGEBITS) - 1);
 
@@ -1,62 +1,100 @@
+use crate::bytecode::Code;
+use crate::cli::read_atom;
+use crate::debugger::Debugger;
 use crate::hamt::Hamt;
-use crate::interpreter::{inc, interpret, Error, Mote};
+use crate::interpreter::{inc, interpret, Error, Formulas, Mote};
 use crate::jets::cold::Cold;
 use crate::jets::hot::{Hot, HotEntry};
 use crate::jets::list::util::{lent, zing};
 use crate::jets::nock::util::mook;
 use crate::jets::warm::Warm;
+use crate::jets::JetTest;
+use crate::mass::{Mass, Report};
 use crate::mem::NockStack;
 use crate::mug::*;
 use crate::newt::Newt;
-use crate::noun::{Atom, Cell, DirectAtom, Noun, Slots, D, T};
+use crate::noun::{tape, Atom, Cell, DirectAtom, Noun, Slots, D, T};
 use crate::persist::pma_meta_set;
-use crate::persist::{pma_meta_get, pma_open, pma_sync, Persist};
+use crate::persist::{
+    nouns_space_needed, pma_dirty_all, pma_hash_cons, pma_hash_cons_record, pma_meld, pma_meta_get,
+    pma_open, pma_pack, pma_sync, HashConsStats, Keep, Persist,
+};
+use crate::serialization::{cue, jam};
 use crate::trace::*;
 use crate::{flog, interpreter};
 use ares_macros::tas;
 use signal_hook;
 use signal_hook::consts::SIGINT;
-use std::fs::create_dir_all;
+use std::fs::{create_dir_all, File};
 use std::io;
 use std::mem::size_of;
 use std::path::PathBuf;
 use std::result::Result;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::time::Instant;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 crate::gdb!();
 
+/// Sample the Nock being run into a folded-stack profile (u3o_debug_cpu in vere)
+const FLAG_PROFILE: u32 = 1 << 1;
 const FLAG_TRACE: u32 = 1 << 8;
 
+/// How many earlier snapshots to keep as rollback points
+pub(crate) const SNAPSHOTS_RETAINED: usize = 8;
+
+/// Words of NockStack if the king doesn't say: 16 GiB
+const DEFAULT_STACK_SIZE: usize = 2048 << 10 << 10;
+
+/// Words of NockStack it may grow to on running out of memory: 64 GiB
+const MAX_STACK_SIZE: usize = 8192 << 10 << 10;
+
 #[repr(usize)]
-enum BTMetaField {
+pub(crate) enum BTMetaField {
     SnapshotVersion = 0,
     Snapshot = 1,
+    /// The first of [SNAPSHOTS_RETAINED] fields holding earlier snapshots, newest first
+    Retained = 2,
+    /// A table of the nouns in the current snapshot as of the last pack, if hash-consing
+    HashCons = 2 + SNAPSHOTS_RETAINED,
+}
+pub(crate) struct Snapshot(pub *mut SnapshotMem);
+
+impl Snapshot {
+    pub(crate) fn epoch(&self) -> u64 {
+        unsafe { (*self.0).epoch }
+    }
+
+    pub(crate) fn event_num(&self) -> u64 {
+        unsafe { (*self.0).event_num }
+    }
+
+    pub(crate) fn time(&self) -> u64 {
+        unsafe { (*self.0).time }
+    }
 }
-struct Snapshot(pub *mut SnapshotMem);
 
 impl Persist for Snapshot {
-    unsafe fn space_needed(&mut self, stack: &mut NockStack) -> usize {
+    unsafe fn space_needed(&mut self, stack: &mut NockStack, keep: Keep) -> usize {
         let mut arvo = (*(self.0)).arvo;
         let mut cold = (*(self.0)).cold;
-        let arvo_space_needed = arvo.space_needed(stack);
-        let cold_space_needed = cold.space_needed(stack);
+        let arvo_space_needed = arvo.space_needed(stack, keep);
+        let cold_space_needed = cold.space_needed(stack, keep);
         (((size_of::<SnapshotMem>() + 7) >> 3) << 3) + arvo_space_needed + cold_space_needed
     }
 
-    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, buffer: &mut *mut u8) {
+    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, keep: Keep, buffer: &mut *mut u8) {
         let snapshot_buffer = *buffer as *mut SnapshotMem;
         std::ptr::copy_nonoverlapping(self.0, snapshot_buffer, 1);
         *self = Snapshot(snapshot_buffer);
         *buffer = snapshot_buffer.add(1) as *mut u8;
 
         let mut arvo = (*snapshot_buffer).arvo;
-        arvo.copy_to_buffer(stack, buffer);
+        arvo.copy_to_buffer(stack, keep, buffer);
         (*snapshot_buffer).arvo = arvo;
 
         let mut cold = (*snapshot_buffer).cold;
-        cold.copy_to_buffer(stack, buffer);
+        cold.copy_to_buffer(stack, keep, buffer);
         (*snapshot_buffer).cold = cold;
     }
 
@@ -69,65 +107,215 @@ impl Persist for Snapshot {
     }
 }
 
+/// Several snapshots, persisted together so that they keep sharing structure in the PMA
+struct Snapshots(Vec<Snapshot>);
+
+impl Persist for Snapshots {
+    unsafe fn space_needed(&mut self, stack: &mut NockStack, keep: Keep) -> usize {
+        self.0
+            .iter_mut()
+            .map(|snapshot| snapshot.space_needed(stack, keep))
+            .sum()
+    }
+
+    unsafe fn copy_to_buffer(&mut self, stack: &mut NockStack, keep: Keep, buffer: &mut *mut u8) {
+        for snapshot in self.0.iter_mut() {
+            snapshot.copy_to_buffer(stack, keep, buffer);
+        }
+    }
+
+    unsafe fn handle_to_u64(&self) -> u64 {
+        self.0
+            .first()
+            .map_or(0, |snapshot| snapshot.handle_to_u64())
+    }
+
+    unsafe fn handle_from_u64(meta_handle: u64) -> Self {
+        Snapshots(vec![Snapshot::handle_from_u64(meta_handle)])
+    }
+}
+
+/// The snapshots retained as rollback points, newest first
+pub(crate) fn retained_snapshots() -> Vec<Snapshot> {
+    (0..SNAPSHOTS_RETAINED)
+        .map(|idx| pma_meta_get(BTMetaField::Retained as usize + idx))
+        .take_while(|handle| *handle != 0)
+        .map(|handle| unsafe { Snapshot::handle_from_u64(handle) })
+        .collect()
+}
+
+pub(crate) fn set_retained_snapshots(snapshots: &[Snapshot]) {
+    for idx in 0..SNAPSHOTS_RETAINED {
+        let handle = snapshots
+            .get(idx)
+            .map_or(0, |snapshot| unsafe { snapshot.handle_to_u64() });
+        pma_meta_set(BTMetaField::Retained as usize + idx, handle);
+    }
+}
+
+#[repr(C)]
+#[repr(packed)]
+pub(crate) struct SnapshotMem {
+    pub epoch: u64,
+    pub event_num: u64,
+    /// When the snapshot was saved, in seconds since the Unix epoch, or 0 if not known
+    pub time: u64,
+    pub arvo: Noun,
+    pub cold: Cold,
+}
+
+/// The layout of a snapshot at version 1, before [SnapshotMem::time]
 #[repr(C)]
 #[repr(packed)]
-struct SnapshotMem {
+pub(crate) struct SnapshotMemV1 {
     pub epoch: u64,
     pub event_num: u64,
     pub arvo: Noun,
     pub cold: Cold,
 }
 
-const PMA_CURRENT_SNAPSHOT_VERSION: u64 = 1;
+pub(crate) const PMA_CURRENT_SNAPSHOT_VERSION: u64 = 2;
+
+/**
+ * Upgrade a snapshot from one version to the next, given its handle, and return the handle of the
+ * upgraded snapshot.
+ *
+ * A migration may rewrite the snapshot in place, after marking its pages dirty with
+ * [crate::persist::pma_dirty], or build an upgraded copy with [Persist::save_to_pma]. Each should
+ * be tested against a fixture PMA written at the old version.
+ */
+type Migration = unsafe fn(&mut NockStack, u64) -> u64;
+
+/// Entry `n` upgrades a version `n + 1` snapshot to version `n + 2`
+const SNAPSHOT_MIGRATIONS: &[Migration] = &[add_snapshot_time];
+
+const _: () = assert!(SNAPSHOT_MIGRATIONS.len() as u64 + 1 == PMA_CURRENT_SNAPSHOT_VERSION);
+
+/// Version 1 to 2: copy the snapshot with a [SnapshotMem::time] of 0. Arvo and the cold state
+/// stay where they are in the PMA, and the old snapshot is left for [Context::pack] to drop.
+unsafe fn add_snapshot_time(stack: &mut NockStack, handle: u64) -> u64 {
+    let old = handle as *const SnapshotMemV1;
+    stack.frame_push(0);
+    let snapshot_mem_ptr: *mut SnapshotMem = stack.struct_alloc(1);
+    (*snapshot_mem_ptr).epoch = (*old).epoch;
+    (*snapshot_mem_ptr).event_num = (*old).event_num;
+    (*snapshot_mem_ptr).time = 0;
+    (*snapshot_mem_ptr).arvo = (*old).arvo;
+    (*snapshot_mem_ptr).cold = (*old).cold;
+    let handle = Snapshot(snapshot_mem_ptr).save_to_pma(stack, Keep::Pma);
+    stack.frame_pop();
+    handle
+}
+
+/// Upgrade a snapshot handle from `version` through each of the remaining `migrations`
+unsafe fn migrate_handle(
+    stack: &mut NockStack,
+    migrations: &[Migration],
+    version: u64,
+    handle: u64,
+) -> u64 {
+    assert!(version >= 1 && version <= migrations.len() as u64 + 1);
+    migrations[(version - 1) as usize..]
+        .iter()
+        .fold(handle, |handle, migration| migration(stack, handle))
+}
+
+/// Upgrade the current and retained snapshots in the PMA from `version` to the current version.
+/// Takes effect at the next [pma_sync].
+unsafe fn migrate_snapshots(stack: &mut NockStack, version: u64) {
+    // The newest retained snapshot is often the current one, which should stay a single snapshot
+    let mut migrated: Vec<(u64, u64)> = Vec::new();
+    let mut migrate = |stack: &mut NockStack, handle: u64| {
+        if let Some((_, new)) = migrated.iter().find(|(old, _)| *old == handle) {
+            return *new;
+        }
+        let new = migrate_handle(stack, SNAPSHOT_MIGRATIONS, version, handle);
+        migrated.push((handle, new));
+        new
+    };
+
+    let handle = pma_meta_get(BTMetaField::Snapshot as usize);
+    pma_meta_set(BTMetaField::Snapshot as usize, migrate(stack, handle));
+
+    for idx in 0..SNAPSHOTS_RETAINED {
+        let field = BTMetaField::Retained as usize + idx;
+        let handle = pma_meta_get(field);
+        if handle != 0 {
+            pma_meta_set(field, migrate(stack, handle));
+        }
+    }
 
-struct Context {
-    epoch: u64,
-    event_num: u64,
-    arvo: Noun,
-    mug: u32,
-    nock_context: interpreter::Context,
+    pma_meta_set(
+        BTMetaField::SnapshotVersion as usize,
+        PMA_CURRENT_SNAPSHOT_VERSION,
+    );
+}
+
+pub(crate) struct Context {
+    pier_path: PathBuf,
+    pub(crate) epoch: u64,
+    pub(crate) event_num: u64,
+    pub(crate) arvo: Noun,
+    pub(crate) mug: u32,
+    pub(crate) nock_context: interpreter::Context,
+    /// The hot state, to set up again on a new NockStack
+    hot_state: Vec<HotEntry>,
+    /// Whether to hash-cons nouns as they are persisted, as `ARES_HASH_CONS` asks
+    pub(crate) hash_cons: bool,
 }
 
 impl Context {
+    /// Load the snapshot in the PMA at `snap_path`, with a NockStack of `stack_size` words
     pub fn load(
+        pier_path: PathBuf,
         snap_path: PathBuf,
+        stack_size: usize,
+        newt: Newt,
         trace_info: Option<TraceInfo>,
         constant_hot_state: &[HotEntry],
     ) -> Context {
         pma_open(snap_path).expect("serf: pma open failed");
 
+        let mut stack = NockStack::new(stack_size, 0);
         let snapshot_version = pma_meta_get(BTMetaField::SnapshotVersion as usize);
 
         let snapshot = match snapshot_version {
             0 => None,
-            1 => Some(unsafe {
+            PMA_CURRENT_SNAPSHOT_VERSION => Some(unsafe {
                 Snapshot::handle_from_u64(pma_meta_get(BTMetaField::Snapshot as usize))
             }),
-            _ => panic!("Unsupported snapshot version"),
+            version if version < PMA_CURRENT_SNAPSHOT_VERSION => unsafe {
+                migrate_snapshots(&mut stack, version);
+                pma_sync();
+                Some(Snapshot::handle_from_u64(pma_meta_get(
+                    BTMetaField::Snapshot as usize,
+                )))
+            },
+            version => panic!(
+                "serf: snapshot version {} is newer than supported version {}",
+                version, PMA_CURRENT_SNAPSHOT_VERSION
+            ),
         };
 
-        Context::new(trace_info, snapshot, constant_hot_state)
+        Context::new(
+            pier_path,
+            stack,
+            newt,
+            trace_info,
+            snapshot,
+            constant_hot_state,
+        )
     }
 
     pub unsafe fn save(&mut self) {
+        if self.hash_cons {
+            self.hash_cons();
+        }
         let handle = {
-            let mut snapshot = Snapshot({
-                let snapshot_mem_ptr: *mut SnapshotMem = self.nock_context.stack.struct_alloc(1);
-
-                // Save into PMA (does not sync)
-                (*snapshot_mem_ptr).epoch = self.epoch;
-                (*snapshot_mem_ptr).event_num = self.event_num;
-                (*snapshot_mem_ptr).arvo = self.arvo;
-                (*snapshot_mem_ptr).cold = self.nock_context.cold;
-                snapshot_mem_ptr
-            });
-
-            let handle = snapshot.save_to_pma(&mut self.nock_context.stack);
-
-            self.epoch = (*snapshot.0).epoch;
-            self.arvo = (*snapshot.0).arvo;
-            self.event_num = (*snapshot.0).event_num;
-            self.nock_context.cold = (*snapshot.0).cold;
+            // Save into PMA (does not sync)
+            let mut snapshot = self.snapshot();
+            let handle = snapshot.save_to_pma(&mut self.nock_context.stack, Keep::Pma);
+            self.adopt(&snapshot);
 
             handle
         };
@@ -138,13 +326,74 @@ impl Context {
         pma_meta_set(BTMetaField::Snapshot as usize, handle);
     }
 
+    /// Unify equal nouns in arvo and the cold state which are about to be persisted, with each
+    /// other and with the nouns in the table saved by the last [Context::pack]
+    unsafe fn hash_cons(&mut self) {
+        let known = match pma_meta_get(BTMetaField::HashCons as usize) {
+            0 => None,
+            handle => Some(Hamt::<()>::handle_from_u64(handle)),
+        };
+        let roots = self.roots();
+        pma_hash_cons(&mut self.nock_context.stack, known, &roots);
+    }
+
+    /// Slots of arvo and of the nouns in the cold state
+    unsafe fn roots(&mut self) -> Vec<*mut Noun> {
+        let mut roots: Vec<*mut Noun> = vec![&mut self.arvo];
+        self.nock_context
+            .cold
+            .for_each_noun(|noun| roots.push(noun));
+        roots
+    }
+
+    /// Stage the current state on the stack, to be persisted
+    unsafe fn snapshot(&mut self) -> Snapshot {
+        let snapshot_mem_ptr: *mut SnapshotMem = self.nock_context.stack.struct_alloc(1);
+        (*snapshot_mem_ptr).epoch = self.epoch;
+        (*snapshot_mem_ptr).event_num = self.event_num;
+        (*snapshot_mem_ptr).time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        (*snapshot_mem_ptr).arvo = self.arvo;
+        (*snapshot_mem_ptr).cold = self.nock_context.cold;
+        Snapshot(snapshot_mem_ptr)
+    }
+
+    /// Switch over to the persisted copy of the current state
+    unsafe fn adopt(&mut self, snapshot: &Snapshot) {
+        self.epoch = (*snapshot.0).epoch;
+        self.arvo = (*snapshot.0).arvo;
+        self.event_num = (*snapshot.0).event_num;
+        self.nock_context.cold = (*snapshot.0).cold;
+    }
+
+    /// Keep the current snapshot as a rollback point, forgetting the oldest one if there are
+    /// already [SNAPSHOTS_RETAINED]. Takes effect at the next [pma_sync].
+    pub fn retain(&mut self) {
+        if pma_meta_get(BTMetaField::SnapshotVersion as usize) == 0 {
+            return;
+        }
+        let current =
+            unsafe { Snapshot::handle_from_u64(pma_meta_get(BTMetaField::Snapshot as usize)) };
+        let mut retained = retained_snapshots();
+        if retained.first().is_some_and(|newest| {
+            newest.epoch() == current.epoch() && newest.event_num() == current.event_num()
+        }) {
+            return;
+        }
+        retained.insert(0, current);
+        retained.truncate(SNAPSHOTS_RETAINED);
+        set_retained_snapshots(&retained);
+    }
+
     fn new(
+        pier_path: PathBuf,
+        mut stack: NockStack,
+        newt: Newt,
         trace_info: Option<TraceInfo>,
         snapshot: Option<Snapshot>,
         constant_hot_state: &[HotEntry],
     ) -> Self {
-        let mut stack = NockStack::new(2048 << 10 << 10, 0);
-        let newt = Newt::new();
         let cache = Hamt::<Noun>::new(&mut stack);
 
         let (epoch, event_num, arvo, mut cold) = unsafe {
@@ -161,6 +410,7 @@ impl Context {
 
         let hot = Hot::init(&mut stack, constant_hot_state);
         let warm = Warm::init(&mut stack, &mut cold, &hot);
+        let code = Code::new(&mut stack);
         let mug = mug_u32(&mut stack, arvo);
 
         let nock_context = interpreter::Context {
@@ -169,20 +419,241 @@ impl Context {
             cold,
             warm,
             hot,
+            code,
+            formulas: Formulas::new(),
             cache,
             scry_stack: D(0),
             trace_info,
+            jet_test: JetTest::from_env(),
+            mean_slot: std::ptr::null(),
+            outer_means: D(0),
+            outer_trace: std::ptr::null(),
+            live: LiveCounters::new(),
+            profile: None,
+            jet_stats: None,
+            gas: None,
+            debugger: Debugger::from_env(),
         };
 
-        Context {
+        let mut context = Context {
+            pier_path,
             epoch,
             event_num,
             arvo,
             mug,
             nock_context,
+            hot_state: constant_hot_state.to_vec(),
+            hash_cons: std::env::var_os("ARES_HASH_CONS").is_some(),
+        };
+        context.trace_preserves();
+        context.load_code();
+        context
+    }
+
+    /// Time preserves on the NockStack, to trace them, if the trace records the gc category
+    fn trace_preserves(&mut self) {
+        let threshold = self
+            .nock_context
+            .trace_info
+            .as_ref()
+            .filter(|info| info.config.records(Category::Gc))
+            .map(|info| info.config.threshold);
+        self.nock_context.stack.trace_preserves(threshold);
+    }
+
+    /// Compile the arms of the jammed linearizer output named by ARES_TOWN, if any
+    fn load_code(&mut self) {
+        let Some(path) = std::env::var_os("ARES_TOWN") else {
+            return;
+        };
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                flog!(
+                    &mut self.nock_context,
+                    "\rserf: could not read town {:?}: {}",
+                    path,
+                    e
+                );
+                return;
+            }
+        };
+        let stack = &mut self.nock_context.stack;
+        let atom = read_atom(stack, &bytes);
+        let town = cue(stack, atom);
+        match self
+            .nock_context
+            .code
+            .install(stack, &self.nock_context.warm, town)
+        {
+            Ok(arms) => {
+                flog!(
+                    &mut self.nock_context,
+                    "\rserf: compiled {} arms from {:?}",
+                    arms,
+                    path
+                );
+            }
+            Err(e) => {
+                flog!(
+                    &mut self.nock_context,
+                    "\rserf: bad town {:?}: {:?}",
+                    path,
+                    e
+                );
+            }
         }
     }
 
+    //
+    // %live commands
+    //
+
+    /// Write a jammed copy of arvo to .urb/roc/<eve>.jam in the pier
+    pub fn cram(&mut self, eve: u64) -> io::Result<()> {
+        let mut rock_path = self.pier_path.clone();
+        rock_path.push(".urb");
+        rock_path.push("roc");
+        create_dir_all(&rock_path)?;
+        rock_path.push(format!("{}.jam", eve));
+
+        let stack = &mut self.nock_context.stack;
+        stack.frame_push(0);
+        let jammed = jam(stack, self.arvo);
+        let res = std::fs::write(rock_path, &jammed.as_bytes()[0..met3_usize(jammed)]);
+        unsafe { stack.frame_pop() };
+        res
+    }
+
+    ///
+    /// ## Safety
+    ///
+    /// Unifies equal nouns in arvo and the cold state in place, then compacts the PMA as [pack]
+    /// does. This invalidates all nouns not in the context.
+    pub unsafe fn meld(&mut self) {
+        self.unify();
+        self.compact();
+    }
+
+    ///
+    /// ## Safety
+    ///
+    /// Copies the snapshot and the retained snapshots into fresh PMA allocations and frees
+    /// everything else, unifying equal nouns in arvo and the cold state first if hash-consing.
+    /// This invalidates all nouns not in the context.
+    pub unsafe fn pack(&mut self) {
+        if self.hash_cons {
+            self.unify();
+        }
+        self.compact();
+    }
+
+    /// Unify equal nouns in arvo and the cold state, wherever they are
+    unsafe fn unify(&mut self) {
+        // Unification writes into the PMA, and caches mugs there
+        pma_dirty_all();
+
+        let roots = self.roots();
+        let stack = &mut self.nock_context.stack;
+        let plain = self
+            .hash_cons
+            .then(|| nouns_space_needed(stack, Keep::Nothing, &roots));
+        stack.frame_push(0);
+        pma_meld(stack, Keep::Nothing, None, &roots);
+        stack.frame_pop();
+        if let Some(plain) = plain {
+            let consed = nouns_space_needed(stack, Keep::Nothing, &roots);
+            pma_hash_cons_record(HashConsStats { plain, consed });
+        }
+    }
+
+    /// Copy the snapshot and the retained snapshots into fresh PMA allocations and free everything
+    /// else, then save a table of the nouns in the snapshot if hash-consing
+    unsafe fn compact(&mut self) {
+        pma_pack(|keep| {
+            let mut snapshots = Snapshots(vec![self.snapshot()]);
+            snapshots.0.extend(retained_snapshots());
+            let handle = snapshots.save_to_pma(&mut self.nock_context.stack, keep);
+            self.adopt(&snapshots.0[0]);
+
+            pma_meta_set(
+                BTMetaField::SnapshotVersion as usize,
+                PMA_CURRENT_SNAPSHOT_VERSION,
+            );
+            pma_meta_set(BTMetaField::Snapshot as usize, handle);
+            set_retained_snapshots(&snapshots.0[1..]);
+            // The old table is freed
+            pma_meta_set(BTMetaField::HashCons as usize, 0);
+        });
+
+        if self.hash_cons {
+            // Everything in the snapshot is unified and mugged, so walking it only reads
+            let roots = self.roots();
+            let stack = &mut self.nock_context.stack;
+            stack.frame_push(0);
+            let mut table = pma_meld(stack, Keep::Nothing, None, &roots).freeze(stack, &|_| ());
+            let handle = table.save_to_pma(stack, Keep::Pma);
+            stack.frame_pop();
+            pma_meta_set(BTMetaField::HashCons as usize, handle);
+        }
+
+        // The warm state, the memo cache, compiled code, and decoded formulas point into the freed
+        // allocations
+        self.nock_context.cache = Hamt::new(&mut self.nock_context.stack);
+        self.nock_context.formulas.clear();
+        self.nock_context.warm = Warm::init(
+            &mut self.nock_context.stack,
+            &mut self.nock_context.cold,
+            &self.nock_context.hot,
+        );
+        self.nock_context.code = Code::new(&mut self.nock_context.stack);
+        self.load_code();
+        self.preserve_event_update_leftovers();
+
+        pma_sync();
+    }
+
+    ///
+    /// ## Safety
+    ///
+    /// Moves to a fresh NockStack twice the size of the current one, unless that would be larger
+    /// than [MAX_STACK_SIZE] or can't be mapped, carrying `noun` over. Must be called at an event
+    /// boundary, once [save] has persisted the state as of the last event, since nothing else on
+    /// the old stack is carried over. This invalidates all nouns not in the context, except the
+    /// copy of `noun` it produces.
+    pub unsafe fn grow(&mut self, noun: Noun) -> Option<Noun> {
+        let size = self.nock_context.stack.size() * 2;
+        if size > MAX_STACK_SIZE {
+            return None;
+        }
+        let mut stack = NockStack::try_new(size, 0).ok()?;
+
+        // Arvo and the cold state are in the PMA, so the jammed noun is the one thing left to copy
+        let jammed = jam(&mut self.nock_context.stack, noun);
+        let jammed = read_atom(&mut stack, &jammed.as_bytes()[0..met3_usize(jammed)]);
+        let noun = cue(&mut stack, jammed);
+        self.nock_context.stack = stack;
+
+        // Everything else on the old stack is set up again on the new one
+        let stack = &mut self.nock_context.stack;
+        self.nock_context.cache = Hamt::new(stack);
+        self.nock_context.scry_stack = D(0);
+        self.nock_context.formulas.clear();
+        self.nock_context.hot = Hot::init(stack, &self.hot_state);
+        self.nock_context.warm =
+            Warm::init(stack, &mut self.nock_context.cold, &self.nock_context.hot);
+        self.nock_context.code = Code::new(stack);
+        self.load_code();
+        self.trace_preserves();
+
+        flog!(
+            &mut self.nock_context,
+            "\rserf: out of memory, grew the loom to {} MiB",
+            size >> 17
+        );
+        Some(noun)
+    }
+
     //
     // Setters
     //
@@ -212,10 +683,16 @@ impl Context {
     /// Other stack-allocated objects needing preservation should be preserved between
     /// [event_update] and invocation of this function
     pub unsafe fn preserve_event_update_leftovers(&mut self) {
+        let start = self.nock_context.trace_info.is_some().then(Instant::now);
         let stack = &mut self.nock_context.stack;
         stack.preserve(&mut self.nock_context.warm);
         stack.preserve(&mut self.nock_context.hot);
+        stack.preserve(&mut self.nock_context.code);
         stack.flip_top_frame(0);
+        if let Some(start) = start {
+            write_serf_trace_safe(&mut self.nock_context, Category::Gc, "preserve", start);
+            write_preserve_traces(&mut self.nock_context);
+        }
     }
 
     //
@@ -238,12 +715,114 @@ impl Context {
         self.nock_context.newt.live(&mut self.nock_context.stack);
     }
 
+    /// Write out the sampling profile, if there is one, and print how its samples divide between
+    /// jets and raw Nock
+    pub fn write_profile(&mut self) {
+        let Some(profile) = self.nock_context.profile.as_mut() else {
+            return;
+        };
+        let (samples, jets, nock) = (profile.samples(), profile.jet_samples, profile.nock_samples);
+        if let Err(e) = profile.write() {
+            flog!(
+                &mut self.nock_context,
+                "\rserf: error writing profile: {}",
+                e
+            );
+        }
+        flog!(
+            &mut self.nock_context,
+            "\rprofile: {} samples, {} in jets, {} in raw nock",
+            samples,
+            jets,
+            nock
+        );
+    }
+
+    /// Write out the sampling profile, if there is one and it is due to be written
+    fn flush_profile(&mut self) {
+        let Some(profile) = self.nock_context.profile.as_mut() else {
+            return;
+        };
+        if let Err(e) = profile.flush() {
+            flog!(
+                &mut self.nock_context,
+                "\rserf: error writing profile: {}",
+                e
+            );
+        }
+    }
+
+    /// Print the jet statistics, if they are being kept, and write them to the trace file
+    pub fn report_jet_stats(&mut self) {
+        let Some(stats) = self.nock_context.jet_stats.as_ref() else {
+            return;
+        };
+        let report = stats.report();
+        if let Some(info) = self.nock_context.trace_info.as_mut() {
+            if let Err(e) = stats.write_trace(info) {
+                flog!(
+                    &mut self.nock_context,
+                    "\rserf: error writing jet statistics to trace file: {:?}",
+                    e
+                );
+                self.nock_context.trace_info = None;
+            }
+        }
+        for line in report {
+            flog!(&mut self.nock_context, "\rjets: {}", line);
+        }
+    }
+
+    /// Print the %live hint counters, if any were hit
+    pub fn flog_live_counters(&mut self) {
+        let counters: Vec<(String, u64)> = self
+            .nock_context
+            .live
+            .sorted()
+            .into_iter()
+            .map(|(name, hits)| (String::from_utf8_lossy(name).into_owned(), hits))
+            .collect();
+        for (name, hits) in counters {
+            flog!(&mut self.nock_context, "\rlive: %{}: {}", name, hits);
+        }
+    }
+
+    /// Report on the memory held by the serf, as |mass does, in %slog pleas to the king
+    pub fn mass(&mut self) {
+        let whey = self.whey();
+        let mut mass = Mass::new(&self.nock_context.stack);
+        let arvo = mass.arvo(self.arvo, whey);
+        let report = Report::new(&mut self.nock_context, &mut mass, vec![arvo]);
+        report.slog(&mut self.nock_context.stack, &mut self.nock_context.newt);
+    }
+
+    /// Arvo's account of its own memory, the `(list mass)` produced by a `%whey` scry
+    fn whey(&mut self) -> Option<Noun> {
+        let stack = &mut self.nock_context.stack;
+        //  [lyc=[~ ~] nom=[%& /whey]]
+        let lyc = T(stack, &[D(0), D(0)]);
+        let pax = T(stack, &[D(tas!(b"whey")), D(0)]);
+        let ovo = T(stack, &[lyc, D(0), pax]);
+        //  [~ ~ %mass p=type q=(list mass)]
+        let res = slam(self, PEEK_AXIS, 0, ovo).ok()?;
+        if unsafe { !res.slot(14).ok()?.raw_equals(D(tas!(b"mass"))) } {
+            return None;
+        }
+        res.slot(31).ok()
+    }
+
     pub fn peek_done(&mut self, dat: Noun) {
         self.nock_context
             .newt
             .peek_done(&mut self.nock_context.stack, dat);
     }
 
+    pub fn peek_bail(&mut self, dud: Noun) {
+        self.nock_context
+            .newt
+            .peek_bail(&mut self.nock_context.stack, dud);
+    }
+
     pub fn play_done(&mut self) {
         self.nock_context
             .newt
@@ -295,11 +874,43 @@ const WISH_AXIS: u64 = 10;
 // Necessary because Arc::new is not const
 lazy_static! {
     pub static ref TERMINATOR: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    /// Set by the deadline watchdog once the running writ's deadline has passed. Unlike
+    /// [TERMINATOR] it is not seen by the SIGINT handler, so a later SIGINT still only interrupts.
+    pub static ref DEADLINE_PASSED: Arc<AtomicBool> = Arc::new(AtomicBool::new(false));
+    static ref DEADLINE: Arc<(Mutex<Option<Instant>>, Condvar)> = {
+        let deadline = Arc::new((Mutex::new(None), Condvar::new()));
+        let watchdog = Arc::clone(&deadline);
+        std::thread::spawn(move || watch_deadline(&watchdog));
+        deadline
+    };
 }
 
 /**
  * This is suitable for talking to the king process.  To test, change the arg_c[0] line in
  * u3_lord_init in vere to point at this binary and start vere like normal.
+ *
+ * The NockStack is as large as the loom size the king passes, and doubles, up to
+ * [MAX_STACK_SIZE], whenever an event runs out of memory, which is then retried.
+ *
+ * If `ARES_TRANSCRIPT` is set in the environment, a transcript of the session is recorded to the
+ * file it names, for use with [crate::cli::pier::conform].
+ *
+ * If `ARES_JET_TEST` is set in the environment, the jets it selects are checked against the raw
+ * Nock of their arms: see [JetTest].
+ *
+ * If `ARES_JET_STATS` is set in the environment, or jets are traced, statistics on calls into
+ * cold-registered cores are kept, and printed on SIGUSR1 and on exit: see [JetStats].
+ *
+ * Tracing is on if the trace flag is set or `ARES_TRACE` is set in the environment, which holds
+ * the trace configuration: see [TraceConfig::parse].
+ *
+ * If `ARES_DEBUG` is set in the environment, the interpreter stops at the breakpoints it lists:
+ * see [crate::debugger].
+ *
+ * If `ARES_HASH_CONS` is set in the environment, equal nouns are unified as they are persisted, with
+ * each other and with those in a table of the nouns in the PMA, so that the PMA holds one copy of
+ * each: see [pma_hash_cons]. The table is rebuilt by `%pack`, which unifies everything in the PMA
+ * first, as `%meld` does.
  */
 pub fn serf(constant_hot_state: &[HotEntry]) -> io::Result<()> {
     // Register SIGINT signal hook to set flag first time, shutdown second time
@@ -308,7 +919,7 @@ pub fn serf(constant_hot_state: &[HotEntry]) -> io::Result<()> {
 
     let pier_path_string = std::env::args()
         .nth(2)
-        .ok_or(io::Error::new(io::ErrorKind::Other, "no pier path"))?;
+        .ok_or(io::Error::other("no pier path"))?;
     let pier_path = PathBuf::from(pier_path_string);
     let mut snap_path = pier_path.clone();
     snap_path.push(".urb");
@@ -317,18 +928,18 @@ pub fn serf(constant_hot_state: &[HotEntry]) -> io::Result<()> {
 
     let wag: u32 = std::env::args()
         .nth(4)
-        .ok_or(io::Error::new(io::ErrorKind::Other, "no flag bitmap"))?
+        .ok_or(io::Error::other("no flag bitmap"))?
         .parse()
-        .or(Err(io::Error::new(
-            io::ErrorKind::Other,
-            "flag bitmap is not integer",
-        )))?;
+        .or(Err(io::Error::other("flag bitmap is not integer")))?;
 
-    let mut trace_info = if wag & FLAG_TRACE != 0 {
-        create_trace_file(pier_path).ok()
-    } else {
-        None
-    };
+    let stack_size = stack_size(std::env::args().nth(6))?;
+
+    let mut trace_config = TraceConfig::from_env()?;
+    if wag & FLAG_TRACE != 0 {
+        trace_config.get_or_insert_with(TraceConfig::default);
+    }
+    let mut trace_info =
+        trace_config.and_then(|config| create_trace_file(pier_path.clone(), config).ok());
     if let Some(ref mut info) = trace_info.as_mut() {
         if let Err(_e) = write_metadata(info) {
             //  XX: need NockStack allocated string interpolation
@@ -339,7 +950,57 @@ pub fn serf(constant_hot_state: &[HotEntry]) -> io::Result<()> {
         }
     }
 
-    let mut context = Context::load(snap_path, trace_info, constant_hot_state);
+    let mut newt = Newt::new();
+    if let Some(transcript_path) = std::env::var_os("ARES_TRANSCRIPT") {
+        newt.record(File::create(transcript_path)?);
+    }
+
+    let profile = if wag & FLAG_PROFILE != 0 {
+        Profile::create(pier_path.clone()).ok()
+    } else {
+        None
+    };
+
+    let trace_jets = trace_info
+        .as_ref()
+        .is_some_and(|info| info.config.records(Category::Jet));
+    let jet_stats = if trace_jets || std::env::var_os("ARES_JET_STATS").is_some() {
+        JetStats::report_on_signal()?;
+        Some(JetStats::new())
+    } else {
+        None
+    };
+
+    let mut context = Context::load(
+        pier_path,
+        snap_path,
+        stack_size,
+        newt,
+        trace_info,
+        constant_hot_state,
+    );
+    context.nock_context.profile = profile;
+    context.nock_context.jet_stats = jet_stats;
+    serve(&mut context)
+}
+
+/// Words of NockStack for the loom size the king passes, as the log2 of its size in bytes like
+/// vere's `--loom`, or [DEFAULT_STACK_SIZE] if it passes none
+pub(crate) fn stack_size(lom: Option<String>) -> io::Result<usize> {
+    let Some(lom) = lom else {
+        return Ok(DEFAULT_STACK_SIZE);
+    };
+    match lom.parse::<u32>() {
+        Ok(bex) if (20..=36).contains(&bex) => Ok(1 << (bex - 3)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "loom size is not a power of two from 20 to 36",
+        )),
+    }
+}
+
+/** Send %ripe, then handle writs from the king until it hangs up or sends %exit. */
+pub(crate) fn serve(context: &mut Context) -> io::Result<()> {
     context.ripe();
 
     // Can't use for loop because it borrows newt
@@ -354,21 +1015,44 @@ pub fn serf(constant_hot_state: &[HotEntry]) -> io::Result<()> {
                 let inner = slot(writ, 6)?.as_direct().unwrap();
                 match inner.data() {
                     tas!(b"cram") => {
-                        flog!(&mut context.nock_context, "\r %cram: not implemented");
+                        let eve = slot(writ, 7)?
+                            .as_atom()
+                            .map_err(|_e| {
+                                io::Error::new(io::ErrorKind::InvalidInput, "cram: bad event")
+                            })?
+                            .as_u64()
+                            .unwrap_or(u64::MAX);
+                        if eve != context.event_num {
+                            flog!(
+                                &mut context.nock_context,
+                                "\rserf: cram: at event {}, not {}",
+                                context.event_num,
+                                eve
+                            );
+                        } else if let Err(e) = context.cram(eve) {
+                            flog!(&mut context.nock_context, "\rserf: cram failed: {}", e);
+                        }
                     }
                     tas!(b"exit") => {
                         flog!(&mut context.nock_context, "\r %exit");
-                        std::process::exit(0);
+                        context.flog_live_counters();
+                        context.write_profile();
+                        context.report_jet_stats();
+                        return Ok(());
                     }
                     tas!(b"save") => {
                         // XX what is eve for?
+                        context.retain();
                         pma_sync();
                     }
-                    tas!(b"meld") => {
-                        flog!(&mut context.nock_context, "\r %meld: not implemented");
-                    }
-                    tas!(b"pack") => {
-                        flog!(&mut context.nock_context, "\r %pack: not implemented");
+                    tas!(b"meld") => unsafe {
+                        context.meld();
+                    },
+                    tas!(b"pack") => unsafe {
+                        context.pack();
+                    },
+                    tas!(b"mass") => {
+                        context.mass();
                     }
                     _ => {
                         flog!(&mut context.nock_context, "unknown live");
@@ -377,34 +1061,45 @@ pub fn serf(constant_hot_state: &[HotEntry]) -> io::Result<()> {
                 context.live();
             }
             tas!(b"peek") => {
+                let mil = mil(writ)?;
                 let ovo = slot(writ, 7)?;
-                let res = peek(&mut context, ovo);
-                context.peek_done(res);
+                match peek(context, mil, ovo) {
+                    Ok(res) => context.peek_done(res),
+                    Err(goof) => context.peek_bail(goof),
+                }
             }
             tas!(b"play") => {
                 let lit = slot(writ, 7)?;
                 if context.epoch == 0 && context.event_num == 0 {
                     // apply lifecycle to first batch
-                    play_life(&mut context, lit);
+                    play_life(context, lit);
                 } else {
-                    play_list(&mut context, lit);
+                    play_list(context, lit);
                 };
             }
             tas!(b"work") => {
-                //  XX: what is in slot 6? it's mil_w in Vere Serf
+                let mil = mil(writ)?;
                 let job = slot(writ, 7)?;
-                work(&mut context, job);
+                work(context, mil, job);
             }
             _ => panic!("got message with unknown tag {}", tag),
         };
 
         clear_interrupt();
+        context.flush_profile();
+
+        if JetStats::report_requested() {
+            context.report_jet_stats();
+        }
     }
 
     Ok(())
 }
 
-fn slam(context: &mut Context, axis: u64, ovo: Noun) -> Result<Noun, Error> {
+/** Slam an arm of arvo, interrupting it if it runs for longer than `mil` milliseconds (or never, if
+ *  `mil` is 0).
+ */
+fn slam(context: &mut Context, axis: u64, mil: u64, ovo: Noun) -> Result<Noun, Error> {
     let arvo = context.arvo;
     let stack = &mut context.nock_context.stack;
     let pul = T(stack, &[D(9), D(axis), D(0), D(2)]);
@@ -412,20 +1107,42 @@ fn slam(context: &mut Context, axis: u64, ovo: Noun) -> Result<Noun, Error> {
     let fol = T(stack, &[D(8), pul, D(9), D(2), D(10), sam, D(0), D(2)]);
     let sub = T(stack, &[arvo, ovo]);
 
-    interpret(&mut context.nock_context, sub, fol)
+    set_deadline(mil);
+    let res = interpret(&mut context.nock_context, sub, fol);
+    set_deadline(0);
+    res
 }
 
-fn peek(context: &mut Context, ovo: Noun) -> Noun {
-    if context.nock_context.trace_info.is_some() {
+/** Run a scry; process stack trace to goof if error.
+ */
+fn peek(context: &mut Context, mil: u64, ovo: Noun) -> Result<Noun, Noun> {
+    let slam_res = if context.nock_context.trace_info.is_some() {
         //  XX: way too many cases in the input to pull the actual vane, care, and path out
         let trace_name = "peek";
         let start = Instant::now();
-        let slam_res = slam(context, PEEK_AXIS, ovo);
-        write_serf_trace_safe(&mut context.nock_context, trace_name, start);
+        let slam_res = slam(context, PEEK_AXIS, mil, ovo);
+        write_serf_trace_safe(&mut context.nock_context, Category::Scry, trace_name, start);
 
-        slam_res.expect("peek error handling unimplemented")
+        slam_res
     } else {
-        slam(context, PEEK_AXIS, ovo).expect("peek error handling unimplemented")
+        slam(context, PEEK_AXIS, mil, ovo)
+    };
+
+    match slam_res {
+        Ok(res) => Ok(res),
+        Err(error) => match error {
+            Error::Deterministic(mote, traces) | Error::NonDeterministic(mote, traces) => {
+                Err(goof(context, mote, traces))
+            }
+            Error::ScryCrashed(traces) => Err(goof(context, Mote::Exit, traces)),
+            Error::ScryBlocked(_) => {
+                //  A scry from outside of virtual Nock has no handler to block on
+                let stack = &mut context.nock_context.stack;
+                let msg = tape(stack, "serf: peek: scry blocked");
+                let leaf = T(stack, &[D(tas!(b"leaf")), msg]);
+                Err(T(stack, &[D(Mote::Exit as u64), leaf, D(0)]))
+            }
+        },
     }
 }
 
@@ -441,19 +1158,25 @@ fn goof(context: &mut Context, mote: Mote, traces: Noun) -> Noun {
 /** Run slam; process stack trace to tang if error.
  *  Generate tracing events, if JSON tracing enabled.
  */
-fn soft(context: &mut Context, ovo: Noun, trace_name: Option<String>) -> Result<Noun, Noun> {
+fn soft(
+    context: &mut Context,
+    mil: u64,
+    ovo: Noun,
+    trace_name: Option<String>,
+) -> Result<Noun, Noun> {
     let slam_res = if context.nock_context.trace_info.is_some() {
         let start = Instant::now();
-        let slam_res = slam(context, POKE_AXIS, ovo);
+        let slam_res = slam(context, POKE_AXIS, mil, ovo);
         write_serf_trace_safe(
             &mut context.nock_context,
+            Category::Event,
             trace_name.as_ref().unwrap(),
             start,
         );
 
         slam_res
     } else {
-        slam(context, POKE_AXIS, ovo)
+        slam(context, POKE_AXIS, mil, ovo)
     };
 
     match slam_res {
@@ -470,6 +1193,16 @@ fn soft(context: &mut Context, ovo: Noun, trace_name: Option<String>) -> Result<
 }
 
 fn play_life(context: &mut Context, eve: Noun) {
+    match life(context, eve) {
+        Ok(()) => context.play_done(),
+        Err(goof) => context.play_bail(goof),
+    }
+}
+
+/** Run the lifecycle formula over the first batch of events, producing the initial Arvo.
+ *  Returns a goof if the lifecycle crashes.
+ */
+pub(crate) fn life(context: &mut Context, eve: Noun) -> Result<(), Noun> {
     let stack = &mut context.nock_context.stack;
     let sub = T(stack, &[D(0), D(3)]);
     let lyf = T(stack, &[D(2), sub, D(0), D(2)]);
@@ -477,7 +1210,12 @@ fn play_life(context: &mut Context, eve: Noun) {
         let trace_name = "boot";
         let start = Instant::now();
         let boot_res = interpret(&mut context.nock_context, eve, lyf);
-        write_serf_trace_safe(&mut context.nock_context, trace_name, start);
+        write_serf_trace_safe(
+            &mut context.nock_context,
+            Category::Event,
+            trace_name,
+            start,
+        );
 
         boot_res
     } else {
@@ -493,12 +1231,11 @@ fn play_life(context: &mut Context, eve: Noun) {
                 context.event_update(eved, arvo);
                 context.preserve_event_update_leftovers();
             }
-            context.play_done();
+            Ok(())
         }
         Err(error) => match error {
             Error::Deterministic(mote, traces) | Error::NonDeterministic(mote, traces) => {
-                let goof = goof(context, mote, traces);
-                context.play_bail(goof);
+                Err(goof(context, mote, traces))
             }
             Error::ScryBlocked(_) | Error::ScryCrashed(_) => {
                 panic!("serf: play: .^ invalid outside of virtual Nock")
@@ -507,7 +1244,17 @@ fn play_life(context: &mut Context, eve: Noun) {
     }
 }
 
-fn play_list(context: &mut Context, mut lit: Noun) {
+fn play_list(context: &mut Context, lit: Noun) {
+    match play(context, lit) {
+        Ok(()) => context.play_done(),
+        Err(goof) => context.play_bail(goof),
+    }
+}
+
+/** Apply a list of events on top of the current Arvo.
+ *  Returns a goof if an event crashes, leaving Arvo as of the previous event.
+ */
+pub(crate) fn play(context: &mut Context, mut lit: Noun) -> Result<(), Noun> {
     let mut eve = context.event_num;
     while let Ok(cell) = lit.as_cell() {
         let ovo = cell.head();
@@ -518,29 +1265,43 @@ fn play_list(context: &mut Context, mut lit: Noun) {
             None
         };
 
-        match soft(context, ovo, trace_name) {
-            Ok(res) => {
-                let arvo = res
-                    .as_cell()
-                    .expect("serf: work: +slam returned atom")
-                    .tail();
-                eve += 1;
-
-                unsafe {
-                    context.event_update(eve, arvo);
-                    context.nock_context.stack.preserve(&mut lit);
-                    context.preserve_event_update_leftovers();
+        let res = match soft(context, 0, ovo, trace_name) {
+            Ok(res) => res,
+            Err(goof) if is_meme(goof) => match unsafe {
+                context.save();
+                context.grow(cell.as_noun())
+            } {
+                // Retry the event on the larger stack
+                Some(rest) => {
+                    lit = rest;
+                    continue;
                 }
-            }
-            Err(goof) => {
-                return context.play_bail(goof);
-            }
+                None => return Err(goof),
+            },
+            Err(goof) => return Err(goof),
+        };
+        let arvo = res
+            .as_cell()
+            .expect("serf: work: +slam returned atom")
+            .tail();
+        eve += 1;
+
+        unsafe {
+            context.event_update(eve, arvo);
+            context.nock_context.stack.preserve(&mut lit);
+            context.preserve_event_update_leftovers();
         }
     }
-    context.play_done();
+    Ok(())
 }
 
-fn work(context: &mut Context, job: Noun) {
+/// Whether a goof is from running out of memory
+fn is_meme(goof: Noun) -> bool {
+    goof.slot(2)
+        .is_ok_and(|mote| unsafe { mote.raw_equals(D(Mote::Meme as u64)) })
+}
+
+fn work(context: &mut Context, mil: u64, job: Noun) {
     let trace_name = if context.nock_context.trace_info.is_some() {
         //  XX: good luck making this safe AND rust idiomatic!
         let wire = job.slot(6).expect("serf: work: job missing wire");
@@ -555,7 +1316,7 @@ fn work(context: &mut Context, job: Noun) {
         None
     };
 
-    match soft(context, job, trace_name) {
+    match soft(context, mil, job, trace_name) {
         Ok(res) => {
             let cell = res.as_cell().expect("serf: work: +slam returned atom");
             let mut fec = cell.head();
@@ -569,12 +1330,21 @@ fn work(context: &mut Context, job: Noun) {
             context.work_done(fec);
         }
         Err(goof) => {
-            work_swap(context, job, goof);
+            if is_meme(goof) {
+                // Retry the event on the larger stack
+                if let Some(job) = unsafe {
+                    context.save();
+                    context.grow(job)
+                } {
+                    return work(context, mil, job);
+                }
+            }
+            work_swap(context, mil, job, goof);
         }
     }
 }
 
-fn work_swap(context: &mut Context, job: Noun, goof: Noun) {
+fn work_swap(context: &mut Context, mil: u64, job: Noun, goof: Noun) {
     //  TODO: on decryption failure in aes_siv, should bail as fast as
     //  possible, without rendering stack trace or injecting crud event.  See
     //  c3__evil in vere.
@@ -600,7 +1370,7 @@ fn work_swap(context: &mut Context, job: Noun, goof: Noun) {
         None
     };
 
-    match soft(context, ovo, trace_name) {
+    match soft(context, mil, ovo, trace_name) {
         Ok(res) => {
             let cell = res.as_cell().expect("serf: work: crud +slam returned atom");
             let mut fec = cell.head();
@@ -648,11 +1418,409 @@ fn work_trace_name(stack: &mut NockStack, wire: Noun, vent: Atom) -> String {
     format!("work [{} {}]", wpc_str, vc_str)
 }
 
-fn slot(noun: Noun, axis: u64) -> io::Result<Noun> {
+pub(crate) fn slot(noun: Noun, axis: u64) -> io::Result<Noun> {
     noun.slot(axis)
         .map_err(|_e| io::Error::new(io::ErrorKind::InvalidInput, "Bad axis"))
 }
 
+/// The milliseconds allowed for a %peek or %work writ
+fn mil(writ: Noun) -> io::Result<u64> {
+    slot(writ, 6)?
+        .as_atom()
+        .map(|mil| mil.as_u64().unwrap_or(u64::MAX))
+        .map_err(|_e| io::Error::new(io::ErrorKind::InvalidInput, "mil is not an atom"))
+}
+
+/** Interrupt the running event, as SIGINT would, once `mil` milliseconds have passed. A `mil` of 0
+ *  disarms the deadline. Either way [DEADLINE_PASSED] is cleared: the watchdog only sets it while
+ *  holding the lock, so no deadline can fire once this returns until the next is armed.
+ */
+fn set_deadline(mil: u64) {
+    let (lock, cvar) = &**DEADLINE;
+    let mut at = lock.lock().expect("serf: deadline lock poisoned");
+    *at = if mil == 0 {
+        None
+    } else {
+        Instant::now().checked_add(Duration::from_millis(mil))
+    };
+    DEADLINE_PASSED.store(false, Ordering::Relaxed);
+    cvar.notify_one();
+}
+
+/// Whether the running computation should stop, for SIGINT or for its deadline passing
+#[inline]
+pub fn interrupted(terminator: &AtomicBool, deadline: &AtomicBool) -> bool {
+    terminator.load(Ordering::Relaxed) || deadline.load(Ordering::Relaxed)
+}
+
+/// Body of the deadline watchdog thread: sleep until the armed deadline and then set
+/// [DEADLINE_PASSED]
+fn watch_deadline(deadline: &(Mutex<Option<Instant>>, Condvar)) {
+    let (lock, cvar) = deadline;
+    let mut at = lock.lock().expect("serf: deadline lock poisoned");
+    loop {
+        at = match *at {
+            None => cvar.wait(at).expect("serf: deadline lock poisoned"),
+            Some(instant) => {
+                let now = Instant::now();
+                if now >= instant {
+                    DEADLINE_PASSED.store(true, Ordering::Relaxed);
+                    *at = None;
+                    at
+                } else {
+                    cvar.wait_timeout(at, instant - now)
+                        .expect("serf: deadline lock poisoned")
+                        .0
+                }
+            }
+        };
+    }
+}
+
 fn clear_interrupt() {
     (*TERMINATOR).store(false, Ordering::Relaxed);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jets::hot::URBIT_HOT_STATE;
+    use crate::jets::util::test::assert_noun_eq;
+    use crate::newt::{read_frame, write_frame};
+    use crate::noun::CellMemory;
+    use crate::persist::{pma_contains, pma_hash_cons_stats, pma_open_for_test};
+
+    unsafe fn add_one(_stack: &mut NockStack, handle: u64) -> u64 {
+        handle + 1
+    }
+
+    unsafe fn double(_stack: &mut NockStack, handle: u64) -> u64 {
+        handle * 2
+    }
+
+    #[test]
+    fn migrations_chain() {
+        let mut stack = NockStack::new(8 << 10 << 10, 0);
+        let migrations: &[Migration] = &[add_one, double];
+        unsafe {
+            assert_eq!(migrate_handle(&mut stack, migrations, 1, 5), 12);
+            assert_eq!(migrate_handle(&mut stack, migrations, 2, 5), 10);
+            assert_eq!(migrate_handle(&mut stack, migrations, 3, 5), 5);
+            let current = PMA_CURRENT_SNAPSHOT_VERSION;
+            assert_eq!(
+                migrate_handle(&mut stack, SNAPSHOT_MIGRATIONS, current, 5),
+                5
+            );
+        }
+    }
+
+    #[test]
+    fn deadline() {
+        // The deadline interrupts without arming SIGINT's shutdown, and disarming it clears it
+        set_deadline(1);
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(DEADLINE_PASSED.load(Ordering::Relaxed));
+        assert!(!TERMINATOR.load(Ordering::Relaxed));
+        assert!(interrupted(&TERMINATOR, &DEADLINE_PASSED));
+        set_deadline(0);
+        assert!(!interrupted(&TERMINATOR, &DEADLINE_PASSED));
+
+        set_deadline(60_000);
+        set_deadline(0);
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(!DEADLINE_PASSED.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn loom_stack_size() {
+        assert_eq!(stack_size(None).unwrap(), DEFAULT_STACK_SIZE);
+        // 2 GiB, vere's default
+        assert_eq!(stack_size(Some("31".to_string())).unwrap(), 256 << 10 << 10);
+        assert!(stack_size(Some("19".to_string())).is_err());
+        assert!(stack_size(Some("37".to_string())).is_err());
+        assert!(stack_size(Some("big".to_string())).is_err());
+    }
+
+    /// A context with an empty state, in the shared test PMA
+    fn test_context(pier_path: PathBuf) -> Context {
+        let stack = NockStack::new(1 << 20, 0);
+        Context::new(
+            pier_path,
+            stack,
+            Newt::new_mock(),
+            None,
+            None,
+            URBIT_HOT_STATE,
+        )
+    }
+
+    /// An Arvo whose +peek is `arm`, run with the scry request as its sample
+    fn peek_arvo(stack: &mut NockStack, arm: Noun) -> Noun {
+        // PEEK_AXIS is 22: head, tail, tail, head
+        let gate = T(stack, &[arm, D(0), D(0)]);
+        let peek = T(stack, &[D(1), gate]);
+        let head = T(stack, &[D(0), D(0), peek, D(0)]);
+        T(stack, &[head, D(0)])
+    }
+
+    #[test]
+    fn peek_bail() {
+        let path = std::env::temp_dir().join(format!("ares-test-peek-{}", std::process::id()));
+        let mut context = test_context(std::env::temp_dir());
+        let stack = &mut context.nock_context.stack;
+
+        // [11 [%mean 1 %oops] 0 0]
+        let oops = T(stack, &[D(1), D(tas!(b"oops"))]);
+        let mean = T(stack, &[D(tas!(b"mean")), oops]);
+        let crash = T(stack, &[D(0), D(0)]);
+        let arm = T(stack, &[D(11), mean, crash]);
+        context.arvo = peek_arvo(stack, arm);
+
+        // [%peek mil=0 sam=~]
+        let writ = T(stack, &[D(tas!(b"peek")), D(0), D(0)]);
+        let jammed = jam(stack, writ);
+        let mut writs = Vec::new();
+        write_frame(&mut writs, 0, jammed.as_bytes()).unwrap();
+        context.nock_context.newt =
+            Newt::from_io(io::Cursor::new(writs), File::create(&path).unwrap());
+        serve(&mut context).unwrap();
+
+        // The serf survives to answer [%peek %bail [%exit [%leaf "oops"] ~]], after its %ripe
+        let stack = &mut context.nock_context.stack;
+        let mut pleas = File::open(&path).unwrap();
+        read_frame(stack, &mut pleas).unwrap().expect("no %ripe");
+        let (_, jammed) = read_frame(stack, &mut pleas).unwrap().expect("no %peek");
+        std::fs::remove_file(&path).unwrap();
+        let plea = cue(stack, jammed);
+        let msg = tape(stack, "oops");
+        let leaf = T(stack, &[D(tas!(b"leaf")), msg]);
+        let expected = T(
+            stack,
+            &[
+                D(tas!(b"peek")),
+                D(tas!(b"bail")),
+                D(tas!(b"exit")),
+                leaf,
+                D(0),
+            ],
+        );
+        assert_noun_eq(stack, plea, expected);
+    }
+
+    #[test]
+    fn peek_blocked() {
+        let mut context = test_context(std::env::temp_dir());
+        let stack = &mut context.nock_context.stack;
+
+        // A scry handler which always blocks, producing ~, and a +peek which scries
+        let block = T(stack, &[D(1), D(0)]);
+        let handler = T(stack, &[block, D(0), D(0)]);
+        context.nock_context.scry_stack = T(stack, &[handler, D(0)]);
+        let zero = T(stack, &[D(1), D(0)]);
+        let arm = T(stack, &[D(12), zero, zero]);
+        context.arvo = peek_arvo(stack, arm);
+
+        let goof = peek(&mut context, 0, D(0)).unwrap_err();
+        let stack = &mut context.nock_context.stack;
+        let msg = tape(stack, "serf: peek: scry blocked");
+        let leaf = T(stack, &[D(tas!(b"leaf")), msg]);
+        let expected = T(stack, &[D(tas!(b"exit")), leaf, D(0)]);
+        assert_noun_eq(stack, goof, expected);
+    }
+
+    #[test]
+    fn grow_stack() {
+        let _pma = pma_open_for_test();
+        let mut context = test_context(std::env::temp_dir());
+        context.arvo = T(&mut context.nock_context.stack, &[D(1), D(2)]);
+        let job = T(&mut context.nock_context.stack, &[D(3), D(4), D(5)]);
+
+        let job = unsafe {
+            context.save();
+            context.grow(job)
+        }
+        .unwrap();
+        let stack = &mut context.nock_context.stack;
+        assert_eq!(stack.size(), 1 << 21);
+        let expected = T(stack, &[D(3), D(4), D(5)]);
+        assert_noun_eq(stack, job, expected);
+        // The job was copied onto the new stack, and arvo into the PMA
+        unsafe {
+            assert!(stack.is_in_frame(job.as_cell().unwrap().to_raw_pointer()));
+            assert!(pma_contains(
+                context.arvo.as_cell().unwrap().to_raw_pointer(),
+                1
+            ));
+        }
+        let expected = T(stack, &[D(1), D(2)]);
+        assert_noun_eq(stack, context.arvo, expected);
+    }
+
+    #[test]
+    fn hash_cons_save() {
+        let _pma = pma_open_for_test();
+        let mut context = test_context(std::env::temp_dir());
+        context.hash_cons = true;
+        let stack = &mut context.nock_context.stack;
+        let a = T(stack, &[D(1), D(2)]);
+        let b = T(stack, &[D(1), D(2)]);
+        let c = T(stack, &[D(1), D(2)]);
+        let c = T(stack, &[D(3), c]);
+        context.arvo = T(stack, &[a, b, c]);
+
+        let before = pma_hash_cons_stats();
+        unsafe { context.save() };
+        let after = pma_hash_cons_stats();
+        let cell = size_of::<CellMemory>() as u64;
+        assert_eq!(after.plain - before.plain, 6 * cell);
+        assert_eq!(after.consed - before.consed, 4 * cell);
+
+        // The three copies of [1 2] are one in the PMA
+        let a = slot(context.arvo, 2).unwrap();
+        unsafe {
+            assert!(pma_contains(a.as_cell().unwrap().to_raw_pointer(), 1));
+            assert!(a.raw_equals(slot(context.arvo, 6).unwrap()));
+            assert!(a.raw_equals(slot(context.arvo, 15).unwrap()));
+        }
+        let expected = T(&mut context.nock_context.stack, &[D(1), D(2)]);
+        assert_noun_eq(&mut context.nock_context.stack, a, expected);
+    }
+
+    #[test]
+    fn hash_cons_pack() {
+        let _pma = pma_open_for_test();
+        let mut context = test_context(std::env::temp_dir());
+        let stack = &mut context.nock_context.stack;
+        let a = T(stack, &[D(1), D(2)]);
+        let b = T(stack, &[D(1), D(2)]);
+        context.arvo = T(stack, &[a, b]);
+        unsafe { context.save() };
+
+        // Packing unifies the nouns already in the PMA, and saves a table of them
+        context.hash_cons = true;
+        let before = pma_hash_cons_stats();
+        unsafe { context.pack() };
+        let after = pma_hash_cons_stats();
+        let cell = size_of::<CellMemory>() as u64;
+        assert_eq!(after.plain - before.plain, 3 * cell);
+        assert_eq!(after.consed - before.consed, 2 * cell);
+        let a = slot(context.arvo, 2).unwrap();
+        unsafe { assert!(a.raw_equals(slot(context.arvo, 3).unwrap())) };
+        assert!(pma_meta_get(BTMetaField::HashCons as usize) != 0);
+
+        // A new noun equal to one in the table is not copied again
+        let stack = &mut context.nock_context.stack;
+        let c = T(stack, &[D(1), D(2)]);
+        context.arvo = T(stack, &[D(3), c]);
+        let before = pma_hash_cons_stats();
+        unsafe { context.save() };
+        let after = pma_hash_cons_stats();
+        assert_eq!(after.plain - before.plain, 2 * cell);
+        assert_eq!(after.consed - before.consed, cell);
+        unsafe { assert!(a.raw_equals(slot(context.arvo, 3).unwrap())) };
+
+        // Packing without hash-consing drops the table
+        context.hash_cons = false;
+        unsafe { context.pack() };
+        assert_eq!(pma_meta_get(BTMetaField::HashCons as usize), 0);
+    }
+
+    #[test]
+    fn meld_dedups() {
+        let _pma = pma_open_for_test();
+        let mut context = test_context(std::env::temp_dir());
+        let stack = &mut context.nock_context.stack;
+        let a = T(stack, &[D(1), D(2)]);
+        let b = T(stack, &[D(1), D(2)]);
+        let big = Atom::new(stack, u64::MAX).as_noun();
+        let c = T(stack, &[big, D(2)]);
+        let big = Atom::new(stack, u64::MAX).as_noun();
+        let d = T(stack, &[big, D(2)]);
+        context.arvo = T(stack, &[a, b, c, d]);
+        unsafe { context.save() };
+        let a = slot(context.arvo, 2).unwrap();
+        assert!(unsafe { !a.raw_equals(slot(context.arvo, 6).unwrap()) });
+
+        unsafe { context.meld() };
+        let arvo = context.arvo;
+        let a = slot(arvo, 2).unwrap();
+        let c = slot(arvo, 14).unwrap();
+        unsafe {
+            assert!(pma_contains(arvo.as_cell().unwrap().to_raw_pointer(), 1));
+            assert!(pma_contains(a.as_cell().unwrap().to_raw_pointer(), 1));
+            assert!(a.raw_equals(slot(arvo, 6).unwrap()));
+            assert!(c.raw_equals(slot(arvo, 15).unwrap()));
+            assert!(!a.raw_equals(c));
+        }
+
+        let stack = &mut context.nock_context.stack;
+        let a = T(stack, &[D(1), D(2)]);
+        let big = Atom::new(stack, u64::MAX).as_noun();
+        let c = T(stack, &[big, D(2)]);
+        let expected = T(stack, &[a, a, c, c]);
+        assert_noun_eq(stack, arvo, expected);
+    }
+
+    #[test]
+    fn pack_preserves_state() {
+        let _pma = pma_open_for_test();
+        let mut context = test_context(std::env::temp_dir());
+        let stack = &mut context.nock_context.stack;
+        let big = Atom::new(stack, u64::MAX).as_noun();
+        context.arvo = T(stack, &[D(1), big]);
+        context.epoch = 7;
+        context.event_num = 8;
+        unsafe { context.save() };
+        context.retain();
+
+        let stack = &mut context.nock_context.stack;
+        context.arvo = T(stack, &[D(2), D(3)]);
+        context.event_num = 9;
+        unsafe { context.save() };
+        let old_arvo = unsafe { context.arvo.as_cell().unwrap().to_raw_pointer() };
+
+        unsafe { context.pack() };
+        let stack = &mut context.nock_context.stack;
+        assert_eq!((context.epoch, context.event_num), (7, 9));
+        unsafe {
+            let arvo = context.arvo.as_cell().unwrap().to_raw_pointer();
+            assert!(pma_contains(arvo, 1));
+            assert!(arvo != old_arvo);
+        }
+        let expected = T(stack, &[D(2), D(3)]);
+        assert_noun_eq(stack, context.arvo, expected);
+        unsafe {
+            let current = Snapshot::handle_from_u64(pma_meta_get(BTMetaField::Snapshot as usize));
+            assert!((*current.0).arvo.raw_equals(context.arvo));
+        }
+
+        // The retained snapshot was carried over too
+        let retained = retained_snapshots();
+        assert_eq!(retained.len(), 1);
+        assert_eq!((retained[0].epoch(), retained[0].event_num()), (7, 8));
+        let big = Atom::new(stack, u64::MAX).as_noun();
+        let expected = T(stack, &[D(1), big]);
+        assert_noun_eq(stack, unsafe { (*retained[0].0).arvo }, expected);
+        set_retained_snapshots(&[]);
+    }
+
+    #[test]
+    fn cram_then_load() {
+        let _pma = pma_open_for_test();
+        let pier_path = std::env::temp_dir().join(format!("ares-test-cram-{}", std::process::id()));
+        let mut context = test_context(pier_path.clone());
+        let stack = &mut context.nock_context.stack;
+        let big = Atom::new(stack, u64::MAX).as_noun();
+        let inner = T(stack, &[big, D(2)]);
+        context.arvo = T(stack, &[D(1), inner, inner]);
+        unsafe { context.save() };
+
+        context.cram(5).unwrap();
+        let bytes = std::fs::read(pier_path.join(".urb").join("roc").join("5.jam")).unwrap();
+        std::fs::remove_dir_all(&pier_path).unwrap();
+        let stack = &mut context.nock_context.stack;
+        let jammed = read_atom(stack, &bytes);
+        let arvo = cue(stack, jammed);
+        assert_noun_eq(stack, arvo, context.arvo);
+    }
+}
@@ -2,24 +2,420 @@ use crate::flog;
 use crate::interpreter::Context;
 use crate::jets::bits::util::rap;
 use crate::jets::form::util::scow;
+use crate::jets::{JetErr, Result as JetResult};
 use crate::mem::NockStack;
 use crate::mug::met3_usize;
 use crate::noun::{Atom, DirectAtom, IndirectAtom, Noun};
 use ares_macros::tas;
+use assert_no_alloc::permit_alloc;
 use either::Either::*;
-use json::object;
+use json::{object, JsonValue};
+use signal_hook::consts::{SIGPROF, SIGUSR1};
+use std::collections::HashMap;
 use std::fs::{create_dir_all, File};
-use std::io::{Error, Write};
+use std::io::{BufWriter, Error, ErrorKind, Seek, SeekFrom, Write};
 use std::path::PathBuf;
 use std::result::Result;
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 crate::gdb!();
 
+/// A kind of span or counter in the trace, which is recorded only if the configuration asks for it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+    /// Events, and boot, applied by the serf
+    Event,
+    /// Calls into cold-registered cores
+    Nock,
+    /// Jet calls, and the jet statistics
+    Jet,
+    /// Scries into Arvo from the king
+    Scry,
+    /// Preservation of the event's results at the end of the event
+    Gc,
+}
+
+impl Category {
+    const ALL: [Category; 5] = [
+        Category::Event,
+        Category::Nock,
+        Category::Jet,
+        Category::Scry,
+        Category::Gc,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Event => "event",
+            Category::Nock => "nock",
+            Category::Jet => "jet",
+            Category::Scry => "scry",
+            Category::Gc => "gc",
+        }
+    }
+
+    fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+/// The format of the trace file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// A JSON array of Chrome trace events, for chrome://tracing and Perfetto alike
+    Chrome,
+    /// Perfetto's protobuf trace format
+    Perfetto,
+}
+
+/// What goes into the trace file, and how it is written
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceConfig {
+    /// Spans shorter than this are dropped, except for events
+    pub threshold: Duration,
+    /// Bitmap of the recorded categories
+    categories: u8,
+    /// If set, only calls and jets whose paths, as written in the trace, start with this
+    pub prefix: Option<String>,
+    pub format: TraceFormat,
+    /// How often the trace is flushed to the file
+    pub flush_interval: Duration,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        TraceConfig {
+            // Same threshold used in vere
+            threshold: Duration::from_micros(33),
+            categories: Category::ALL.iter().fold(0, |bits, cat| bits | cat.bit()),
+            prefix: None,
+            format: TraceFormat::Chrome,
+            flush_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl TraceConfig {
+    /// Parse a comma-separated list of settings, each overriding the default:
+    ///
+    /// - `threshold=<us>`: the threshold in microseconds
+    /// - `categories=<cat>+<cat>...`: the categories to record, of `event`, `nock`, `jet`, `scry`
+    ///   and `gc`
+    /// - `prefix=<path>`: the path prefix, like `/add`
+    /// - `format=chrome` or `format=perfetto`
+    /// - `flush=<ms>`: the flush interval in milliseconds
+    pub fn parse(spec: &str) -> Result<Self, Error> {
+        let invalid = |setting: &str| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("trace: bad setting {}", setting),
+            )
+        };
+        let mut config = TraceConfig::default();
+        for setting in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|setting| !setting.is_empty())
+        {
+            let (key, value) = setting.split_once('=').ok_or_else(|| invalid(setting))?;
+            match key {
+                "threshold" => {
+                    let us = value.parse().map_err(|_| invalid(setting))?;
+                    config.threshold = Duration::from_micros(us);
+                }
+                "categories" => {
+                    config.categories = 0;
+                    for name in value.split('+').filter(|name| !name.is_empty()) {
+                        let cat = Category::ALL
+                            .iter()
+                            .copied()
+                            .find(|cat| cat.name() == name)
+                            .ok_or_else(|| invalid(setting))?;
+                        config.categories |= cat.bit();
+                    }
+                }
+                "prefix" => config.prefix = Some(value.to_string()),
+                "format" => {
+                    config.format = match value {
+                        "chrome" => TraceFormat::Chrome,
+                        "perfetto" => TraceFormat::Perfetto,
+                        _ => return Err(invalid(setting)),
+                    }
+                }
+                "flush" => {
+                    let ms = value.parse().map_err(|_| invalid(setting))?;
+                    config.flush_interval = Duration::from_millis(ms);
+                }
+                _ => return Err(invalid(setting)),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Read the configuration from `ARES_TRACE`, if it is set
+    pub fn from_env() -> Result<Option<Self>, Error> {
+        match std::env::var("ARES_TRACE") {
+            Ok(spec) => TraceConfig::parse(&spec).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn records(&self, cat: Category) -> bool {
+        self.categories & cat.bit() != 0
+    }
+
+    /// Whether a call or jet with this path is recorded
+    pub fn matches(&self, path: &str) -> bool {
+        match &self.prefix {
+            Some(prefix) => path.starts_with(prefix.as_str()),
+            None => true,
+        }
+    }
+}
+
 pub struct TraceInfo {
-    pub file: File,
+    out: BufWriter<File>,
     pub pid: u32,
     pub process_start: Instant,
+    pub config: TraceConfig,
+    /// Whether anything was written yet, so that a JSON trace needs a separator before the next
+    written: bool,
+    last_flush: Instant,
+    /// Whether the JSON array was closed
+    finished: bool,
+}
+
+// Track uuids for Perfetto
+const PROCESS_TRACK: u64 = 1;
+const THREAD_TRACK: u64 = 2;
+
+// TracePacket fields
+const PACKET_TIMESTAMP: u64 = 8;
+const PACKET_SEQUENCE_ID: u64 = 10;
+const PACKET_TRACK_EVENT: u64 = 11;
+const PACKET_SEQUENCE_FLAGS: u64 = 13;
+const PACKET_TRACK_DESCRIPTOR: u64 = 60;
+
+// TrackEvent fields, and its types
+const EVENT_DEBUG_ANNOTATIONS: u64 = 4;
+const EVENT_TYPE: u64 = 9;
+const EVENT_TRACK_UUID: u64 = 11;
+const EVENT_CATEGORIES: u64 = 22;
+const EVENT_NAME: u64 = 23;
+const SLICE_BEGIN: u64 = 1;
+const SLICE_END: u64 = 2;
+const INSTANT: u64 = 3;
+
+/// A debug annotation of a track event, with an unsigned value
+fn annotation(key: &str, value: u64) -> Proto {
+    Proto::default().bytes(10, key.as_bytes()).uint(3, value)
+}
+
+/// A protobuf message, encoded as it is built
+#[derive(Default)]
+struct Proto(Vec<u8>);
+
+impl Proto {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    fn uint(mut self, field: u64, value: u64) -> Self {
+        self.varint(field << 3);
+        self.varint(value);
+        self
+    }
+
+    fn bytes(mut self, field: u64, bytes: &[u8]) -> Self {
+        self.varint(field << 3 | 2);
+        self.varint(bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    fn message(self, field: u64, message: Proto) -> Self {
+        self.bytes(field, &message.0)
+    }
+}
+
+impl TraceInfo {
+    fn new(file: File, config: TraceConfig) -> Result<TraceInfo, Error> {
+        let now = Instant::now();
+        let mut info = TraceInfo {
+            out: BufWriter::new(file),
+            pid: std::process::id(),
+            process_start: now,
+            config,
+            written: false,
+            last_flush: now,
+            finished: false,
+        };
+        if info.config.format == TraceFormat::Chrome {
+            info.out.write_all("[ ".as_bytes())?;
+        }
+        Ok(info)
+    }
+
+    fn since_start(&self, time: Instant) -> Duration {
+        time.saturating_duration_since(self.process_start)
+    }
+
+    fn write_json(&mut self, obj: JsonValue) -> Result<(), Error> {
+        if self.written {
+            self.out.write_all(",\n".as_bytes())?;
+        }
+        self.written = true;
+        obj.write(&mut self.out)?;
+        self.flush_if_due()
+    }
+
+    /// Write a packet with the sequence all our packets are on
+    fn write_packet(&mut self, packet: Proto) -> Result<(), Error> {
+        let mut packet = packet.uint(PACKET_SEQUENCE_ID, 1);
+        if !self.written {
+            // Incremental state cleared
+            packet = packet.uint(PACKET_SEQUENCE_FLAGS, 1);
+        }
+        self.written = true;
+        // Each packet is a `packet` field of the Trace message the file holds
+        let trace = Proto::default().message(1, packet);
+        self.out.write_all(&trace.0)?;
+        self.flush_if_due()
+    }
+
+    fn write_track_event(&mut self, time: Instant, event: Proto) -> Result<(), Error> {
+        let timestamp = self.since_start(time).as_nanos() as u64;
+        self.write_packet(Proto::default().uint(PACKET_TIMESTAMP, timestamp).message(
+            PACKET_TRACK_EVENT,
+            event.uint(EVENT_TRACK_UUID, THREAD_TRACK),
+        ))
+    }
+
+    /// Write a span of `cat` from `start` until now, unless the configuration drops it
+    pub fn span(&mut self, cat: Category, name: &str, start: Instant) -> Result<(), Error> {
+        let dur = Instant::now().saturating_duration_since(start);
+        self.span_with_args(cat, name, start, dur, &[])
+    }
+
+    /// Write a span of `cat` lasting `dur` from `start`, annotated with `args`, unless the
+    /// configuration drops it
+    pub fn span_with_args(
+        &mut self,
+        cat: Category,
+        name: &str,
+        start: Instant,
+        dur: Duration,
+        args: &[(&str, u64)],
+    ) -> Result<(), Error> {
+        if !self.config.records(cat)
+            || (cat != Category::Event && dur < self.config.threshold)
+            || (matches!(cat, Category::Nock | Category::Jet) && !self.config.matches(name))
+        {
+            return Ok(());
+        }
+        permit_alloc(|| match self.config.format {
+            TraceFormat::Chrome => {
+                let mut obj = object! {
+                    cat: cat.name(),
+                    name: name,
+                    ph: "X",
+                    pid: self.pid,
+                    tid: 1,
+                    ts: self.since_start(start).as_micros() as f64,
+                    dur: dur.as_micros() as f64,
+                };
+                for (key, value) in args {
+                    obj["args"][*key] = (*value).into();
+                }
+                self.write_json(obj)
+            }
+            TraceFormat::Perfetto => {
+                let mut event = Proto::default()
+                    .uint(EVENT_TYPE, SLICE_BEGIN)
+                    .bytes(EVENT_CATEGORIES, cat.name().as_bytes())
+                    .bytes(EVENT_NAME, name.as_bytes());
+                for (key, value) in args {
+                    event = event.message(EVENT_DEBUG_ANNOTATIONS, annotation(key, *value));
+                }
+                self.write_track_event(start, event)?;
+                self.write_track_event(start + dur, Proto::default().uint(EVENT_TYPE, SLICE_END))
+            }
+        })
+    }
+
+    /// Write named counters of `cat` at the current time
+    pub fn counters(
+        &mut self,
+        cat: Category,
+        name: &str,
+        counters: &[(&str, u64)],
+    ) -> Result<(), Error> {
+        if !self.config.records(cat) {
+            return Ok(());
+        }
+        let now = Instant::now();
+        permit_alloc(|| match self.config.format {
+            TraceFormat::Chrome => {
+                let mut args = JsonValue::new_object();
+                for (key, value) in counters {
+                    args[*key] = (*value).into();
+                }
+                self.write_json(object! {
+                    cat: cat.name(),
+                    name: name,
+                    ph: "C",
+                    pid: self.pid,
+                    tid: 1,
+                    ts: self.since_start(now).as_micros() as f64,
+                    args: args,
+                })
+            }
+            TraceFormat::Perfetto => {
+                let mut event = Proto::default()
+                    .uint(EVENT_TYPE, INSTANT)
+                    .bytes(EVENT_CATEGORIES, cat.name().as_bytes())
+                    .bytes(EVENT_NAME, name.as_bytes());
+                for (key, value) in counters {
+                    event = event.message(EVENT_DEBUG_ANNOTATIONS, annotation(key, *value));
+                }
+                self.write_track_event(now, event)
+            }
+        })
+    }
+
+    fn flush_if_due(&mut self) -> Result<(), Error> {
+        if self.last_flush.elapsed() >= self.config.flush_interval {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.last_flush = Instant::now();
+        self.out.flush()
+    }
+
+    /// Close the JSON array, and flush. Nothing can be written after this.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        if !self.finished {
+            self.finished = true;
+            if self.config.format == TraceFormat::Chrome {
+                self.out.write_all("\n]\n".as_bytes())?;
+            }
+        }
+        self.flush()
+    }
+}
+
+impl Drop for TraceInfo {
+    fn drop(&mut self) {
+        let _ = permit_alloc(|| self.finish());
+    }
 }
 
 pub struct TraceStack {
@@ -28,79 +424,448 @@ pub struct TraceStack {
     pub next: *const TraceStack,
 }
 
-pub fn create_trace_file(pier_path: PathBuf) -> Result<TraceInfo, Error> {
-    let mut trace_dir_path = pier_path.clone();
-    trace_dir_path.push(".urb");
-    trace_dir_path.push("put");
-    trace_dir_path.push("trace");
-    create_dir_all(&trace_dir_path)?;
+/// Set by the profiling timer when the interpreter should take a sample
+static SAMPLE: AtomicBool = AtomicBool::new(false);
 
-    let trace_path: PathBuf;
-    let mut trace_idx = 0u32;
-    loop {
-        let mut prospective_path = trace_dir_path.clone();
-        prospective_path.push(format!("{}.json", trace_idx));
+/// How often the profiling timer asks for a sample, in microseconds of CPU time
+const SAMPLE_INTERVAL: i64 = 10_000;
 
-        if prospective_path.exists() {
-            trace_idx += 1;
+/// How often the profile is written out between writs, so that a serf which is killed loses little
+const PROFILE_FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+// Not bound by libc on every platform
+extern "C" {
+    fn setitimer(
+        which: libc::c_int,
+        new_value: *const libc::itimerval,
+        old_value: *mut libc::itimerval,
+    ) -> libc::c_int;
+}
+
+/// A sampling profile of the Nock being run, by the cold paths of the calls it is nested in, written
+/// out as folded stacks for flamegraph tooling
+pub struct Profile {
+    file: Option<File>,
+    stacks: HashMap<String, u64>,
+    /// Samples taken just after a jet returned, so counted against the jet
+    pub jet_samples: u64,
+    /// Samples taken while running raw Nock
+    pub nock_samples: u64,
+    /// When the folded stacks were last written out
+    written: Instant,
+}
+
+impl Profile {
+    /// A profile to be written to `file`, which is sampled only on [request_sample]
+    pub fn new(file: Option<File>) -> Profile {
+        Profile {
+            file,
+            stacks: HashMap::new(),
+            jet_samples: 0,
+            nock_samples: 0,
+            written: Instant::now(),
+        }
+    }
+
+    /// A profile to be written to `.urb/put/profile` of the pier, sampled on a timer signal
+    pub fn create(pier_path: PathBuf) -> Result<Profile, Error> {
+        let file = create_put_file(pier_path, "profile", "folded")?;
+        unsafe {
+            signal_hook::low_level::register(SIGPROF, request_sample)?;
+            let interval = libc::timeval {
+                tv_sec: 0,
+                tv_usec: SAMPLE_INTERVAL as libc::suseconds_t,
+            };
+            let timer = libc::itimerval {
+                it_interval: interval,
+                it_value: interval,
+            };
+            if setitimer(libc::ITIMER_PROF, &timer, std::ptr::null_mut()) != 0 {
+                return Err(Error::last_os_error());
+            }
+        }
+        Ok(Profile::new(Some(file)))
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.jet_samples + self.nock_samples
+    }
+
+    /// The samples as folded stacks, one `frame;frame;... count` line per distinct stack
+    pub fn folded(&self) -> String {
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort();
+        stacks
+            .into_iter()
+            .map(|(stack, count)| format!("{} {}\n", stack, count))
+            .collect()
+    }
+
+    /// Write the folded stacks out, replacing what was written before
+    pub fn write(&mut self) -> Result<(), Error> {
+        let folded = self.folded();
+        if let Some(file) = self.file.as_mut() {
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(folded.as_bytes())?;
+            file.flush()?;
+        }
+        self.written = Instant::now();
+        Ok(())
+    }
+
+    /// Write the folded stacks out if they were last written [PROFILE_FLUSH_INTERVAL] ago or more
+    pub fn flush(&mut self) -> Result<(), Error> {
+        if self.written.elapsed() >= PROFILE_FLUSH_INTERVAL {
+            self.write()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Profile {
+    /// Write the folded stacks out on the way down, whether the king sent %exit, hung up, or the
+    /// serf panicked
+    fn drop(&mut self) {
+        let _ = self.write();
+    }
+}
+
+/// Ask the interpreter for a sample at its next safe point. Async-signal-safe.
+pub fn request_sample() {
+    SAMPLE.store(true, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn sample_requested() -> bool {
+    SAMPLE.load(Ordering::Relaxed)
+}
+
+/// Record a sample of the calls the interpreter is in, from the trace stack of the current frame,
+/// and the path of the jet which just returned, if it was a jet which ran
+///
+/// # Safety
+///
+/// The current frame must have a trace stack, as within [crate::interpreter::interpret].
+pub unsafe fn sample(context: &mut Context, jet: Option<Noun>) {
+    SAMPLE.store(false, Ordering::Relaxed);
+    let Some(profile) = context.profile.as_mut() else {
+        return;
+    };
+    let stack = &mut context.stack;
+
+    let mut trace_stack = *(stack.local_noun_pointer(1) as *const *const TraceStack);
+    permit_alloc(|| {
+        let mut paths = Vec::new();
+        while !trace_stack.is_null() {
+            paths.push((*trace_stack).path);
+            trace_stack = (*trace_stack).next;
+        }
+
+        let mut folded = String::from("nock");
+        let mut last = None;
+        // Outermost first, and once for a run of tail calls into the same arm
+        for path in paths.into_iter().rev() {
+            if last.is_some_and(|last: Noun| last.raw_equals(path)) {
+                continue;
+            }
+            last = Some(path);
+            folded.push(';');
+            folded.push_str(&cord_to_string(path_to_cord(stack, path)));
+        }
+        match jet {
+            Some(path) => {
+                folded.push(';');
+                folded.push_str(&cord_to_string(path_to_cord(stack, path)));
+                folded.push_str("_[j]");
+                profile.jet_samples += 1;
+            }
+            None => profile.nock_samples += 1,
+        }
+        *profile.stacks.entry(folded).or_insert(0) += 1;
+    });
+}
+
+fn cord_to_string(cord: Atom) -> String {
+    String::from_utf8_lossy(&cord.as_bytes()[..met3_usize(cord)]).into_owned()
+}
+
+/// Set by a signal asking for the jet statistics to be printed
+static REPORT: AtomicBool = AtomicBool::new(false);
+
+/// Calls into the core at one cold path
+#[derive(Clone, Default)]
+pub struct JetStat {
+    pub name: String,
+    /// Calls which a jet computed
+    pub hits: u64,
+    /// Calls which a jet punted back to the raw Nock
+    pub punts: u64,
+    /// Calls which a jet failed
+    pub fails: u64,
+    /// Calls run as raw Nock because there is no jet for the core
+    pub unjetted: u64,
+    /// Time in the jet, and in the raw Nock of the calls it did not compute, exclusive of the calls
+    /// made from them
+    pub time: Duration,
+}
+
+/// Statistics on calls into cold-registered cores, by cold path, to tell which jets fire and which
+/// cores run unjetted. Paths are keyed by the bytes of their cords.
+#[derive(Default)]
+pub struct JetStats {
+    stats: HashMap<Vec<u8>, JetStat>,
+    /// When time was last given to a call
+    last: Option<Instant>,
+}
+
+impl JetStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn entry(&mut self, stack: &mut NockStack, path: Noun) -> &mut JetStat {
+        let cord = path_to_cord(stack, path);
+        let key = &cord.as_bytes()[..met3_usize(cord)];
+        if !self.stats.contains_key(key) {
+            permit_alloc(|| {
+                self.stats.insert(
+                    key.to_vec(),
+                    JetStat {
+                        name: cord_to_string(cord),
+                        ..JetStat::default()
+                    },
+                )
+            });
+        }
+        self.stats
+            .get_mut(key)
+            .expect("serf: jet stats: missing entry")
+    }
+
+    /// Count a call of the jet at `path`, which took `time` to produce `result`
+    pub fn jet(&mut self, stack: &mut NockStack, path: Noun, result: &JetResult, time: Duration) {
+        let stat = self.entry(stack, path);
+        match result {
+            Ok(_) => stat.hits += 1,
+            Err(JetErr::Punt) => stat.punts += 1,
+            Err(JetErr::Fail(_)) => stat.fails += 1,
+        }
+        stat.time += time;
+        self.last = Some(Instant::now());
+    }
+
+    /// Count a call into the core at `path`, which has no jet
+    pub fn unjetted(&mut self, stack: &mut NockStack, path: Noun) {
+        self.entry(stack, path).unjetted += 1;
+    }
+
+    /// Give the time since time was last given to the raw Nock of the innermost call on the trace
+    /// stack, before a call is made, or a jet is called, from it
+    ///
+    /// # Safety
+    ///
+    /// `trace_stack` must be the trace stack of the current frame.
+    pub unsafe fn lap(&mut self, stack: &mut NockStack, trace_stack: *const TraceStack) {
+        let now = Instant::now();
+        if let (Some(last), false) = (self.last, trace_stack.is_null()) {
+            let time = now.saturating_duration_since(last);
+            self.entry(stack, (*trace_stack).path).time += time;
+        }
+        self.last = Some(now);
+    }
+
+    /// Give the raw Nock of the innermost call on the trace stack its time, if the call was made
+    /// in the current frame, which is returning. Only the innermost call is ever timed, so a path
+    /// which recurses is not counted once for each of its calls.
+    ///
+    /// # Safety
+    ///
+    /// `trace_stack` must be the trace stack of the current frame.
+    pub unsafe fn time_calls(&mut self, stack: &mut NockStack, trace_stack: *const TraceStack) {
+        if !trace_stack.is_null() && stack.is_in_frame(trace_stack) {
+            self.lap(stack, trace_stack);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&JetStat> {
+        self.stats.values().find(|stat| stat.name == name)
+    }
+
+    /// The statistics, most time first
+    pub fn sorted(&self) -> Vec<&JetStat> {
+        let mut stats: Vec<_> = self.stats.values().collect();
+        stats.sort_by(|a, b| b.time.cmp(&a.time).then(a.name.cmp(&b.name)));
+        stats
+    }
+
+    /// The report, a line for each path, most time first
+    pub fn report(&self) -> Vec<String> {
+        self.sorted()
+            .into_iter()
+            .map(|stat| {
+                format!(
+                    "{}: {} hits, {} punts, {} fails, {} unjetted, {}us",
+                    stat.name,
+                    stat.hits,
+                    stat.punts,
+                    stat.fails,
+                    stat.unjetted,
+                    stat.time.as_micros()
+                )
+            })
+            .collect()
+    }
+
+    /// Write the statistics to the trace file, as counters at the current time
+    pub fn write_trace(&self, info: &mut TraceInfo) -> Result<(), Error> {
+        for stat in self.sorted() {
+            info.counters(
+                Category::Jet,
+                &stat.name,
+                &[
+                    ("hits", stat.hits),
+                    ("punts", stat.punts),
+                    ("fails", stat.fails),
+                    ("unjetted", stat.unjetted),
+                    ("us", stat.time.as_micros() as u64),
+                ],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Print the report on SIGUSR1
+    pub fn report_on_signal() -> Result<(), Error> {
+        unsafe {
+            signal_hook::low_level::register(SIGUSR1, || REPORT.store(true, Ordering::Relaxed))
+        }?;
+        Ok(())
+    }
+
+    /// Whether the report was asked for since this was last called
+    pub fn report_requested() -> bool {
+        REPORT.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Hit counters for the %live hint, by name
+#[derive(Default)]
+pub struct LiveCounters(HashMap<Vec<u8>, u64>);
+
+impl LiveCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a hit on the counter named by `name`, a cord of any length
+    pub fn hit(&mut self, name: Atom) {
+        let name = &name.as_bytes()[..met3_usize(name)];
+        if let Some(hits) = self.0.get_mut(name) {
+            *hits += 1;
         } else {
-            trace_path = prospective_path.clone();
-            break;
+            permit_alloc(|| self.0.insert(name.to_vec(), 1));
         }
     }
 
-    let file = File::create(trace_path)?;
-    let process_start = Instant::now();
-    let pid = std::process::id();
+    pub fn get(&self, name: &[u8]) -> u64 {
+        self.0.get(name).copied().unwrap_or(0)
+    }
+
+    /// Counters by name, most hits first
+    pub fn sorted(&self) -> Vec<(&[u8], u64)> {
+        let mut counters: Vec<_> = self.0.iter().map(|(k, v)| (&k[..], *v)).collect();
+        counters.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counters
+    }
+}
+
+pub fn create_trace_file(pier_path: PathBuf, config: TraceConfig) -> Result<TraceInfo, Error> {
+    let ext = match config.format {
+        TraceFormat::Chrome => "json",
+        TraceFormat::Perfetto => "pftrace",
+    };
+    let file = create_put_file(pier_path, "trace", ext)?;
+    TraceInfo::new(file, config)
+}
+
+/// Create the first unused `<n>.<ext>` in `.urb/put/<dir>` of the pier
+fn create_put_file(pier_path: PathBuf, dir: &str, ext: &str) -> Result<File, Error> {
+    let mut put_dir_path = pier_path;
+    put_dir_path.push(".urb");
+    put_dir_path.push("put");
+    put_dir_path.push(dir);
+    create_dir_all(&put_dir_path)?;
 
-    Ok(TraceInfo {
-        file,
-        pid,
-        process_start,
-    })
+    let mut idx = 0u32;
+    loop {
+        let mut prospective_path = put_dir_path.clone();
+        prospective_path.push(format!("{}.{}", idx, ext));
+
+        if prospective_path.exists() {
+            idx += 1;
+        } else {
+            return File::create(prospective_path);
+        }
+    }
 }
 
 /// Write metadata to trace file
 pub fn write_metadata(info: &mut TraceInfo) -> Result<(), Error> {
-    info.file.write_all("[ ".as_bytes())?;
-
-    (object! {
-        name: "process_name",
-        ph: "M",
-        pid: info.pid,
-        args: object! { name: "urbit", },
-    })
-    .write(&mut info.file)?;
-    info.file.write_all(",\n".as_bytes())?;
-
-    (object! {
-        name: "thread_name",
-        ph: "M",
-        pid: info.pid,
-        tid: 1,
-        args: object!{ name: "Event Processing", },
-    })
-    .write(&mut info.file)?;
-    info.file.write_all(",\n".as_bytes())?;
-
-    (object! {
-        name: "thread_sort_index",
-        ph: "M",
-        pid: info.pid,
-        tid: 1,
-        args: object!{ sort_index: 1, },
-    })
-    .write(&mut info.file)?;
-    info.file.write_all(",\n".as_bytes())?;
-
-    Ok(())
+    match info.config.format {
+        TraceFormat::Chrome => {
+            info.write_json(object! {
+                name: "process_name",
+                ph: "M",
+                pid: info.pid,
+                args: object! { name: "urbit", },
+            })?;
+            info.write_json(object! {
+                name: "thread_name",
+                ph: "M",
+                pid: info.pid,
+                tid: 1,
+                args: object!{ name: "Event Processing", },
+            })?;
+            info.write_json(object! {
+                name: "thread_sort_index",
+                ph: "M",
+                pid: info.pid,
+                tid: 1,
+                args: object!{ sort_index: 1, },
+            })?;
+        }
+        TraceFormat::Perfetto => {
+            let process = Proto::default()
+                .uint(1, info.pid as u64)
+                .bytes(6, "urbit".as_bytes());
+            info.write_packet(Proto::default().message(
+                PACKET_TRACK_DESCRIPTOR,
+                Proto::default().uint(1, PROCESS_TRACK).message(3, process),
+            ))?;
+            let thread = Proto::default()
+                .uint(1, info.pid as u64)
+                .uint(2, 1)
+                .bytes(5, "Event Processing".as_bytes());
+            info.write_packet(
+                Proto::default().message(
+                    PACKET_TRACK_DESCRIPTOR,
+                    Proto::default()
+                        .uint(1, THREAD_TRACK)
+                        .uint(5, PROCESS_TRACK)
+                        .message(4, thread),
+                ),
+            )?;
+        }
+    }
+    info.flush()
 }
 
 /// Abort writing to trace file if an error is encountered.
 ///
 /// This should result in a well-formed partial trace file.
-pub fn write_serf_trace_safe(context: &mut Context, name: &str, start: Instant) {
-    if let Err(e) = write_serf_trace(context.trace_info.as_mut().unwrap(), name, start) {
+pub fn write_serf_trace_safe(context: &mut Context, cat: Category, name: &str, start: Instant) {
+    if let Err(e) = write_serf_trace(context.trace_info.as_mut().unwrap(), cat, name, start) {
         flog!(
             context,
             "\rserf: error writing event trace to file: {:?}",
@@ -111,27 +876,53 @@ pub fn write_serf_trace_safe(context: &mut Context, name: &str, start: Instant)
     }
 }
 
-pub fn write_serf_trace(info: &mut TraceInfo, name: &str, start: Instant) -> Result<(), Error> {
-    let ts = start
-        .saturating_duration_since(info.process_start)
-        .as_micros() as f64;
-    let dur = Instant::now().saturating_duration_since(start).as_micros() as f64;
+/// Write the preserves the NockStack has traced since the last call, as spans of category gc
+/// annotated with the depth of the frame preserved from and the cells and bytes copied
+pub fn write_preserve_traces(context: &mut Context) {
+    let spans = context.stack.take_preserve_spans();
+    let Some(info) = context.trace_info.as_mut() else {
+        return;
+    };
+    for span in spans {
+        let args = [
+            ("depth", span.depth as u64),
+            ("cells", span.cells),
+            ("bytes", span.bytes),
+        ];
+        if let Err(e) = info.span_with_args(Category::Gc, "preserve", span.start, span.time, &args)
+        {
+            flog!(
+                context,
+                "\rserf: error writing preserve trace to file: {:?}",
+                e
+            );
+            context.trace_info = None;
+            return;
+        }
+    }
+}
 
-    assert_no_alloc::permit_alloc(|| {
-        let obj = object! {
-            cat: "event",
-            name: name,
-            ph: "X",
-            pid: info.pid,
-            tid: 1,
-            ts: ts,
-            dur: dur,
-        };
-        obj.write(&mut info.file)
-    })?;
-    info.file.write_all(",\n".as_bytes())?;
+pub fn write_serf_trace(
+    info: &mut TraceInfo,
+    cat: Category,
+    name: &str,
+    start: Instant,
+) -> Result<(), Error> {
+    info.span(cat, name, start)
+}
 
-    Ok(())
+/// Write a span for a jet call into the core at `path`
+pub fn write_jet_trace(
+    stack: &mut NockStack,
+    info: &mut TraceInfo,
+    path: Noun,
+    start: Instant,
+) -> Result<(), Error> {
+    if !info.config.records(Category::Jet) || start.elapsed() < info.config.threshold {
+        return Ok(());
+    }
+    let name = cord_to_string(path_to_cord(stack, path));
+    info.span(Category::Jet, &name, start)
 }
 
 pub unsafe fn write_nock_trace(
@@ -139,20 +930,17 @@ pub unsafe fn write_nock_trace(
     info: &mut TraceInfo,
     mut trace_stack: *const TraceStack,
 ) -> Result<(), Error> {
+    if !info.config.records(Category::Nock) {
+        return Ok(());
+    }
     let now = Instant::now();
 
-    while !trace_stack.is_null() {
-        let ts = (*trace_stack)
-            .start
-            .saturating_duration_since(info.process_start)
-            .as_micros() as f64;
-        let dur = now
-            .saturating_duration_since((*trace_stack).start)
-            .as_micros() as f64;
-
-        // Don't write out traces less than 33us
-        // (same threshhold used in vere)
-        if dur < 33.0 {
+    // Entries below this frame's belong to the frames it is nested in
+    while !trace_stack.is_null() && stack.is_in_frame(trace_stack) {
+        let start = (*trace_stack).start;
+
+        // Don't write out traces under the threshold
+        if now.saturating_duration_since(start) < info.config.threshold {
             trace_stack = (*trace_stack).next;
             continue;
         }
@@ -168,19 +956,7 @@ pub unsafe fn write_nock_trace(
             }
         };
 
-        assert_no_alloc::permit_alloc(|| {
-            let obj = object! {
-                cat: "nock",
-                name: pc_str,
-                ph: "X",
-                pid: info.pid,
-                tid: 1,
-                ts: ts,
-                dur: dur,
-            };
-            obj.write(&mut info.file)
-        })?;
-        info.file.write_all(",\n".as_bytes())?;
+        info.span(Category::Nock, pc_str, start)?;
 
         trace_stack = (*trace_stack).next;
     }
@@ -260,3 +1036,190 @@ pub fn path_to_cord(stack: &mut NockStack, path: Noun) -> Atom {
 
     unsafe { deres.normalize_as_atom() }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn trace_to_file(name: &str, config: TraceConfig) -> (TraceInfo, PathBuf) {
+        let path = std::env::temp_dir().join(format!("ares-{}-{}", name, std::process::id()));
+        let info = TraceInfo::new(File::create(&path).unwrap(), config).unwrap();
+        (info, path)
+    }
+
+    fn read_back(path: &Path) -> Vec<u8> {
+        let bytes = std::fs::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        bytes
+    }
+
+    fn varint(bytes: &[u8], at: &mut usize) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = bytes[*at];
+            *at += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    #[test]
+    fn profile_written_on_drop() {
+        let path = std::env::temp_dir().join(format!("ares-profile-{}", std::process::id()));
+        let mut profile = Profile::new(Some(File::create(&path).unwrap()));
+        profile.stacks.insert("nock;/add".to_string(), 2);
+        profile.flush().unwrap();
+        assert!(std::fs::read(&path).unwrap().is_empty());
+        drop(profile);
+        assert_eq!(read_back(&path), b"nock;/add 2\n");
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = TraceConfig::parse("").unwrap();
+        assert_eq!(config, TraceConfig::default());
+        assert_eq!(config.threshold, Duration::from_micros(33));
+        assert!(Category::ALL.iter().all(|cat| config.records(*cat)));
+        assert!(config.matches("/add/one"));
+
+        let config = TraceConfig::parse(
+            "threshold=5, categories=nock+gc, prefix=/add, format=perfetto, flush=250",
+        )
+        .unwrap();
+        assert_eq!(config.threshold, Duration::from_micros(5));
+        assert!(config.records(Category::Nock) && config.records(Category::Gc));
+        assert!(!config.records(Category::Event) && !config.records(Category::Jet));
+        assert!(config.matches("/add/one") && !config.matches("/dec"));
+        assert_eq!(config.format, TraceFormat::Perfetto);
+        assert_eq!(config.flush_interval, Duration::from_millis(250));
+
+        for bad in [
+            "threshold",
+            "threshold=soon",
+            "categories=cpu",
+            "format=xml",
+            "depth=3",
+        ] {
+            assert!(TraceConfig::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn chrome_trace() {
+        let config =
+            TraceConfig::parse("threshold=0,categories=event+nock+jet,prefix=/add").unwrap();
+        let (mut info, path) = trace_to_file("chrome-trace", config);
+        write_metadata(&mut info).unwrap();
+        let start = Instant::now();
+        info.span(Category::Event, "boot", start).unwrap();
+        info.span(Category::Nock, "/add/one", start).unwrap();
+        // Filtered out by path, and by category
+        info.span(Category::Nock, "/dec", start).unwrap();
+        info.span(Category::Gc, "preserve", start).unwrap();
+        info.counters(Category::Jet, "/add/one", &[("hits", 2)])
+            .unwrap();
+        // Closes the array
+        drop(info);
+
+        let trace = json::parse(&String::from_utf8(read_back(&path)).unwrap()).unwrap();
+        assert!(trace.is_array());
+        assert_eq!(trace.len(), 6);
+        assert_eq!(trace[0]["name"], "process_name");
+        assert_eq!(trace[3]["cat"], "event");
+        assert_eq!(trace[3]["name"], "boot");
+        assert_eq!(trace[3]["ph"], "X");
+        assert_eq!(trace[4]["cat"], "nock");
+        assert_eq!(trace[4]["name"], "/add/one");
+        assert_eq!(trace[5]["ph"], "C");
+        assert_eq!(trace[5]["args"]["hits"], 2);
+    }
+
+    #[test]
+    fn preserve_trace() {
+        use crate::noun::{CellMemory, D, T};
+
+        let mut stack = NockStack::new(1 << 20, 0);
+        stack.trace_preserves(Some(Duration::ZERO));
+        stack.frame_push(0);
+        let mut noun = T(&mut stack, &[D(1), D(2), D(3)]);
+        unsafe {
+            stack.preserve(&mut noun);
+            stack.frame_pop();
+        }
+
+        let cell = size_of::<CellMemory>() as u64;
+        let stats = stack.preserve_stats();
+        assert_eq!(
+            (stats.preserves, stats.cells, stats.bytes),
+            (1, 2, 2 * cell)
+        );
+        let spans = stack.take_preserve_spans();
+        assert_eq!(spans.len(), 1);
+        assert_eq!((spans[0].depth, spans[0].cells), (2, 2));
+
+        let config = TraceConfig::parse("threshold=0,categories=gc").unwrap();
+        let (mut info, path) = trace_to_file("preserve-trace", config);
+        let span = spans[0];
+        let args = [("depth", span.depth as u64), ("bytes", span.bytes)];
+        info.span_with_args(Category::Gc, "preserve", span.start, span.time, &args)
+            .unwrap();
+        drop(info);
+
+        let trace = json::parse(&String::from_utf8(read_back(&path)).unwrap()).unwrap();
+        assert_eq!(trace[0]["cat"], "gc");
+        assert_eq!(trace[0]["args"]["depth"], 2);
+        assert_eq!(trace[0]["args"]["bytes"], 2 * cell);
+    }
+
+    #[test]
+    fn trace_threshold() {
+        let config = TraceConfig::parse("threshold=1000000").unwrap();
+        let (mut info, path) = trace_to_file("trace-threshold", config);
+        let start = Instant::now();
+        info.span(Category::Nock, "/add/one", start).unwrap();
+        // Events are kept however short
+        info.span(Category::Event, "boot", start).unwrap();
+        info.finish().unwrap();
+
+        let trace = json::parse(&String::from_utf8(read_back(&path)).unwrap()).unwrap();
+        assert_eq!(trace.len(), 1);
+        assert_eq!(trace[0]["name"], "boot");
+    }
+
+    #[test]
+    fn perfetto_trace() {
+        let config = TraceConfig::parse("threshold=0,format=perfetto").unwrap();
+        let (mut info, path) = trace_to_file("perfetto-trace", config);
+        write_metadata(&mut info).unwrap();
+        info.span(Category::Nock, "/add/one", Instant::now())
+            .unwrap();
+        info.counters(Category::Jet, "/add/one", &[("hits", 2)])
+            .unwrap();
+        drop(info);
+
+        // A Trace message of packets: two track descriptors, the slice's beginning and end, and
+        // the counters
+        let bytes = read_back(&path);
+        let mut at = 0;
+        let mut packets = Vec::new();
+        while at < bytes.len() {
+            assert_eq!(varint(&bytes, &mut at), 1 << 3 | 2);
+            let len = varint(&bytes, &mut at) as usize;
+            packets.push(&bytes[at..at + len]);
+            at += len;
+        }
+        assert_eq!(at, bytes.len());
+        assert_eq!(packets.len(), 5);
+        let contains =
+            |packet: &[u8], needle: &[u8]| packet.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(packets[0], b"urbit"));
+        assert!(contains(packets[1], b"Event Processing"));
+        assert!(contains(packets[2], b"/add/one"));
+        assert!(contains(packets[4], b"hits"));
+    }
+}
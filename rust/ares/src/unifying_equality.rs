@@ -1,6 +1,5 @@
 use crate::assert_acyclic;
 use crate::assert_no_forwarding_pointers;
-use crate::assert_no_junior_pointers;
 use crate::mem::{NockStack, ALLOC, FRAME, STACK};
 use crate::noun::Noun;
 use crate::persist::{pma_contains, pma_dirty};
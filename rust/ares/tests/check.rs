@@ -0,0 +1,55 @@
+mod common;
+
+use common::*;
+use std::fs;
+
+#[test]
+fn check_reads_a_sound_pier() {
+    let pier = temp_pier("check-sound");
+    unpack_pier(BABY_V1, &pier);
+
+    // The version 1 snapshot is checked as it is, without being migrated or written
+    let before = pma(&pier);
+    let (ok, out) = ares("check", &pier);
+    assert!(ok, "{}", out);
+    assert!(
+        out.contains("check: current snapshot, event 2: ok\n"),
+        "{}",
+        out
+    );
+    assert!(pma(&pier) == before);
+
+    // and once the serf has migrated it, it checks at the current version
+    assert_eq!(ripe(&pier), (2, 0x57d6b64f));
+    let (ok, out) = ares("check", &pier);
+    assert!(ok, "{}", out);
+    assert!(
+        out.contains("check: current snapshot, event 2: ok\n"),
+        "{}",
+        out
+    );
+
+    fs::remove_dir_all(&pier).unwrap();
+}
+
+#[test]
+fn check_finds_a_corrupt_pier() {
+    let pier = temp_pier("check-corrupt");
+    unpack_pier(BABY_V1, &pier);
+
+    // Scribble over the depth and root of the B-tree in both meta pages, past their 256-byte
+    // headers
+    let mut bytes = pma(&pier);
+    for page in 0..2 {
+        let at = page * PAGE_SIZE + 256 + 64;
+        bytes[at..at + 64].fill(0xa5);
+    }
+    fs::write(pma_path(&pier), &bytes).unwrap();
+
+    let (ok, out) = ares("check", &pier);
+    assert!(!ok, "{}", out);
+    assert!(out.contains("problems in the PMA"), "{}", out);
+    assert!(pma(&pier) == bytes);
+
+    fs::remove_dir_all(&pier).unwrap();
+}
@@ -0,0 +1,139 @@
+//! Helpers shared by the integration tests, which run the `ares` binary on piers unpacked from
+//! the fixtures in resources/snapshots
+#![allow(dead_code)]
+
+use ares::cli::read_atom;
+use ares::mem::NockStack;
+use ares::newt::{read_frame, write_frame};
+use ares::noun::{Noun, Slots};
+use ares::serialization::{cue, jam};
+use std::convert::TryInto;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// The PMA written by `ares boot resources/pills/baby.pill` at snapshot version 1, which booted to
+/// event 2 with mug 57d6b64f. Only its nonzero pages are kept: the length of the PMA as a
+/// little-endian u64, then for each page its offset as a little-endian u64 and its contents.
+pub const BABY_V1: &str = "../../resources/snapshots/baby-v1.pma";
+
+/// The pill the fixtures were booted from
+pub const BABY_PILL: &str = "../../resources/pills/baby.pill";
+
+pub const PAGE_SIZE: usize = 1 << 14;
+
+/// The path of a file in the repository, given relative to this crate
+pub fn resource(path: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join(path)
+}
+
+/// Write out a pier holding the PMA in the fixture at `fixture`
+pub fn unpack_pier(fixture: &str, pier: &Path) {
+    let packed = fs::read(resource(fixture)).unwrap();
+    let word = |at: usize| u64::from_le_bytes(packed[at..at + 8].try_into().unwrap()) as usize;
+
+    let mut pma = vec![0u8; word(0)];
+    let mut at = 8;
+    while at < packed.len() {
+        let offset = word(at);
+        pma[offset..offset + PAGE_SIZE].copy_from_slice(&packed[at + 8..at + 8 + PAGE_SIZE]);
+        at += 8 + PAGE_SIZE;
+    }
+
+    let chk = pier.join(".urb").join("chk");
+    fs::create_dir_all(&chk).unwrap();
+    File::create(chk.join("data.pma"))
+        .unwrap()
+        .write_all(&pma)
+        .unwrap();
+}
+
+pub fn pma_path(pier: &Path) -> PathBuf {
+    pier.join(".urb").join("chk").join("data.pma")
+}
+
+pub fn pma(pier: &Path) -> Vec<u8> {
+    fs::read(pma_path(pier)).unwrap()
+}
+
+/// A fresh pier path in the temporary directory, named for `name` and this process
+pub fn temp_pier(name: &str) -> PathBuf {
+    let pier = std::env::temp_dir().join(format!("ares-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&pier);
+    pier
+}
+
+/// Start the serf on a pier, as the king does, send it `writs`, and return the pleas it sends
+/// back, starting with its %ripe, once it has handled them all
+pub fn session(stack: &mut NockStack, pier: &Path, writs: &[Noun]) -> Vec<Noun> {
+    let mut serf = Command::new(env!("CARGO_BIN_EXE_ares"))
+        .arg("serf")
+        .arg(pier)
+        .args(["0", "0", "0", "25"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut input = serf.stdin.take().unwrap();
+    for writ in writs {
+        let jammed = jam(stack, *writ);
+        let len = (jammed.bit_size() + 7) >> 3;
+        write_frame(&mut input, 0, &jammed.as_bytes()[..len]).unwrap();
+    }
+    drop(input);
+
+    let mut pleas = Vec::new();
+    while let Some((_, jammed)) = read_frame(stack, serf.stdout.as_mut().unwrap()).unwrap() {
+        pleas.push(cue(stack, jammed));
+    }
+    assert!(serf.wait().unwrap().success());
+    pleas
+}
+
+/// Start the serf on a pier, as the king does, and return the event number and mug of its %ripe
+pub fn ripe(pier: &Path) -> (u64, u64) {
+    let mut stack = NockStack::new(1 << 20, 0);
+    let pleas = session(&mut stack, pier, &[]);
+
+    // [%ripe [pro hon nok] eve mug]
+    let atom = |axis| {
+        pleas[0]
+            .slot(axis)
+            .unwrap()
+            .as_atom()
+            .unwrap()
+            .as_u64()
+            .unwrap()
+    };
+    (atom(14), atom(15))
+}
+
+/// The boot events of the baby pill
+pub fn baby_boot_events(stack: &mut NockStack) -> Noun {
+    let pill_atom = read_atom(stack, &fs::read(resource(BABY_PILL)).unwrap());
+    let pill = cue(stack, pill_atom);
+    // [%pill nam=term boot-ova=(list) kernel-ova=(list) userspace-ova=(list)]
+    pill.slot(14).unwrap()
+}
+
+/// Run `ares <command> <pier>`, returning whether it succeeded and what it printed
+pub fn ares(command: &str, pier: &Path) -> (bool, String) {
+    run(&[command.as_ref(), pier.as_os_str()])
+}
+
+/// Run `ares` with some arguments and a small loom, returning whether it succeeded and what it
+/// printed
+pub fn run(args: &[&OsStr]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_ares"))
+        .args(args)
+        .env("ARES_LOOM", "25")
+        .output()
+        .unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
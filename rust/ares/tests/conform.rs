@@ -0,0 +1,67 @@
+mod common;
+
+use ares::mem::NockStack;
+use ares::newt::write_frame;
+use ares::noun::{Noun, D, T};
+use ares::serialization::jam;
+use ares_macros::tas;
+use common::*;
+use std::fs;
+use std::path::Path;
+
+/// Write `nouns` as a stream of newt frames, as vere's king and serf write them to each other
+fn write_stream(stack: &mut NockStack, path: &Path, nouns: &[Noun]) {
+    let mut bytes = Vec::new();
+    for noun in nouns {
+        let jammed = jam(stack, *noun);
+        let len = (jammed.bit_size() + 7) >> 3;
+        write_frame(&mut bytes, 0, &jammed.as_bytes()[..len]).unwrap();
+    }
+    fs::write(path, bytes).unwrap();
+}
+
+/// Check with `ares conform` a session of a king booting the baby pill on a serf which said it
+/// reached `mug`
+fn conform(name: &str, mug: u64) -> (bool, String) {
+    let mut stack = NockStack::new(64 << 10 << 10, 0);
+    let stack = &mut stack;
+    let bot = baby_boot_events(stack);
+
+    let dir = temp_pier(name);
+    fs::create_dir_all(&dir).unwrap();
+    let (writs, pleas, pier) = (dir.join("writs"), dir.join("pleas"), dir.join("pier"));
+
+    //  [%play eve=@ lit=(list ovum)]
+    let play = T(stack, &[D(tas!(b"play")), D(1), bot]);
+    write_stream(stack, &writs, &[play]);
+    //  [%ripe [pro hon nok] eve mug], with the mug of the empty Arvo, 0, then [%play %done mug]
+    let version = T(stack, &[D(1), D(138), D(4)]);
+    let ripe = T(stack, &[D(tas!(b"ripe")), version, D(0), D(0x79ff04e8)]);
+    let slog = T(stack, &[D(tas!(b"slog")), D(0), D(tas!(b"hi"))]);
+    let done = T(stack, &[D(tas!(b"play")), D(tas!(b"done")), D(mug)]);
+    write_stream(stack, &pleas, &[ripe, slog, done]);
+
+    let res = run(&[
+        "conform".as_ref(),
+        writs.as_os_str(),
+        pleas.as_os_str(),
+        pier.as_os_str(),
+    ]);
+    fs::remove_dir_all(&dir).unwrap();
+    res
+}
+
+#[test]
+fn conform_to_a_vere_session() {
+    assert_eq!(
+        conform("conform-match", 0x57d6b64f),
+        (true, "conform: 2 pleas match\n".to_string())
+    );
+}
+
+#[test]
+fn conform_finds_a_mismatch() {
+    let (ok, out) = conform("conform-mismatch", 0x57d6b64e);
+    assert!(!ok, "{}", out);
+    assert!(out.starts_with("conform: plea 1: expected "), "{}", out);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use ares::cli::read_atom;
+use ares::mem::NockStack;
+use ares::mug::mug_u32;
+use ares::noun::{Noun, Slots, D, T};
+use ares::serialization::{cue, jam};
+use ares::unifying_equality::unifying_equality;
+use ares_macros::tas;
+use common::*;
+use std::fs;
+use std::path::Path;
+
+fn write_jam(stack: &mut NockStack, path: &Path, noun: Noun) {
+    let jammed = jam(stack, noun);
+    let len = (jammed.bit_size() + 7) >> 3;
+    fs::write(path, &jammed.as_bytes()[..len]).unwrap();
+}
+
+/// Three `(pair @da ovum)` events, `[now //term/1 %belt %txt ~[n]]`
+fn events(stack: &mut NockStack) -> Vec<Noun> {
+    (1..=3)
+        .map(|n| {
+            let wire = T(stack, &[D(0), D(tas!(b"term")), D(b'1' as u64), D(0)]);
+            let card = T(stack, &[D(tas!(b"belt")), D(tas!(b"txt")), D(n), D(0)]);
+            T(stack, &[D(0x1000 + n), wire, card])
+        })
+        .collect()
+}
+
+/// Export the state of a pier, as `[%snap epoch eve arvo cold]`
+fn export(stack: &mut NockStack, pier: &Path) -> Noun {
+    let snap = pier.with_extension("jam");
+    let (ok, out) = run(&["export".as_ref(), pier.as_os_str(), snap.as_os_str()]);
+    assert!(ok, "{}", out);
+    let atom = read_atom(stack, &fs::read(&snap).unwrap());
+    cue(stack, atom)
+}
+
+/// Replay the events on the baby pill, written out by `write_events`, and check the state it
+/// leaves in the pier
+fn replay(name: &str, write_events: fn(&mut NockStack, &Path, &[Noun])) {
+    let mut stack = NockStack::new(64 << 10 << 10, 0);
+    let stack = &mut stack;
+    let dir = temp_pier(name);
+    fs::create_dir_all(&dir).unwrap();
+    let (events_path, booted, replayed) = (dir.join("events"), dir.join("boot"), dir.join("play"));
+    let pill = resource(BABY_PILL);
+
+    let evs = events(stack);
+    write_events(stack, &events_path, &evs);
+    let (ok, out) = run(&[
+        "replay".as_ref(),
+        pill.as_os_str(),
+        events_path.as_os_str(),
+        replayed.as_os_str(),
+    ]);
+    assert_eq!((ok, out.as_str()), (true, "replay: event 5 mug 57d6b64f\n"));
+    // Each event was timed
+    assert!(replayed.join(".urb/put/trace/0.json").is_file());
+    let (ok, out) = run(&["boot".as_ref(), pill.as_os_str(), booted.as_os_str()]);
+    assert_eq!((ok, out.as_str()), (true, "boot: event 2 mug 57d6b64f\n"));
+
+    // The baby pill's Arvo ignores its events, so the pier holds the state it booted to, after
+    // the two boot events and the three replayed
+    let snap = export(stack, &replayed);
+    let boot = export(stack, &booted);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(snap.slot(14).unwrap().as_direct().unwrap().data(), 5);
+    let mut arvo = snap.slot(30).unwrap();
+    let mut boot_arvo = boot.slot(30).unwrap();
+    assert_eq!(mug_u32(stack, arvo), 0x57d6b64f);
+    assert!(unsafe { unifying_equality(stack, &mut arvo, &mut boot_arvo) });
+}
+
+#[test]
+fn replay_event_list() {
+    replay("replay-list", |stack, path, evs| {
+        let mut lit = D(0);
+        for ev in evs.iter().rev() {
+            lit = T(stack, &[*ev, lit]);
+        }
+        write_jam(stack, path, lit);
+    });
+}
+
+#[test]
+fn replay_event_directory() {
+    replay("replay-directory", |stack, path, evs| {
+        fs::create_dir_all(path).unwrap();
+        for (idx, ev) in evs.iter().enumerate() {
+            write_jam(stack, &path.join(format!("{:04}.jam", idx)), *ev);
+        }
+    });
+}
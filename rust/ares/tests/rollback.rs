@@ -0,0 +1,56 @@
+mod common;
+
+use ares::mem::NockStack;
+use ares::noun::{Noun, D, T};
+use ares_macros::tas;
+use common::*;
+use std::fs;
+use std::path::Path;
+
+/// The event numbers of the snapshots `ares snapshots` lists, current first
+fn snapshots(pier: &Path) -> Vec<u64> {
+    let (ok, out) = ares("snapshots", pier);
+    assert!(ok, "{}", out);
+    out.lines()
+        .map(|line| {
+            let (_, rest) = line.split_once(": epoch 0 event ").expect(line);
+            rest.split(',').next().unwrap().parse().unwrap()
+        })
+        .collect()
+}
+
+/// Boot the baby pill on the serf, then have it run nine events, saving after each
+fn boot_and_save(stack: &mut NockStack, pier: &Path) -> Vec<Noun> {
+    //  [%play eve=@ lit=(list ovum)]
+    let bot = baby_boot_events(stack);
+    let mut writs = vec![T(stack, &[D(tas!(b"play")), D(1), bot])];
+    for eve in 3..=11 {
+        //  [%work mil=@ job=(pair @da ovum)], then [%live %save eve=@]
+        let wire = T(stack, &[D(0), D(tas!(b"term")), D(b'1' as u64), D(0)]);
+        let job = T(stack, &[D(eve), wire, D(tas!(b"belt")), D(0)]);
+        writs.push(T(stack, &[D(tas!(b"work")), D(0), job]));
+        writs.push(T(stack, &[D(tas!(b"live")), D(tas!(b"save")), D(eve)]));
+    }
+    session(stack, pier, &writs)
+}
+
+#[test]
+fn retain_and_roll_back() {
+    let mut stack = NockStack::new(64 << 10 << 10, 0);
+    let pier = temp_pier("rollback");
+    boot_and_save(&mut stack, &pier);
+
+    // Only the newest eight snapshots are retained, and listing them leaves the pier as it was
+    let before = pma(&pier);
+    assert_eq!(snapshots(&pier), [11, 11, 10, 9, 8, 7, 6, 5, 4]);
+    assert!(pma(&pier) == before);
+
+    // Rolling back drops the newer snapshots, and the serf starts from the earlier event
+    assert!(!run(&["rollback".as_ref(), pier.as_os_str(), "3".as_ref()]).0);
+    let (ok, out) = run(&["rollback".as_ref(), pier.as_os_str(), "7".as_ref()]);
+    assert_eq!((ok, out.as_str()), (true, "rollback: epoch 0 event 7\n"));
+    assert_eq!(snapshots(&pier), [7, 6, 5, 4]);
+    assert_eq!(ripe(&pier), (7, 0x57d6b64f));
+
+    fs::remove_dir_all(&pier).unwrap();
+}
@@ -0,0 +1,25 @@
+mod common;
+
+use common::*;
+use std::fs;
+
+#[test]
+fn version_1_snapshot_loads() {
+    let pier = temp_pier("baby-v1");
+    unpack_pier(BABY_V1, &pier);
+
+    // Inspecting the pier leaves it at version 1
+    let before = pma(&pier);
+    assert!(!ares("snapshots", &pier).0);
+    assert!(pma(&pier) == before);
+
+    // The serf migrates it as it loads, and it then reads at the current version
+    assert_eq!(ripe(&pier), (2, 0x57d6b64f));
+    assert_eq!(
+        ares("snapshots", &pier),
+        (true, "current: epoch 0 event 2\n".to_string())
+    );
+    assert_eq!(ripe(&pier), (2, 0x57d6b64f));
+
+    fs::remove_dir_all(&pier).unwrap();
+}
@@ -367,5 +367,27 @@ int main(int argc, char *argv[])
   /* the partition offset should be the same */
   assert(t4partoff0 == state5->meta_pages[state5->which]->blk_base[1]);
 
+  /* and the tree, spanning both partitions, passes the integrity check */
+  assert(bt_check(state5) == 0);
+  assert(bt_check_file("./pmatest5") == 0);
+  bt_state_close(state5);
+
+
+  DPUTS("== test 6: integrity check of a corrupt metapage");
+  int fd6 = open("./pmatest5/data.pma", O_RDWR);
+  assert(fd6 != -1);
+  BT_page meta6;
+  off_t off6 = 0;
+  /* alter the clean metapage, leaving its checksum stale */
+  assert(pread(fd6, &meta6, sizeof meta6, off6) == sizeof meta6);
+  if (METADATA(&meta6)->chk == 0) {
+    off6 = BT_PAGESIZE;
+    assert(pread(fd6, &meta6, sizeof meta6, off6) == sizeof meta6);
+  }
+  METADATA(&meta6)->txnid ^= 1;
+  assert(pwrite(fd6, &meta6, sizeof meta6, off6) == sizeof meta6);
+  close(fd6);
+  assert(bt_check_file("./pmatest5") > 0);
+
   return 0;
 }
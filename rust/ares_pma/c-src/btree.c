@@ -351,6 +351,7 @@ struct BT_state {
   BT_flistnode *flist;          /* pma file freelist */
   BT_flistnode *pending_flist;
   BT_nlistnode *pending_nlist;
+  int           rdonly;         /* opened with BT_RDONLY */
 };
 
 
@@ -366,6 +367,14 @@ _bt_insertdat(vaof_t lo, vaof_t hi, pgno_t fo,
 
 static int _bt_flip_meta(BT_state *);
 
+/* flags to map the PMA file with: a PMA opened read-only is mapped privately, so
+   that nothing written in memory reaches the file */
+static int
+_bt_file_flags(BT_state *state, int flags)
+{
+  return state->rdonly ? (flags & ~MAP_SHARED) | MAP_PRIVATE : flags;
+}
+
 
 /* TODO: derive BT_MAXDEPTH */
 #ifndef BT_MAXDEPTH
@@ -474,7 +483,7 @@ _nlist_grow(BT_state *state)
   if (targ != mmap(targ,
                    block_len_b,
                    BT_PROT_CLEAN,
-                   BT_FLAG_CLEAN,
+                   _bt_file_flags(state, BT_FLAG_CLEAN),
                    state->data_fd,
                    partoff_b)) {
     DPRINTF("mmap: failed to map node stripe %zu, addr: 0x%p, file offset (bytes): 0x%zX, errno: %s",
@@ -2165,7 +2174,7 @@ _bt_state_restore_maps2(BT_state *state, BT_page *node,
             mmap(loaddr,
                  bytelen,
                  BT_PROT_CLEAN,
-                 BT_FLAG_CLEAN,
+                 _bt_file_flags(state, BT_FLAG_CLEAN),
                  state->data_fd,
                  offset)) {
           DPRINTF("mmap: failed to map at addr %p, errno: %s", loaddr, strerror(errno));
@@ -2416,6 +2425,11 @@ _freelist_restore(BT_state *state)
   assert(SUCC(_flist_new(state, state->file_size_p)));
   assert(SUCC(_nlist_load(state)));
   assert(SUCC(_mlist_new(state)));
+  /* the node partitions are file space too, which data must not be given */
+  for (size_t i = 0; i < BT_NUMPARTS && meta->blk_base[i] != 0; i++) {
+    pgno_t lo = meta->blk_base[i];
+    _flist_record_alloc(state, lo, lo + B2PAGES(BLK_BASE_LENS_b[i]));
+  }
   /* first record root's allocation */
   _nlist_record_alloc(state, root);
   _freelist_restore2(state, root, 1, meta->depth);
@@ -2441,7 +2455,7 @@ _bt_state_map_node_segment(BT_state *state)
     if (targ != mmap(targ,
                      partlen_b,
                      BT_PROT_CLEAN,
-                     BT_FLAG_CLEAN,
+                     _bt_file_flags(state, BT_FLAG_CLEAN),
                      state->data_fd,
                      partoff_b)) {
       DPRINTF("mmap: failed to map node stripe %zu, addr: 0x%p, file offset (bytes): 0x%zX, errno: %s",
@@ -2485,7 +2499,7 @@ _bt_state_load(BT_state *state)
   state->map = mmap(BT_MAPADDR,
                     BT_META_SECTION_WIDTH,
                     BT_PROT_CLEAN,
-                    BT_FLAG_CLEAN,
+                    _bt_file_flags(state, BT_FLAG_CLEAN),
                     state->data_fd,
                     0);
 
@@ -2499,7 +2513,7 @@ _bt_state_load(BT_state *state)
   state->meta_pages[1] = METADATA(p + 1);
 
   if (!SUCC(rc = _bt_state_read_header(state))) {
-    if (rc != ENOENT) return rc;
+    if (rc != ENOENT || state->rdonly) return rc;
     DPUTS("creating new db");
     state->file_size_p = PMA_GROW_SIZE_p;
     new = 1;
@@ -2787,9 +2801,9 @@ bt_state_open(BT_state *state, const char *path, ULONG flags, mode_t mode)
   char *dpath;
 
   TRACE();
-  UNUSED(flags);
 
-  oflags = O_RDWR | O_CREAT;
+  state->rdonly = (flags & BT_RDONLY) != 0;
+  oflags = state->rdonly ? O_RDONLY : O_RDWR | O_CREAT;
   dpath = malloc(strlen(path) + sizeof(DATANAME));
   if (!dpath) return ENOMEM;
   sprintf(dpath, "%s" DATANAME, path);
@@ -2868,7 +2882,7 @@ bt_malloc(BT_state *state, size_t pages)
       mmap(ret,
            P2BYTES(pages),
            BT_PROT_DIRTY,
-           BT_FLAG_DIRTY,
+           _bt_file_flags(state, BT_FLAG_DIRTY),
            state->data_fd,
            P2BYTES(pgno))) {
     DPRINTF("mmap: failed to map at addr %p, errno: %s", ret, strerror(errno));
@@ -2938,6 +2952,9 @@ bt_sync(BT_state *state)
   BT_page *root = _node_get(state, meta->root);
   int rc = 0;
 
+  if (state->rdonly)
+    return BT_SUCC;
+
   /* sync root subtrees */
   if ((rc = _bt_sync(state, root, 1, meta->depth)))
     return rc;
@@ -3094,7 +3111,7 @@ _bt_data_cow(BT_state *state, vaof_t lo, vaof_t hi, pgno_t pg)
       mmap(loaddr,
            bytelen,
            BT_PROT_DIRTY,
-           BT_FLAG_DIRTY,
+           _bt_file_flags(state, BT_FLAG_DIRTY),
            state->data_fd,
            offset)) {
     DPRINTF("mmap: failed to map at addr %p, errno: %s", loaddr, strerror(errno));
@@ -3170,56 +3187,58 @@ bt_dirty(BT_state *state, void *lo, void *hi)
   return _bt_dirty(state, looff, hioff, meta->root, 1, meta->depth);
 }
 
-int
-bt_next_alloc(BT_state *state, void *p, void **lo, void **hi)
-/* if p is free, sets lo and hi to the bounds of the next adjacent allocated
-   space. If p is allocated, sets lo and hi to the bounds of the allocated space
-   it falls in. */
-{
-  BT_mlistnode *head = state->mlist;
-  BYTE *pb = p;
-  BYTE* pma_end;
-  while (head) {
-    /* at last free block, different logic applies */
-    if (head->next == 0)
-      goto end;
-
-    /* p is in a free range, return the allocated hole after it */
-    if (head->lo <= pb
-        && head->hi > pb) {
-      goto found;
-    }
+static int
+_bt_next_alloc(BT_state *state, vaof_t p, vaof_t *lo, vaof_t *hi,
+               pgno_t nodepg, uint8_t depth, uint8_t maxdepth)
+/* find the lowest allocated range in the subtree rooted at nodepg which ends
+   above p */
+{
+  BT_page *node = _node_get(state, nodepg);
+  size_t N = _bt_numkeys(node);
+
+  for (size_t i = 0; i < N-1; i++) {
+    vaof_t llo = node->datk[i].va;
+    vaof_t hhi = node->datk[i+1].va;
+    pgno_t pg = node->datk[i].fo;
 
-    /* p is alloced, return this hole */
-    if (head->next->lo > pb
-        && head->hi <= pb) {
-      goto found;
+    if (hhi <= p || pg == 0)
+      continue;
+
+    if (depth == maxdepth) {
+      *lo = llo;
+      *hi = hhi;
+      return BT_SUCC;
     }
 
-    head = head->next;
+    if (SUCC(_bt_next_alloc(state, p, lo, hi, pg, depth+1, maxdepth)))
+      return BT_SUCC;
   }
 
-  /* not found */
   return 1;
+}
 
- found:
-  /* the alloced space begins at the end of the free block */
-  *lo = head->hi;
-  /* ... and ends at the start of the next free block */
-  *hi = head->next->lo;
-  return BT_SUCC;
+int
+bt_next_alloc(BT_state *state, void *p, void **lo, void **hi)
+/* if p is free, sets lo and hi to the bounds of the next adjacent allocated
+   space. If p is allocated, sets lo and hi to the bounds of the allocated space
+   it falls in.
 
- end:
-  pma_end = (void *)((uintptr_t)BT_MAPADDR + BT_ADDRSIZE);
-  assert(head->hi <= pma_end);
-  /* no alloced region between tail of freelist and end of pma memory space */
-  if (head->hi == pma_end)
+   The btree, rather than the mlist, is walked: the mlist does not describe the
+   allocated space below its first node. */
+{
+  BT_meta *meta = state->meta_pages[state->which];
+  vaof_t poff = 0;
+  vaof_t looff, hioff;
+
+  if ((BYTE *)p >= BT_MAPADDR)
+    poff = (vaof_t)(((uintptr_t)p - (uintptr_t)BT_MAPADDR) >> BT_PAGEBITS);
+
+  if (!SUCC(_bt_next_alloc(state, poff, &looff, &hioff,
+                           meta->root, 1, meta->depth)))
     return 1;
 
-  /* otherwise, return the alloced region between the tail of the freelist and
-     the end of the memory arena */
-  *lo = head->hi;
-  *hi = pma_end;
+  *lo = off2addr(looff);
+  *hi = off2addr(hioff);
   return BT_SUCC;
 }
 
@@ -3238,6 +3257,436 @@ bt_inbounds(BT_state *state, void *p)
     && p < (void *)((uintptr_t)BT_MAPADDR + BT_ADDRSIZE);
 }
 
+void
+bt_usage(BT_state *state, size_t *file_p, size_t *free_p, size_t *pending_p)
+{
+  *file_p = state->file_size_p;
+  *free_p = 0;
+  for (BT_flistnode *n = state->flist; n; n = n->next)
+    *free_p += n->hi - n->lo;
+  *pending_p = 0;
+  for (BT_flistnode *n = state->pending_flist; n; n = n->next)
+    *pending_p += n->hi - n->lo;
+}
+
+
+//// ===========================================================================
+////                              integrity check
+
+/* a half-open range of pages, either virtual (vaof_t) or in the file (pgno_t) */
+typedef struct BT_checkrange BT_checkrange;
+struct BT_checkrange {
+  uint64_t lo;
+  uint64_t hi;
+};
+
+typedef struct BT_checklist BT_checklist;
+struct BT_checklist {
+  BT_checkrange *ranges;
+  size_t len;
+  size_t cap;
+};
+
+/* The tree is checked either through the open state, or, so that corruption
+   which would abort bt_state_open can be reported, by reading nodes from the
+   file with one buffer per level of the tree. */
+typedef struct BT_check BT_check;
+struct BT_check {
+  BT_state *state;              /* open state, or */
+  int fd;                       /* the data file, and */
+  BT_page *bufs;                /* a node buffer per level */
+  BT_meta *meta;                /* current metapage */
+  pgno_t file_size_p;
+  size_t errs;
+  BT_checklist nodes;           /* node pages reached from the root */
+  BT_checklist leaves_va;       /* allocated ranges, in address order */
+  BT_checklist leaves_fo;       /* the file pages backing them */
+  BT_checklist reserved;        /* the metapages and node partitions */
+};
+
+#define CHECK_ERR(chk, fmt, ...)                                \
+  do {                                                          \
+    fprintf(stderr, "bt_check: " fmt "\n", __VA_ARGS__);        \
+    (chk)->errs++;                                              \
+  } while (0)
+
+static void
+_bt_checklist_push(BT_checklist *list, uint64_t lo, uint64_t hi)
+{
+  if (list->len == list->cap) {
+    list->cap = list->cap ? list->cap * 2 : 64;
+    list->ranges = realloc(list->ranges, list->cap * sizeof *list->ranges);
+    if (!list->ranges) {
+      DPUTS("out of memory for integrity check. aborting");
+      abort();
+    }
+  }
+  list->ranges[list->len].lo = lo;
+  list->ranges[list->len].hi = hi;
+  list->len++;
+}
+
+static int
+_bt_checkrange_cmp(const void *a, const void *b)
+{
+  const BT_checkrange *ra = a;
+  const BT_checkrange *rb = b;
+  return (ra->lo > rb->lo) - (ra->lo < rb->lo);
+}
+
+static void
+_bt_checklist_sort(BT_checklist *list)
+{
+  if (list->len)
+    qsort(list->ranges, list->len, sizeof *list->ranges, _bt_checkrange_cmp);
+}
+
+static void
+_bt_check_ordered(BT_check *chk, const char *name, BT_checklist *list)
+/* report empty ranges in a list, and ranges which are out of order or overlap
+   their predecessor */
+{
+  for (size_t i = 0; i < list->len; i++) {
+    BT_checkrange r = list->ranges[i];
+    if (r.lo >= r.hi)
+      CHECK_ERR(chk, "%s: empty range [0x%" PRIX64 ", 0x%" PRIX64 ")",
+                name, r.lo, r.hi);
+    if (i > 0 && list->ranges[i-1].hi > r.lo)
+      CHECK_ERR(chk, "%s: range [0x%" PRIX64 ", 0x%" PRIX64 ") out of order"
+                " with or overlapping [0x%" PRIX64 ", 0x%" PRIX64 ")",
+                name, r.lo, r.hi, list->ranges[i-1].lo, list->ranges[i-1].hi);
+  }
+}
+
+static void
+_bt_check_disjoint(BT_check *chk,
+                   const char *aname, BT_checklist *a,
+                   const char *bname, BT_checklist *b)
+/* report every overlap between two lists of ranges, each sorted by lo */
+{
+  size_t i = 0, j = 0;
+  while (i < a->len && j < b->len) {
+    BT_checkrange ra = a->ranges[i];
+    BT_checkrange rb = b->ranges[j];
+    if (ra.lo < rb.hi && rb.lo < ra.hi)
+      CHECK_ERR(chk, "%s [0x%" PRIX64 ", 0x%" PRIX64 ") overlaps"
+                " %s [0x%" PRIX64 ", 0x%" PRIX64 ")",
+                aname, ra.lo, ra.hi, bname, rb.lo, rb.hi);
+    if (ra.hi <= rb.hi)
+      i++;
+    else
+      j++;
+  }
+}
+
+static pgno_t
+_bt_check_nodefo(BT_meta *meta, pgno_t pg)
+/* node pages are numbered by their offset in the node segment of memory, in
+   which the partitions are contiguous. Returns the file page of node page pg,
+   or 0 if pg does not lie in an allocated partition */
+{
+  pgno_t lo = BT_NUMMETAS;
+  for (size_t i = 0; i < BT_NUMPARTS && meta->blk_base[i] != 0; i++) {
+    pgno_t hi = lo + B2PAGES(BLK_BASE_LENS_b[i]);
+    if (pg >= lo && pg < hi)
+      return meta->blk_base[i] + (pg - lo);
+    lo = hi;
+  }
+  return 0;
+}
+
+static BT_page *
+_bt_check_getnode(BT_check *chk, pgno_t pg, uint8_t depth)
+{
+  if (chk->state)
+    return _node_get(chk->state, pg);
+
+  BT_page *buf = &chk->bufs[depth];
+  off_t off = P2BYTES(_bt_check_nodefo(chk->meta, pg));
+  if (pread(chk->fd, buf, sizeof *buf, off) != sizeof *buf) {
+    CHECK_ERR(chk, "node %" PRIu32 ": could not be read from file offset"
+              " 0x%jX", pg, (intmax_t)off);
+    return 0;
+  }
+  return buf;
+}
+
+static void
+_bt_check_meta(BT_check *chk, BT_meta *meta, int which)
+{
+  if (meta->magic != BT_MAGIC)
+    CHECK_ERR(chk, "metapage %d: bad magic 0x%" PRIX32, which, meta->magic);
+  if (meta->version != BT_VERSION)
+    CHECK_ERR(chk, "metapage %d: version %" PRIu32 ", expected %u",
+              which, meta->version, BT_VERSION);
+  if ((meta->flags & BP_META) != BP_META)
+    CHECK_ERR(chk, "metapage %d: missing meta page flag", which);
+  if (meta->chk != 0) {
+    uint32_t sum = nonzero_crc_32(meta, BT_META_LEN_b);
+    if (sum != meta->chk)
+      CHECK_ERR(chk, "metapage %d: checksum 0x%" PRIX32 " does not match"
+                " contents (0x%" PRIX32 ")", which, meta->chk, sum);
+  }
+  if (meta->fix_addr != BT_MAPADDR)
+    CHECK_ERR(chk, "metapage %d: fixed address %p, expected %p",
+              which, meta->fix_addr, (void *)BT_MAPADDR);
+  if (meta->depth < 1 || meta->depth > BT_MAXDEPTH)
+    CHECK_ERR(chk, "metapage %d: depth %u out of range", which, meta->depth);
+  if (meta->blk_base[0] != BT_NUMMETAS)
+    CHECK_ERR(chk, "metapage %d: first node partition at page %" PRIu32
+              ", expected %u", which, meta->blk_base[0], BT_NUMMETAS);
+  else if (!_bt_check_nodefo(meta, meta->root))
+    CHECK_ERR(chk, "metapage %d: root page %" PRIu32
+              " outside the node partitions", which, meta->root);
+}
+
+static int
+_bt_check_metas(BT_check *chk, BT_meta *m1, BT_meta *m2)
+/* check both metapages, and return which is current as _bt_state_meta_which
+   would choose it, or -1 if neither can be */
+{
+  _bt_check_meta(chk, m1, 0);
+  _bt_check_meta(chk, m2, 1);
+  if (m1->chk == 0 && m2->chk == 0) {
+    CHECK_ERR(chk, "both metapages are dirty (txnid %" PRIu64 " and %" PRIu64 ")",
+              m1->txnid, m2->txnid);
+    return -1;
+  }
+  if (m1->chk != 0 && m2->chk != 0 && m1->txnid == m2->txnid) {
+    CHECK_ERR(chk, "both metapages are clean with txnid %" PRIu64, m1->txnid);
+    return -1;
+  }
+  if (m1->chk == 0)
+    return 1;
+  if (m2->chk == 0)
+    return 0;
+  return m1->txnid > m2->txnid ? 0 : 1;
+}
+
+static void
+_bt_check_node(BT_check *chk, pgno_t pg, vaof_t lo, vaof_t hi,
+               uint8_t depth, uint8_t maxdepth)
+{
+  if (!_bt_check_nodefo(chk->meta, pg)) {
+    CHECK_ERR(chk, "node %" PRIu32 " at depth %u: page outside the node"
+              " partitions", pg, depth);
+    return;
+  }
+  _bt_checklist_push(&chk->nodes, pg, pg + 1);
+
+  BT_page *node = _bt_check_getnode(chk, pg, depth);
+  if (!node)
+    return;
+  size_t N = _bt_numkeys(node);
+  /* the first key of a node may be 0, standing for the bottom of the PMA */
+  vaof_t base = B2PAGES(BLK_BASE_LEN_TOTAL);
+  vaof_t first = node->datk[0].va ? node->datk[0].va : base;
+
+  if (N < 2) {
+    CHECK_ERR(chk, "node %" PRIu32 ": fewer than two keys", pg);
+    return;
+  }
+  if (first != lo || node->datk[N-1].va != hi)
+    CHECK_ERR(chk, "node %" PRIu32 ": keys span [0x%" PRIX32 ", 0x%" PRIX32 ")"
+              ", but its parent gives it [0x%" PRIX32 ", 0x%" PRIX32 ")",
+              pg, first, node->datk[N-1].va, lo, hi);
+
+  for (size_t i = 0; i < N-1; i++) {
+    vaof_t llo = i == 0 ? first : node->datk[i].va;
+    vaof_t hhi = node->datk[i+1].va;
+    pgno_t fo = node->datk[i].fo;
+
+    if (llo >= hhi) {
+      CHECK_ERR(chk, "node %" PRIu32 ": keys %zu and %zu out of order"
+                " (0x%" PRIX32 " >= 0x%" PRIX32 ")", pg, i, i+1, llo, hhi);
+      continue;
+    }
+
+    /* leaf */
+    if (depth == maxdepth) {
+      if (fo == 0)
+        continue;
+      uint64_t fohi = (uint64_t)fo + (hhi - llo);
+      _bt_checklist_push(&chk->leaves_va, llo, hhi);
+      _bt_checklist_push(&chk->leaves_fo, fo, fohi);
+      if (fohi > chk->file_size_p)
+        CHECK_ERR(chk, "leaf %" PRIu32 " key %zu: data pages [0x%" PRIX32
+                  ", 0x%" PRIX64 ") beyond the end of the file (0x%" PRIX32
+                  " pages)", pg, i, fo, fohi, chk->file_size_p);
+      continue;
+    }
+
+    /* branch */
+    if (fo == 0) {
+      CHECK_ERR(chk, "branch %" PRIu32 " key %zu: no child for"
+                " [0x%" PRIX32 ", 0x%" PRIX32 ")", pg, i, llo, hhi);
+      continue;
+    }
+    _bt_check_node(chk, fo, llo, hhi, depth+1, maxdepth);
+  }
+}
+
+static void
+_bt_check_tree(BT_check *chk)
+/* check the tree under the current metapage, which spans all of the address
+   space above the node segment. Each node must be reached once, and no two
+   allocations may share file pages or overlap the metapages or node
+   partitions */
+{
+  BT_meta *meta = chk->meta;
+
+  _bt_check_node(chk, meta->root, B2PAGES(BLK_BASE_LEN_TOTAL), UINT32_MAX,
+                 1, meta->depth);
+
+  _bt_checklist_sort(&chk->nodes);
+  for (size_t i = 1; i < chk->nodes.len; i++) {
+    if (chk->nodes.ranges[i].lo == chk->nodes.ranges[i-1].lo)
+      CHECK_ERR(chk, "node %" PRIu64 " reached more than once",
+                chk->nodes.ranges[i].lo);
+  }
+
+  _bt_checklist_sort(&chk->leaves_fo);
+  _bt_check_ordered(chk, "data pages", &chk->leaves_fo);
+  _bt_checklist_push(&chk->reserved, 0, BT_NUMMETAS);
+  for (size_t i = 0; i < BT_NUMPARTS && meta->blk_base[i] != 0; i++) {
+    pgno_t lo = meta->blk_base[i];
+    _bt_checklist_push(&chk->reserved, lo, lo + B2PAGES(BLK_BASE_LENS_b[i]));
+  }
+  _bt_checklist_sort(&chk->reserved);
+  _bt_check_disjoint(chk, "data pages", &chk->leaves_fo,
+                     "metapages or node partition", &chk->reserved);
+}
+
+static void
+_bt_check_free(BT_check *chk)
+{
+  free(chk->nodes.ranges);
+  free(chk->leaves_va.ranges);
+  free(chk->leaves_fo.ranges);
+  free(chk->reserved.ranges);
+}
+
+int
+bt_check_file(const char *path)
+#define DATANAME "/data.pma"
+{
+  BT_check chk = {0};
+  BT_page metas[BT_NUMMETAS];
+  struct stat st;
+  char *dpath;
+  int which;
+
+  dpath = malloc(strlen(path) + sizeof(DATANAME));
+  if (!dpath) return 1;
+  sprintf(dpath, "%s" DATANAME, path);
+
+  if ((chk.fd = open(dpath, O_RDONLY)) == -1) {
+    CHECK_ERR(&chk, "could not open %s: %s", dpath, strerror(errno));
+    free(dpath);
+    return chk.errs;
+  }
+
+  if (fstat(chk.fd, &st) != 0
+      || pread(chk.fd, metas, sizeof metas, 0) != sizeof metas) {
+    CHECK_ERR(&chk, "could not read the metapages of %s", dpath);
+    goto e;
+  }
+  if (st.st_size % BT_PAGESIZE != 0)
+    CHECK_ERR(&chk, "file size 0x%jX is not a whole number of pages",
+              (intmax_t)st.st_size);
+  chk.file_size_p = st.st_size / BT_PAGESIZE;
+
+  which = _bt_check_metas(&chk, METADATA(&metas[0]), METADATA(&metas[1]));
+  if (chk.errs)
+    goto e;
+  chk.meta = METADATA(&metas[which]);
+
+  chk.bufs = malloc((BT_MAXDEPTH + 1) * sizeof *chk.bufs);
+  if (!chk.bufs) {
+    DPUTS("out of memory for integrity check. aborting");
+    abort();
+  }
+  _bt_check_tree(&chk);
+  free(chk.bufs);
+
+ e:
+  _bt_check_free(&chk);
+  close(chk.fd);
+  free(dpath);
+  return chk.errs;
+}
+#undef DATANAME
+
+int
+bt_check(BT_state *state)
+{
+  BT_check chk = {0};
+  BT_checklist list = {0};
+  chk.state = state;
+  chk.meta = state->meta_pages[state->which];
+  chk.file_size_p = state->file_size_p;
+
+  /* metapages. While open, the current metapage is the dirty one */
+  _bt_check_metas(&chk, state->meta_pages[0], state->meta_pages[1]);
+  if (chk.meta->chk != 0)
+    CHECK_ERR(&chk, "current metapage %u is not dirty", state->which);
+  if (chk.errs)
+    goto e;
+
+  _bt_check_tree(&chk);
+
+  /* free memory: ordered, and not allocated in the tree */
+  for (BT_mlistnode *n = state->mlist; n; n = n->next)
+    _bt_checklist_push(&list, addr2off(n->lo), addr2off(n->hi));
+  _bt_check_ordered(&chk, "mlist", &list);
+  _bt_check_disjoint(&chk, "mlist", &list, "allocation", &chk.leaves_va);
+  list.len = 0;
+
+  /* free file pages, including those pending the next sync: ordered, within
+     the file, and not backing any allocation */
+  for (BT_flistnode *n = state->flist; n; n = n->next)
+    _bt_checklist_push(&list, n->lo, n->hi);
+  _bt_check_ordered(&chk, "flist", &list);
+  if (list.len && list.ranges[list.len-1].hi > state->file_size_p)
+    CHECK_ERR(&chk, "flist: range ends at page 0x%" PRIX64 ", beyond the end"
+              " of the file (0x%" PRIX32 " pages)", list.ranges[list.len-1].hi,
+              state->file_size_p);
+  _bt_check_disjoint(&chk, "flist", &list, "data pages", &chk.leaves_fo);
+  _bt_check_disjoint(&chk, "flist", &list, "metapages or node partition",
+                     &chk.reserved);
+  list.len = 0;
+  for (BT_flistnode *n = state->pending_flist; n; n = n->next)
+    _bt_checklist_push(&list, n->lo, n->hi);
+  _bt_check_ordered(&chk, "pending flist", &list);
+  _bt_check_disjoint(&chk, "pending flist", &list, "data pages", &chk.leaves_fo);
+  list.len = 0;
+
+  /* free node pages: within the node partitions, and not reached from the
+     root */
+  for (BT_nlistnode *n = state->nlist; n; n = n->next)
+    _bt_checklist_push(&list, _fo_get(state, n->lo), _fo_get(state, n->hi));
+  for (BT_nlistnode *n = state->pending_nlist; n; n = n->next)
+    _bt_checklist_push(&list, _fo_get(state, n->lo), _fo_get(state, n->hi));
+  _bt_checklist_sort(&list);
+  _bt_check_ordered(&chk, "nlist", &list);
+  for (size_t i = 0; i < list.len; i++) {
+    if (list.ranges[i].lo >= list.ranges[i].hi)
+      continue;
+    if (!_bt_check_nodefo(chk.meta, list.ranges[i].lo)
+        || !_bt_check_nodefo(chk.meta, list.ranges[i].hi - 1))
+      CHECK_ERR(&chk, "nlist: range [0x%" PRIX64 ", 0x%" PRIX64 ") outside"
+                " the node partitions", list.ranges[i].lo, list.ranges[i].hi);
+  }
+  _bt_check_disjoint(&chk, "nlist", &list, "node", &chk.nodes);
+
+ e:
+  free(list.ranges);
+  _bt_check_free(&chk);
+  return chk.errs;
+}
+
+#undef CHECK_ERR
+
 
 //// ===========================================================================
 ////                                    tests
@@ -19,6 +19,12 @@ typedef unsigned long ULONG;
  */
 int bt_state_new(BT_state **state);
 
+/**
+ * Flag for bt_state_open: open an existing PMA without ever writing to its file.
+ * Changes made in memory are private to the process, and bt_sync does nothing.
+ */
+#define BT_RDONLY 0x1
+
 /**
  * Open the persistent state or create if one doesn't exist
  */
@@ -84,4 +90,26 @@ void bt_bounds(BT_state *state, void **lo, void **hi);
  */
 int bt_inbounds(BT_state *state, void *p);
 
+/**
+ * Return the size of the persistent file in pages, how many of those are free,
+ * and how many more become free at the next sync
+ */
+void bt_usage(BT_state *state, size_t *file_p, size_t *free_p,
+              size_t *pending_p);
+
+/**
+ * Check the metapages and B-tree of the persistent state at `path' by reading
+ * its file, before it is opened. Reports each problem on stderr and returns the
+ * number found
+ */
+int bt_check_file(const char *path);
+
+/**
+ * Check the integrity of open persistent state: both metapages, the ordering
+ * of keys in every B-tree node, and the consistency of the free lists with the
+ * tree. Only the metapages carry checksums: data pages are not checked. Reports
+ * each problem on stderr and returns the number found
+ */
+int bt_check(BT_state *state);
+
 #endif